@@ -0,0 +1,2 @@
+-- Момент постановки парсинга на паузу, NULL - парсинг не на паузе
+ALTER TABLE POST ADD COLUMN IF NOT EXISTS paused_at TIMESTAMP;
@@ -0,0 +1,12 @@
+-- Опросы, пропущенные из-за очереди к VK API: пост не успел получить слот до следующего тика
+CREATE TABLE IF NOT EXISTS POLL_SKIP (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER NOT NULL,
+    skipped_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    reason VARCHAR(255) NOT NULL,
+
+    CONSTRAINT fk_poll_skip_post FOREIGN KEY (post_id)
+        REFERENCES POST(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_poll_skip_post_id ON POLL_SKIP(post_id);
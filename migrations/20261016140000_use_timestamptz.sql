@@ -0,0 +1,19 @@
+-- Время хранится с часовым поясом; старые значения записывались в сессии с TimeZone=UTC
+ALTER TABLE POST DROP CONSTRAINT IF EXISTS no_overlapping_periods;
+
+ALTER TABLE POST
+    ALTER COLUMN dt_parse_begin TYPE TIMESTAMPTZ USING dt_parse_begin AT TIME ZONE 'UTC',
+    ALTER COLUMN dt_parse_end TYPE TIMESTAMPTZ USING dt_parse_end AT TIME ZONE 'UTC',
+    ALTER COLUMN paused_at TYPE TIMESTAMPTZ USING paused_at AT TIME ZONE 'UTC';
+
+-- Периоды парсинга одного поста по-прежнему не пересекаются
+ALTER TABLE POST ADD CONSTRAINT no_overlapping_periods EXCLUDE USING gist (
+    vk_id WITH =,
+    tstzrange(dt_parse_begin, dt_parse_end) WITH &&
+);
+
+ALTER TABLE POST_INFO
+    ALTER COLUMN info_time TYPE TIMESTAMPTZ USING info_time AT TIME ZONE 'UTC';
+
+ALTER TABLE POLL_SKIP
+    ALTER COLUMN skipped_at TYPE TIMESTAMPTZ USING skipped_at AT TIME ZONE 'UTC';
@@ -0,0 +1,6 @@
+-- Счетчики популярных постов (просмотры, репосты) не помещаются в INTEGER
+ALTER TABLE POST_INFO
+    ALTER COLUMN likes_count TYPE BIGINT,
+    ALTER COLUMN comments_count TYPE BIGINT,
+    ALTER COLUMN reposts_count TYPE BIGINT,
+    ALTER COLUMN views_count TYPE BIGINT;
@@ -0,0 +1,19 @@
+-- Содержимое поста из wall.getById: каждая правка сохраняется новой версией
+CREATE TABLE IF NOT EXISTS POST_META (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER NOT NULL,
+    version INTEGER NOT NULL,
+    owner_id BIGINT NOT NULL,
+    from_id BIGINT NOT NULL,
+    published_at TIMESTAMPTZ NOT NULL,
+    post_type VARCHAR(32) NOT NULL,
+    text TEXT NOT NULL,
+    attachments JSONB NOT NULL DEFAULT '[]',
+    edited_at TIMESTAMPTZ,
+    is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    fetched_at TIMESTAMPTZ NOT NULL,
+
+    CONSTRAINT fk_post_meta_post FOREIGN KEY (post_id)
+        REFERENCES POST(id) ON DELETE CASCADE,
+    CONSTRAINT uq_post_meta_version UNIQUE (post_id, version)
+);
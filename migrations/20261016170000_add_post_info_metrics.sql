@@ -0,0 +1,2 @@
+-- Дополнительные числовые метрики снимка (разбивка репостов, просмотры видео и т.д.) по имени
+ALTER TABLE POST_INFO ADD COLUMN IF NOT EXISTS metrics JSONB NOT NULL DEFAULT '{}';
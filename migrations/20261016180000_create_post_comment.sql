@@ -0,0 +1,23 @@
+-- Время последнего сбора комментариев, комментарии собираются со своей периодичностью
+ALTER TABLE POST ADD COLUMN IF NOT EXISTS comments_polled_at TIMESTAMPTZ;
+
+-- Комментарии поста из wall.getComments, включая ответы в ветках
+CREATE TABLE IF NOT EXISTS POST_COMMENT (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER NOT NULL,
+    comment_id BIGINT NOT NULL,
+    from_id BIGINT NOT NULL,
+    published_at TIMESTAMPTZ NOT NULL,
+    text TEXT NOT NULL,
+    likes_count BIGINT NOT NULL DEFAULT 0,
+    -- Комментарий, на который это ответ; NULL у комментариев верхнего уровня
+    parent_id BIGINT,
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL,
+
+    CONSTRAINT fk_post_comment_post FOREIGN KEY (post_id)
+        REFERENCES POST(id) ON DELETE CASCADE,
+    CONSTRAINT uq_post_comment UNIQUE (post_id, comment_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_post_comment_first_seen ON POST_COMMENT(post_id, first_seen_at);
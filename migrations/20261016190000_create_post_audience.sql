@@ -0,0 +1,22 @@
+-- Сбор аудитории (кто лайкнул и репостнул) включается для каждой задачи отдельно
+ALTER TABLE POST ADD COLUMN IF NOT EXISTS track_audience BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE POST ADD COLUMN IF NOT EXISTS audience_polled_at TIMESTAMPTZ;
+
+-- Пользователи, лайкнувшие (likes.getList) или репостнувшие (wall.getReposts) пост
+CREATE TABLE IF NOT EXISTS POST_AUDIENCE (
+    id SERIAL PRIMARY KEY,
+    post_id INTEGER NOT NULL,
+    user_id BIGINT NOT NULL,
+    -- like или repost
+    kind VARCHAR(16) NOT NULL,
+    -- Время репоста из VK, у лайков VK время не отдает
+    engaged_at TIMESTAMPTZ,
+    first_seen_at TIMESTAMPTZ NOT NULL,
+    last_seen_at TIMESTAMPTZ NOT NULL,
+
+    CONSTRAINT fk_post_audience_post FOREIGN KEY (post_id)
+        REFERENCES POST(id) ON DELETE CASCADE,
+    CONSTRAINT uq_post_audience UNIQUE (post_id, kind, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_post_audience_first_seen ON POST_AUDIENCE(post_id, first_seen_at);
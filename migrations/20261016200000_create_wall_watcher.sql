@@ -0,0 +1,20 @@
+-- Наблюдение за стеной сообщества или пользователя: новые посты сами ставятся на парсинг
+CREATE TABLE IF NOT EXISTS WALL_WATCHER (
+    id SERIAL PRIMARY KEY,
+    -- Сообщество со знаком минус, пользователь без знака, как owner_id в VK
+    owner_id BIGINT NOT NULL,
+    -- Период проверки стены через wall.get
+    interval_seconds BIGINT NOT NULL,
+    -- Длительность парсинга поставленных постов
+    duration_seconds BIGINT NOT NULL,
+    track_audience BOOLEAN NOT NULL DEFAULT FALSE,
+    -- Фильтры постов
+    include_pinned BOOLEAN NOT NULL DEFAULT FALSE,
+    min_age_seconds BIGINT NOT NULL DEFAULT 0,
+    keywords TEXT[] NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL,
+    -- Время последней проверки, по нему выбираются наблюдатели, которым пора
+    checked_at TIMESTAMPTZ,
+    -- Посты, опубликованные раньше, уже рассмотрены и повторно не проверяются
+    scanned_until TIMESTAMPTZ NOT NULL
+);
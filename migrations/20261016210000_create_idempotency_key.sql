@@ -0,0 +1,12 @@
+-- Ответы на запросы с заголовком Idempotency-Key: повтор запроса с тем же ключом получает тот же ответ
+CREATE TABLE IF NOT EXISTS IDEMPOTENCY_KEY (
+    key VARCHAR(255) PRIMARY KEY,
+    -- Параметры исходного запроса, запрос с тем же ключом и другими параметрами отклоняется
+    request JSONB NOT NULL,
+    response JSONB NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    -- После этого времени ключ можно использовать заново
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_idempotency_key_expires_at ON IDEMPOTENCY_KEY(expires_at);
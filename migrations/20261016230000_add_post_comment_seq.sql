@@ -0,0 +1,5 @@
+-- Порядковый номер комментария для курсора в GET /polling/comments: сборы одного поста идут по очереди,
+-- поэтому номера растут в порядке фиксации транзакций, в отличие от first_seen_at
+ALTER TABLE POST_COMMENT ADD COLUMN IF NOT EXISTS seq BIGSERIAL;
+
+CREATE INDEX IF NOT EXISTS idx_post_comment_seq ON POST_COMMENT(post_id, seq);
@@ -0,0 +1,4 @@
+-- Порядковый номер пользователя для курсора в GET /polling/audience, как seq у комментариев
+ALTER TABLE POST_AUDIENCE ADD COLUMN IF NOT EXISTS seq BIGSERIAL;
+
+CREATE INDEX IF NOT EXISTS idx_post_audience_seq ON POST_AUDIENCE(post_id, seq);
@@ -1,106 +1,463 @@
-use crate::models::{PostDetails, PostInfoData, PostWithData};
-use crate::utils::{get_pooling_delta_seconds, get_pooling_period_seconds};
+use crate::errors::AppError;
+use crate::models::{
+    AudienceKind, NewWallWatcher, PostAudienceEntry, PostComment, PostCounters, PostDetails,
+    PostInfoData, PostListFilter, PostListItem, PostMeta, PostWithData, VkAudienceEntry, VkComment,
+    VkPostMeta, WallWatcher,
+};
+use crate::utils::{get_idempotency_key_ttl_seconds, get_pooling_delta_seconds};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
 use sqlx::Row;
-use sqlx::postgres::PgPool;
+use sqlx::postgres::{PgConnection, PgPool, PgRow};
+use sqlx::types::Json;
+use std::collections::HashSet;
 
-pub async fn is_ready_to_finish(pool: &PgPool, post_id: i32) -> Result<bool, sqlx::Error> {
+// Namespaces of transaction-level advisory locks, so a post and a key with the same hash don't collide
+const POST_LOCK_SPACE: i32 = 1;
+const IDEMPOTENCY_KEY_LOCK_SPACE: i32 = 2;
+const POST_COMMENT_LOCK_SPACE: i32 = 3;
+const POST_AUDIENCE_LOCK_SPACE: i32 = 4;
+
+fn post_details_from_row(row: &PgRow) -> PostDetails {
+    PostDetails {
+        id: row.get("id"),
+        vk_id: row.get("vk_id"),
+        dt_parse_begin: row.get("dt_parse_begin"),
+        dt_parse_end: row.get("dt_parse_end"),
+        paused_at: row.get("paused_at"),
+        track_audience: row.get("track_audience"),
+    }
+}
+
+fn wall_watcher_from_row(row: &PgRow) -> WallWatcher {
+    WallWatcher {
+        id: row.get("id"),
+        owner_id: row.get("owner_id"),
+        interval_seconds: row.get("interval_seconds"),
+        duration_seconds: row.get("duration_seconds"),
+        track_audience: row.get("track_audience"),
+        include_pinned: row.get("include_pinned"),
+        min_age_seconds: row.get("min_age_seconds"),
+        keywords: row.get("keywords"),
+        created_at: row.get("created_at"),
+        checked_at: row.get("checked_at"),
+        scanned_until: row.get("scanned_until"),
+    }
+}
+
+// Reads the POST_META columns, the caller makes sure the row has them
+fn post_meta_from_row(row: &PgRow) -> PostMeta {
+    PostMeta {
+        version: row.get("version"),
+        meta: VkPostMeta {
+            owner_id: row.get("owner_id"),
+            from_id: row.get("from_id"),
+            published_at: row.get("published_at"),
+            post_type: row.get("post_type"),
+            text: row.get("text"),
+            attachments: row.get::<Json<serde_json::Value>, _>("attachments").0,
+            edited_at: row.get("edited_at"),
+            is_deleted: row.get("is_deleted"),
+        },
+        fetched_at: row.get("fetched_at"),
+    }
+}
+
+pub async fn save_post_info(
+    pool: &PgPool,
+    post_id: i32,
+    counters: &PostCounters,
+    info_time: DateTime<Utc>,
+) -> Result<bool, AppError> {
+    // Only posts with an active, not paused window accept new snapshots
     let result = sqlx::query(
         r#"
-        SELECT CURRENT_TIMESTAMP > dt_parse_end as is_ready_to_finish
-        FROM POST
-        WHERE id = $1
-        "#,
+        INSERT INTO POST_INFO (post_id, likes_count, comments_count, reposts_count, views_count, metrics, info_time)
+        SELECT $1, $2, $3, $4, $5, $7, $6
+        WHERE EXISTS (
+            SELECT 1 FROM POST
+            WHERE id = $1
+            AND dt_parse_end > $6
+            AND paused_at IS NULL
+        )
+        "#
     )
     .bind(post_id)
-    .fetch_optional(pool)
+    .bind(counters.likes_count)
+    .bind(counters.comments_count)
+    .bind(counters.reposts_count)
+    .bind(counters.views_count)
+    .bind(info_time)
+    .bind(Json(&counters.metrics))
+    .execute(pool)
     .await?;
 
-    match result {
-        Some(row) => Ok(row.get("is_ready_to_finish")),
-        None => Ok(true),
-    }
+    Ok(result.rows_affected() > 0)
 }
 
-pub async fn save_post_info(
+/// Stores the post content as a new version unless the latest version already has it.
+///
+/// Returns whether a version was added.
+pub async fn save_post_meta(
     pool: &PgPool,
     post_id: i32,
-    likes_count: i32,
-    comments_count: i32,
-    reposts_count: i32,
-    views_count: i32,
-) -> Result<(), sqlx::Error> {
-    sqlx::query(
+    meta: &VkPostMeta,
+    fetched_at: DateTime<Utc>,
+) -> Result<bool, AppError> {
+    // Attachment urls are re-signed by VK on every request, an edit shows up in edited_at instead.
+    // Two polls racing for the same version keep the first one
+    let result = sqlx::query(
         r#"
-        INSERT INTO POST_INFO (post_id, likes_count, comments_count, reposts_count, views_count, info_time)
-        VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP)
-        "#
+        INSERT INTO POST_META (
+            post_id, version, owner_id, from_id, published_at, post_type,
+            text, attachments, edited_at, is_deleted, fetched_at
+        )
+        SELECT p.id, COALESCE(l.version, 0) + 1, $2, $3, $4, $5, $6, $7, $8, $9, $10
+        FROM POST p
+        LEFT JOIN LATERAL (
+            SELECT version, owner_id, from_id, published_at, post_type, text, edited_at, is_deleted
+            FROM POST_META
+            WHERE post_id = p.id
+            ORDER BY version DESC
+            LIMIT 1
+        ) l ON TRUE
+        WHERE p.id = $1
+        AND (
+            l.version IS NULL
+            OR (l.owner_id, l.from_id, l.published_at, l.post_type, l.text, l.edited_at, l.is_deleted)
+                IS DISTINCT FROM ($2::bigint, $3::bigint, $4::timestamptz, $5::varchar, $6::text, $8::timestamptz, $9::boolean)
+        )
+        ON CONFLICT (post_id, version) DO NOTHING
+        "#,
     )
     .bind(post_id)
-    .bind(likes_count)
-    .bind(comments_count)
-    .bind(reposts_count)
-    .bind(views_count)
+    .bind(meta.owner_id)
+    .bind(meta.from_id)
+    .bind(meta.published_at)
+    .bind(&meta.post_type)
+    .bind(&meta.text)
+    .bind(Json(&meta.attachments))
+    .bind(meta.edited_at)
+    .bind(meta.is_deleted)
+    .bind(fetched_at)
     .execute(pool)
     .await?;
 
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
-pub async fn get_vk_id_by_post_id(
+/// All stored versions of the post content, oldest first.
+pub async fn get_post_meta_versions(
     pool: &PgPool,
     post_id: i32,
-) -> Result<Option<String>, sqlx::Error> {
-    let result = sqlx::query(
+) -> Result<Vec<PostMeta>, AppError> {
+    let rows = sqlx::query(
         r#"
-        SELECT vk_id FROM POST
-        WHERE id = $1
+        SELECT version, owner_id, from_id, published_at, post_type,
+            text, attachments, edited_at, is_deleted, fetched_at
+        FROM POST_META
+        WHERE post_id = $1
+        ORDER BY version ASC
         "#,
     )
     .bind(post_id)
-    .fetch_optional(pool)
+    .fetch_all(pool)
     .await?;
 
-    Ok(result.map(|row| row.get("vk_id")))
+    Ok(rows.iter().map(post_meta_from_row).collect())
 }
 
-pub async fn has_recent_post_info(
+/// Takes the active posts whose comments were not collected for `interval_seconds`.
+///
+/// The posts are marked as collected right away, so a failed collection waits for the next round.
+pub async fn claim_posts_for_comments(
+    pool: &PgPool,
+    interval_seconds: i64,
+    now: DateTime<Utc>,
+) -> Result<Vec<(i32, String)>, AppError> {
+    let results = sqlx::query(
+        r#"
+        UPDATE POST
+        SET comments_polled_at = $2
+        WHERE dt_parse_end > $2
+        AND paused_at IS NULL
+        AND (
+            comments_polled_at IS NULL
+            OR comments_polled_at <= $2 - ($1 * INTERVAL '1 second')
+        )
+        RETURNING id, vk_id
+        "#,
+    )
+    .bind(interval_seconds)
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(results
+        .iter()
+        .map(|row| (row.get("id"), row.get("vk_id")))
+        .collect())
+}
+
+/// Takes the active posts with audience tracking whose audience was not collected for `interval_seconds`.
+pub async fn claim_posts_for_audience(
+    pool: &PgPool,
+    interval_seconds: i64,
+    now: DateTime<Utc>,
+) -> Result<Vec<(i32, String)>, AppError> {
+    let results = sqlx::query(
+        r#"
+        UPDATE POST
+        SET audience_polled_at = $2
+        WHERE track_audience
+        AND dt_parse_end > $2
+        AND paused_at IS NULL
+        AND (
+            audience_polled_at IS NULL
+            OR audience_polled_at <= $2 - ($1 * INTERVAL '1 second')
+        )
+        RETURNING id, vk_id
+        "#,
+    )
+    .bind(interval_seconds)
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(results
+        .iter()
+        .map(|row| (row.get("id"), row.get("vk_id")))
+        .collect())
+}
+
+/// Stores the users who engaged with the post, returns how many were seen for the first time.
+///
+/// Collections of one post take turns, so `seq` of new users grows in commit order.
+pub async fn save_audience(
     pool: &PgPool,
     post_id: i32,
-    seconds: i64,
-) -> Result<bool, sqlx::Error> {
-    let result = sqlx::query(
+    kind: AudienceKind,
+    entries: &[VkAudienceEntry],
+    now: DateTime<Utc>,
+) -> Result<usize, AppError> {
+    let user_ids: Vec<i64> = entries.iter().map(|entry| entry.user_id).collect();
+    let engaged_at: Vec<Option<DateTime<Utc>>> =
+        entries.iter().map(|entry| entry.engaged_at).collect();
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock($1, $2)")
+        .bind(POST_AUDIENCE_LOCK_SPACE)
+        .bind(post_id)
+        .execute(&mut *tx)
+        .await?;
+
+    // xmax is zero only for the rows this statement has inserted
+    let rows = sqlx::query(
         r#"
-        SELECT EXISTS(
-            SELECT 1 FROM POST_INFO
-            WHERE post_id = $1
-            AND info_time > CURRENT_TIMESTAMP - (2 * $2 * INTERVAL '1 second')
-        ) as has_recent
+        INSERT INTO POST_AUDIENCE (post_id, user_id, kind, engaged_at, first_seen_at, last_seen_at)
+        SELECT $1, user_id, $2, engaged_at, $5, $5
+        FROM UNNEST($3::bigint[], $4::timestamptz[]) AS e(user_id, engaged_at)
+        ON CONFLICT (post_id, kind, user_id) DO UPDATE
+        SET last_seen_at = EXCLUDED.last_seen_at,
+            engaged_at = COALESCE(POST_AUDIENCE.engaged_at, EXCLUDED.engaged_at)
+        RETURNING (xmax = 0) AS inserted
         "#,
     )
     .bind(post_id)
-    .bind(seconds)
-    .fetch_one(pool)
+    .bind(kind.as_str())
+    .bind(&user_ids)
+    .bind(&engaged_at)
+    .bind(now)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(rows
+        .iter()
+        .filter(|row| row.get::<bool, _>("inserted"))
+        .count())
+}
+
+/// Users who engaged with the post, saved after `cursor` and first seen at or after `since`,
+/// in the order they were found.
+pub async fn get_post_audience(
+    pool: &PgPool,
+    post_id: i32,
+    kind: Option<AudienceKind>,
+    cursor: Option<i64>,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<PostAudienceEntry>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT seq, user_id, kind, engaged_at, first_seen_at, last_seen_at
+        FROM POST_AUDIENCE
+        WHERE post_id = $1
+        AND ($2::varchar IS NULL OR kind = $2)
+        AND ($3::bigint IS NULL OR seq > $3)
+        AND ($4::timestamptz IS NULL OR first_seen_at >= $4)
+        ORDER BY first_seen_at ASC, id ASC
+        "#,
+    )
+    .bind(post_id)
+    .bind(kind.map(AudienceKind::as_str))
+    .bind(cursor)
+    .bind(since)
+    .fetch_all(pool)
     .await?;
 
-    Ok(result.get("has_recent"))
+    Ok(rows
+        .iter()
+        .map(|row| PostAudienceEntry {
+            seq: row.get("seq"),
+            user_id: row.get("user_id"),
+            kind: row.get("kind"),
+            engaged_at: row.get("engaged_at"),
+            first_seen_at: row.get("first_seen_at"),
+            last_seen_at: row.get("last_seen_at"),
+        })
+        .collect())
+}
+
+/// Stores new comments and refreshes text and likes of known ones, returns how many were new.
+///
+/// Collections of one post take turns, so `seq` of new comments grows in commit order.
+pub async fn save_comments(
+    pool: &PgPool,
+    post_id: i32,
+    comments: &[VkComment],
+    now: DateTime<Utc>,
+) -> Result<usize, AppError> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock($1, $2)")
+        .bind(POST_COMMENT_LOCK_SPACE)
+        .bind(post_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let mut inserted = 0;
+    for comment in comments {
+        // xmax is zero only for a row this statement has inserted
+        let row = sqlx::query(
+            r#"
+            INSERT INTO POST_COMMENT (
+                post_id, comment_id, from_id, published_at, text,
+                likes_count, parent_id, first_seen_at, updated_at
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
+            ON CONFLICT (post_id, comment_id) DO UPDATE
+            SET text = EXCLUDED.text,
+                likes_count = EXCLUDED.likes_count,
+                updated_at = EXCLUDED.updated_at
+            RETURNING (xmax = 0) AS inserted
+            "#,
+        )
+        .bind(post_id)
+        .bind(comment.id)
+        .bind(comment.from_id)
+        .bind(comment.published_at)
+        .bind(&comment.text)
+        .bind(comment.likes_count)
+        .bind(comment.parent_id)
+        .bind(now)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        if row.get::<bool, _>("inserted") {
+            inserted += 1;
+        }
+    }
+
+    tx.commit().await?;
+
+    Ok(inserted)
 }
 
-pub async fn get_posts_needing_polling(pool: &PgPool) -> Result<Vec<(i32, String)>, sqlx::Error> {
+/// Comments of the post saved after `cursor` and first collected at or after `since`, oldest first.
+pub async fn get_post_comments(
+    pool: &PgPool,
+    post_id: i32,
+    cursor: Option<i64>,
+    since: Option<DateTime<Utc>>,
+) -> Result<Vec<PostComment>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        SELECT seq, comment_id, from_id, published_at, text, likes_count, parent_id, first_seen_at
+        FROM POST_COMMENT
+        WHERE post_id = $1
+        AND ($2::bigint IS NULL OR seq > $2)
+        AND ($3::timestamptz IS NULL OR first_seen_at >= $3)
+        ORDER BY published_at ASC, comment_id ASC
+        "#,
+    )
+    .bind(post_id)
+    .bind(cursor)
+    .bind(since)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| PostComment {
+            seq: row.get("seq"),
+            comment_id: row.get("comment_id"),
+            from_id: row.get("from_id"),
+            published_at: row.get("published_at"),
+            text: row.get("text"),
+            likes_count: row.get("likes_count"),
+            parent_id: row.get("parent_id"),
+            first_seen_at: row.get("first_seen_at"),
+        })
+        .collect())
+}
+
+pub async fn save_poll_skips(
+    pool: &PgPool,
+    post_ids: &[i32],
+    reason: &str,
+    skipped_at: DateTime<Utc>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        INSERT INTO POLL_SKIP (post_id, reason, skipped_at)
+        SELECT UNNEST($1::integer[]), $2, $3
+        "#,
+    )
+    .bind(post_ids)
+    .bind(reason)
+    .bind(skipped_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn get_posts_needing_polling(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+) -> Result<Vec<(i32, String)>, AppError> {
     let pooling_delta = get_pooling_delta_seconds();
 
+    // A snapshot saved on the previous tick is slightly younger than one delta,
+    // so only snapshots from the last half of the delta mean the post was already polled
     let results = sqlx::query(
         r#"
         SELECT DISTINCT p.id, p.vk_id
         FROM POST p
-        WHERE p.dt_parse_end > CURRENT_TIMESTAMP
+        WHERE p.dt_parse_end > $2
+        AND p.paused_at IS NULL
         AND NOT EXISTS (
             SELECT 1 FROM POST_INFO pi
             WHERE pi.post_id = p.id
-            AND pi.info_time > CURRENT_TIMESTAMP - (2 * $1 * INTERVAL '1 second')
+            AND pi.info_time > $2 - ($1 * INTERVAL '1 second' / 2)
         )
         "#,
     )
     .bind(pooling_delta)
+    .bind(now)
     .fetch_all(pool)
     .await?;
 
@@ -114,96 +471,354 @@ pub async fn get_or_create_post_with_prolong(
     pool: &PgPool,
     vk_id: &str,
     prolong: bool,
-) -> Result<PostDetails, sqlx::Error> {
-    let pooling_period = get_pooling_period_seconds();
-
+    duration_seconds: i64,
+    track_audience: bool,
+    now: DateTime<Utc>,
+) -> Result<PostDetails, AppError> {
     // Start a transaction to prevent race conditions
     let mut tx = pool.begin().await?;
 
+    let post_details = upsert_post(
+        &mut tx,
+        vk_id,
+        prolong,
+        duration_seconds,
+        track_audience,
+        now,
+    )
+    .await?;
+
+    // Commit the transaction
+    tx.commit().await?;
+
+    Ok(post_details)
+}
+
+// Body of get_or_create_post_with_prolong, run inside the transaction of the caller
+async fn upsert_post(
+    conn: &mut PgConnection,
+    vk_id: &str,
+    prolong: bool,
+    duration_seconds: i64,
+    track_audience: bool,
+    now: DateTime<Utc>,
+) -> Result<PostDetails, AppError> {
+    // FOR UPDATE can't lock a row that does not exist yet, so requests for the same post take
+    // turns here, otherwise two of them would both insert it and one would hit the constraint
+    sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+        .bind(POST_LOCK_SPACE)
+        .bind(vk_id)
+        .execute(&mut *conn)
+        .await?;
+
     // Try to find an existing post within the current time range with row lock
     let existing_post = sqlx::query(
         r#"
-        SELECT id, vk_id, dt_parse_begin, dt_parse_end
+        SELECT id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
         FROM POST
         WHERE vk_id = $1
-        AND tsrange(dt_parse_begin, dt_parse_end) @> CURRENT_TIMESTAMP::timestamp
+        AND tstzrange(dt_parse_begin, dt_parse_end) @> $2
         LIMIT 1
         FOR UPDATE
         "#,
     )
     .bind(vk_id)
-    .fetch_optional(&mut *tx)
+    .bind(now)
+    .fetch_optional(&mut *conn)
     .await?;
 
+    // Audience tracking can be turned on for a running task, but never off
     let post_details = if let Some(row) = existing_post {
         if prolong {
-            // Prolong the existing post
+            // Prolong the existing post by the requested duration from now. A paused post is
+            // resumed as well, otherwise a later resume would shift the new end by the pause again
             let updated = sqlx::query(
                 r#"
                 UPDATE POST
-                SET dt_parse_end = CURRENT_TIMESTAMP + ($1 * INTERVAL '1 second')
+                SET dt_parse_end = $3 + ($1 * INTERVAL '1 second'),
+                    paused_at = NULL,
+                    track_audience = track_audience OR $4
                 WHERE id = $2
-                RETURNING id, vk_id, dt_parse_begin, dt_parse_end
+                RETURNING id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
+                "#,
+            )
+            .bind(duration_seconds)
+            .bind(row.get::<i32, _>("id"))
+            .bind(now)
+            .bind(track_audience)
+            .fetch_one(&mut *conn)
+            .await?;
+
+            post_details_from_row(&updated)
+        } else if track_audience && !row.get::<bool, _>("track_audience") {
+            let updated = sqlx::query(
+                r#"
+                UPDATE POST
+                SET track_audience = TRUE
+                WHERE id = $1
+                RETURNING id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
                 "#,
             )
-            .bind(pooling_period)
             .bind(row.get::<i32, _>("id"))
-            .fetch_one(&mut *tx)
+            .fetch_one(&mut *conn)
             .await?;
 
-            PostDetails {
-                id: updated.get("id"),
-                vk_id: updated.get("vk_id"),
-                dt_parse_begin: updated.get("dt_parse_begin"),
-                dt_parse_end: updated.get("dt_parse_end"),
-            }
+            post_details_from_row(&updated)
         } else {
             // Return existing post without prolonging
-            PostDetails {
-                id: row.get("id"),
-                vk_id: row.get("vk_id"),
-                dt_parse_begin: row.get("dt_parse_begin"),
-                dt_parse_end: row.get("dt_parse_end"),
-            }
+            post_details_from_row(&row)
         }
     } else {
         // No existing post found, create a new one
         let result = sqlx::query(
             r#"
-            INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end)
-            VALUES ($1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP + ($2 * INTERVAL '1 second'))
-            RETURNING id, vk_id, dt_parse_begin, dt_parse_end
+            INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end, track_audience)
+            VALUES ($1, $3, $3 + ($2 * INTERVAL '1 second'), $4)
+            RETURNING id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
             "#,
         )
         .bind(vk_id)
-        .bind(pooling_period)
-        .fetch_one(&mut *tx)
+        .bind(duration_seconds)
+        .bind(now)
+        .bind(track_audience)
+        .fetch_one(&mut *conn)
         .await?;
 
-        PostDetails {
-            id: result.get("id"),
-            vk_id: result.get("vk_id"),
-            dt_parse_begin: result.get("dt_parse_begin"),
-            dt_parse_end: result.get("dt_parse_end"),
-        }
+        post_details_from_row(&result)
     };
 
-    // Commit the transaction
+    Ok(post_details)
+}
+
+/// Response stored for the Idempotency-Key, None when the key is new or has expired.
+///
+/// A key sent again with another request is a client error, not a retry.
+pub async fn get_idempotent_response<T: DeserializeOwned>(
+    pool: &PgPool,
+    key: &str,
+    request: &serde_json::Value,
+    now: DateTime<Utc>,
+) -> Result<Option<T>, AppError> {
+    let mut conn = pool.acquire().await?;
+    find_idempotent_response(&mut conn, key, request, now).await
+}
+
+async fn find_idempotent_response<T: DeserializeOwned>(
+    conn: &mut PgConnection,
+    key: &str,
+    request: &serde_json::Value,
+    now: DateTime<Utc>,
+) -> Result<Option<T>, AppError> {
+    let row = sqlx::query(
+        r#"
+        SELECT request, response
+        FROM IDEMPOTENCY_KEY
+        WHERE key = $1 AND expires_at > $2
+        "#,
+    )
+    .bind(key)
+    .bind(now)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    if row.get::<Json<serde_json::Value>, _>("request").0 != *request {
+        return Err(AppError::Conflict(format!(
+            "Idempotency-Key {} was already used with another request",
+            key
+        )));
+    }
+
+    Ok(Some(row.try_get::<Json<T>, _>("response")?.0))
+}
+
+/// get_or_create_post_with_prolong that stores its response under the Idempotency-Key.
+///
+/// The post and the stored response are committed together, so a retry either finds both
+/// or repeats the whole call. Requests with the same key take turns, the first one to finish
+/// decides the response of all of them.
+#[allow(clippy::too_many_arguments)]
+pub async fn get_or_create_post_idempotent<T, F>(
+    pool: &PgPool,
+    key: &str,
+    request: &serde_json::Value,
+    vk_id: &str,
+    prolong: bool,
+    duration_seconds: i64,
+    track_audience: bool,
+    now: DateTime<Utc>,
+    respond: F,
+) -> Result<T, AppError>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce(PostDetails) -> T,
+{
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+        .bind(IDEMPOTENCY_KEY_LOCK_SPACE)
+        .bind(key)
+        .execute(&mut *tx)
+        .await?;
+
+    // Another request with this key finished while this one was waiting
+    if let Some(response) = find_idempotent_response(&mut tx, key, request, now).await? {
+        return Ok(response);
+    }
+
+    let post_details = upsert_post(
+        &mut tx,
+        vk_id,
+        prolong,
+        duration_seconds,
+        track_audience,
+        now,
+    )
+    .await?;
+    let response = respond(post_details);
+
+    // Expired keys are dropped on the way, the one being stored may be among them
+    sqlx::query("DELETE FROM IDEMPOTENCY_KEY WHERE expires_at <= $1")
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO IDEMPOTENCY_KEY (key, request, response, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $4 + ($5 * INTERVAL '1 second'))
+        "#,
+    )
+    .bind(key)
+    .bind(Json(request))
+    .bind(Json(&response))
+    .bind(now)
+    .bind(get_idempotency_key_ttl_seconds())
+    .execute(&mut *tx)
+    .await?;
+
     tx.commit().await?;
 
-    Ok(post_details)
+    Ok(response)
+}
+
+pub async fn finish_post(
+    pool: &PgPool,
+    post_id: i32,
+    now: DateTime<Utc>,
+) -> Result<Option<PostDetails>, AppError> {
+    // Already finished posts keep their original end
+    let result = sqlx::query(
+        r#"
+        UPDATE POST
+        SET dt_parse_end = LEAST(dt_parse_end, $2),
+            paused_at = NULL
+        WHERE id = $1
+        RETURNING id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
+        "#,
+    )
+    .bind(post_id)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.as_ref().map(post_details_from_row))
+}
+
+pub async fn get_post_details(
+    pool: &PgPool,
+    post_id: i32,
+) -> Result<Option<PostDetails>, AppError> {
+    let result = sqlx::query(
+        r#"
+        SELECT id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
+        FROM POST
+        WHERE id = $1
+        "#,
+    )
+    .bind(post_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.as_ref().map(post_details_from_row))
+}
+
+pub async fn pause_post(
+    pool: &PgPool,
+    post_id: i32,
+    now: DateTime<Utc>,
+) -> Result<Option<PostDetails>, AppError> {
+    // Pausing twice keeps the first pause time, finished posts can't be paused
+    let result = sqlx::query(
+        r#"
+        UPDATE POST
+        SET paused_at = COALESCE(paused_at, $2)
+        WHERE id = $1
+        AND (paused_at IS NOT NULL OR dt_parse_end > $2)
+        RETURNING id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
+        "#,
+    )
+    .bind(post_id)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.as_ref().map(post_details_from_row))
+}
+
+pub async fn resume_post(
+    pool: &PgPool,
+    post_id: i32,
+    extend: bool,
+    now: DateTime<Utc>,
+) -> Result<Option<PostDetails>, AppError> {
+    // With extend the window is shifted by the pause, so the remaining length is restored
+    let result = sqlx::query(
+        r#"
+        UPDATE POST
+        SET dt_parse_end = CASE
+                WHEN $2 THEN $3 + (dt_parse_end - paused_at)
+                ELSE dt_parse_end
+            END,
+            paused_at = NULL
+        WHERE id = $1
+        AND paused_at IS NOT NULL
+        RETURNING id, vk_id, dt_parse_begin, dt_parse_end, paused_at, track_audience
+        "#,
+    )
+    .bind(post_id)
+    .bind(extend)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(result.as_ref().map(post_details_from_row))
 }
 
 pub async fn get_post_with_data(
     pool: &PgPool,
     scrapper_id: i32,
-) -> Result<Option<PostWithData>, sqlx::Error> {
-    // Get post details
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<Option<PostWithData>, AppError> {
+    // Get post details with the latest version of its content
     let post = sqlx::query(
         r#"
-        SELECT id, vk_id, dt_parse_begin, dt_parse_end
-        FROM POST
-        WHERE id = $1
+        SELECT p.id, p.vk_id, p.dt_parse_begin, p.dt_parse_end, p.paused_at,
+            m.version, m.owner_id, m.from_id, m.published_at, m.post_type,
+            m.text, m.attachments, m.edited_at, m.is_deleted, m.fetched_at
+        FROM POST p
+        LEFT JOIN LATERAL (
+            SELECT *
+            FROM POST_META
+            WHERE post_id = p.id
+            ORDER BY version DESC
+            LIMIT 1
+        ) m ON TRUE
+        WHERE p.id = $1
         "#,
     )
     .bind(scrapper_id)
@@ -215,16 +830,20 @@ pub async fn get_post_with_data(
         None => return Ok(None),
     };
 
-    // Get post info data sorted by info_time
+    // Get post info data within [from, to) sorted by info_time
     let data_rows = sqlx::query(
         r#"
-        SELECT comments_count, likes_count, views_count, reposts_count, info_time
+        SELECT comments_count, likes_count, views_count, reposts_count, metrics, info_time
         FROM POST_INFO
         WHERE post_id = $1
+        AND ($2::timestamptz IS NULL OR info_time >= $2)
+        AND ($3::timestamptz IS NULL OR info_time < $3)
         ORDER BY info_time ASC
         "#,
     )
     .bind(scrapper_id)
+    .bind(from)
+    .bind(to)
     .fetch_all(pool)
     .await?;
 
@@ -235,6 +854,7 @@ pub async fn get_post_with_data(
             likes_count: row.get("likes_count"),
             views_count: row.get("views_count"),
             reposts_count: row.get("reposts_count"),
+            metrics: row.get::<Json<_>, _>("metrics").0,
             info_time: row.get("info_time"),
         })
         .collect();
@@ -244,6 +864,209 @@ pub async fn get_post_with_data(
         vk_id: post.get("vk_id"),
         dt_parse_begin: post.get("dt_parse_begin"),
         dt_parse_end: post.get("dt_parse_end"),
+        paused_at: post.get("paused_at"),
+        meta: post
+            .get::<Option<i32>, _>("version")
+            .map(|_| post_meta_from_row(&post)),
         data,
     }))
 }
+
+pub async fn get_posts(
+    pool: &PgPool,
+    filter: &PostListFilter,
+    now: DateTime<Utc>,
+) -> Result<Vec<PostListItem>, AppError> {
+    // Newest posts first, the cursor is the id of the last post on the previous page
+    let rows = sqlx::query(
+        r#"
+        SELECT p.id, p.vk_id, p.dt_parse_begin, p.dt_parse_end, p.paused_at,
+            CASE
+                WHEN p.paused_at IS NOT NULL THEN 'paused'
+                WHEN p.dt_parse_end > $8 THEN 'active'
+                ELSE 'finished'
+            END AS status,
+            s.snapshot_count,
+            k.skipped_count,
+            l.comments_count, l.likes_count, l.views_count, l.reposts_count, l.metrics, l.info_time,
+            m.version, m.owner_id, m.from_id, m.published_at, m.post_type,
+            m.text, m.attachments, m.edited_at, m.is_deleted, m.fetched_at
+        FROM POST p
+        CROSS JOIN LATERAL (
+            SELECT COUNT(*) AS snapshot_count
+            FROM POST_INFO
+            WHERE post_id = p.id
+        ) s
+        CROSS JOIN LATERAL (
+            SELECT COUNT(*) AS skipped_count
+            FROM POLL_SKIP
+            WHERE post_id = p.id
+        ) k
+        LEFT JOIN LATERAL (
+            SELECT comments_count, likes_count, views_count, reposts_count, metrics, info_time
+            FROM POST_INFO
+            WHERE post_id = p.id
+            ORDER BY info_time DESC
+            LIMIT 1
+        ) l ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT *
+            FROM POST_META
+            WHERE post_id = p.id
+            ORDER BY version DESC
+            LIMIT 1
+        ) m ON TRUE
+        WHERE ($1::varchar IS NULL OR p.vk_id = $1)
+        AND ($2::varchar IS NULL OR split_part(p.vk_id, '_', 1) = $2)
+        AND ($3::timestamptz IS NULL OR tstzrange(p.dt_parse_begin, p.dt_parse_end) @> $3)
+        AND ($4::timestamptz IS NULL OR p.dt_parse_begin >= $4)
+        AND ($5::timestamptz IS NULL OR p.dt_parse_begin < $5)
+        AND ($6::integer IS NULL OR p.id < $6)
+        ORDER BY p.id DESC
+        LIMIT $7
+        "#,
+    )
+    .bind(&filter.vk_id)
+    .bind(&filter.owner_id)
+    .bind(filter.active_at)
+    .bind(filter.created_from)
+    .bind(filter.created_to)
+    .bind(filter.cursor)
+    .bind(filter.limit)
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| PostListItem {
+            id: row.get("id"),
+            vk_id: row.get("vk_id"),
+            status: row.get("status"),
+            dt_parse_begin: row.get("dt_parse_begin"),
+            dt_parse_end: row.get("dt_parse_end"),
+            paused_at: row.get("paused_at"),
+            snapshot_count: row.get("snapshot_count"),
+            skipped_count: row.get("skipped_count"),
+            meta: row
+                .get::<Option<i32>, _>("version")
+                .map(|_| post_meta_from_row(row)),
+            latest_snapshot: row
+                .get::<Option<DateTime<Utc>>, _>("info_time")
+                .map(|info_time| PostInfoData {
+                    comments_count: row.get("comments_count"),
+                    likes_count: row.get("likes_count"),
+                    views_count: row.get("views_count"),
+                    reposts_count: row.get("reposts_count"),
+                    metrics: row.get::<Json<_>, _>("metrics").0,
+                    info_time,
+                }),
+        })
+        .collect())
+}
+
+pub async fn create_wall_watcher(
+    pool: &PgPool,
+    watcher: &NewWallWatcher,
+    now: DateTime<Utc>,
+) -> Result<WallWatcher, AppError> {
+    // Posts published before the watcher was created are never enrolled
+    let row = sqlx::query(
+        r#"
+        INSERT INTO WALL_WATCHER (
+            owner_id, interval_seconds, duration_seconds, track_audience,
+            include_pinned, min_age_seconds, keywords, created_at, scanned_until
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
+        RETURNING *
+        "#,
+    )
+    .bind(watcher.owner_id)
+    .bind(watcher.interval_seconds)
+    .bind(watcher.duration_seconds)
+    .bind(watcher.track_audience)
+    .bind(watcher.include_pinned)
+    .bind(watcher.min_age_seconds)
+    .bind(&watcher.keywords)
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(wall_watcher_from_row(&row))
+}
+
+pub async fn get_wall_watchers(pool: &PgPool) -> Result<Vec<WallWatcher>, AppError> {
+    let rows = sqlx::query("SELECT * FROM WALL_WATCHER ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.iter().map(wall_watcher_from_row).collect())
+}
+
+/// Stops watching the wall, the posts it has enrolled keep their parsing windows.
+pub async fn delete_wall_watcher(
+    pool: &PgPool,
+    watcher_id: i32,
+) -> Result<Option<WallWatcher>, AppError> {
+    let row = sqlx::query("DELETE FROM WALL_WATCHER WHERE id = $1 RETURNING *")
+        .bind(watcher_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.as_ref().map(wall_watcher_from_row))
+}
+
+/// Takes the watchers whose wall was not checked for their own interval.
+pub async fn claim_due_wall_watchers(
+    pool: &PgPool,
+    now: DateTime<Utc>,
+) -> Result<Vec<WallWatcher>, AppError> {
+    let rows = sqlx::query(
+        r#"
+        UPDATE WALL_WATCHER
+        SET checked_at = $1
+        WHERE checked_at IS NULL
+        OR checked_at <= $1 - (interval_seconds * INTERVAL '1 second')
+        RETURNING *
+        "#,
+    )
+    .bind(now)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows.iter().map(wall_watcher_from_row).collect())
+}
+
+/// Moves the point before which the wall needs no more checks, never backwards.
+pub async fn set_wall_watcher_scanned_until(
+    pool: &PgPool,
+    watcher_id: i32,
+    scanned_until: DateTime<Utc>,
+) -> Result<(), AppError> {
+    sqlx::query(
+        r#"
+        UPDATE WALL_WATCHER
+        SET scanned_until = GREATEST(scanned_until, $2)
+        WHERE id = $1
+        "#,
+    )
+    .bind(watcher_id)
+    .bind(scanned_until)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// The ids out of `vk_ids` that already have a parsing task.
+pub async fn get_known_vk_ids(
+    pool: &PgPool,
+    vk_ids: &[String],
+) -> Result<HashSet<String>, AppError> {
+    let rows = sqlx::query("SELECT vk_id FROM POST WHERE vk_id = ANY($1)")
+        .bind(vk_ids)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows.iter().map(|row| row.get("vk_id")).collect())
+}
@@ -1,141 +1,758 @@
-use rocket::State;
-use rocket::response::status;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rocket::request::{FromRequest, Outcome};
 use rocket::serde::json::Json;
+use rocket::{Request, State};
+use serde_json::json;
 use sqlx::postgres::PgPool;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use tokio_cron_scheduler::{Job, JobScheduler};
 
+use crate::clock::Clock;
 use crate::db_commands::{
-    get_or_create_post_with_prolong, get_post_with_data, has_recent_post_info,
+    create_wall_watcher, delete_wall_watcher, finish_post, get_idempotent_response,
+    get_or_create_post_idempotent, get_or_create_post_with_prolong, get_post_audience,
+    get_post_comments, get_post_details, get_post_meta_versions, get_post_with_data, get_posts,
+    get_wall_watchers, pause_post, resume_post,
 };
-use crate::models::{GetPollingResponse, PollingRequest, PollingResponse, PostInfoDataResponse};
-use crate::tasks::poll_post_stats;
-use crate::utils::{get_pooling_delta_seconds, is_post_stats_empty};
-use crate::vk_api::call_vk;
+use crate::errors::AppError;
+use crate::models::{
+    Aggregation, AudienceEntryResponse, AudienceResponse, BatchPollingItemResponse,
+    BatchPollingRequest, BatchPollingResponse, CommentResponse, CommentsResponse, ErrorBody,
+    GetPollingResponse, NewWallWatcher, PollingRequest, PollingResponse, PostDetails, PostInfoData,
+    PostInfoDataResponse, PostListFilter, PostListItemResponse, PostListResponse, PostMeta,
+    PostMetaHistoryResponse, PostMetaResponse, TokenRotation, VkTokenPoolResponse,
+    VkTokenStatsResponse, WallWatcher, WallWatcherListResponse, WallWatcherRequest,
+    WallWatcherResponse,
+};
+use crate::tasks::JobRegistry;
+use crate::token_pool::{TokenStatus, vk_token_pool};
+use crate::utils::{
+    bucket_post_info, format_timestamp, get_polling_batch_max_posts, is_post_stats_empty,
+    parse_aggregation, parse_audience_kind, parse_idempotency_key, parse_step_seconds,
+    parse_timestamp, parse_timezone, resolve_duration_seconds, resolve_min_age_seconds,
+    resolve_watch_interval_seconds,
+};
+use crate::vk_api::VkClient;
+use crate::vk_link::{parse_vk_owner_id, parse_vk_post_link};
+
+/// Raw Idempotency-Key header, checked by the endpoint so a bad key gets a JSON error.
+pub struct IdempotencyKeyHeader(Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKeyHeader {
+    type Error = std::convert::Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let key = request.headers().get_one("Idempotency-Key");
+        Outcome::Success(IdempotencyKeyHeader(key.map(str::to_string)))
+    }
+}
+
+fn polling_response(post_details: PostDetails, tz: Tz) -> PollingResponse {
+    PollingResponse {
+        scrapper_id: post_details.id,
+        vk_id: post_details.vk_id,
+        dt_parse_begin: format_timestamp(&post_details.dt_parse_begin, tz),
+        dt_parse_end: format_timestamp(&post_details.dt_parse_end, tz),
+        paused_at: post_details.paused_at.map(|dt| format_timestamp(&dt, tz)),
+        track_audience: post_details.track_audience,
+    }
+}
 
-#[post("/polling", data = "<request>")]
+fn wall_watcher_response(watcher: WallWatcher, tz: Tz) -> WallWatcherResponse {
+    WallWatcherResponse {
+        watcher_id: watcher.id,
+        owner_id: watcher.owner_id,
+        interval_seconds: watcher.interval_seconds,
+        duration_seconds: watcher.duration_seconds,
+        track_audience: watcher.track_audience,
+        include_pinned: watcher.include_pinned,
+        min_age_seconds: watcher.min_age_seconds,
+        keywords: watcher.keywords,
+        created_at: format_timestamp(&watcher.created_at, tz),
+        checked_at: watcher.checked_at.map(|dt| format_timestamp(&dt, tz)),
+    }
+}
+
+fn post_info_response(d: PostInfoData, tz: Tz) -> PostInfoDataResponse {
+    PostInfoDataResponse {
+        comments_count: d.comments_count,
+        likes_count: d.likes_count,
+        views_count: d.views_count,
+        reposts_count: d.reposts_count,
+        metrics: d.metrics,
+        info_time: format_timestamp(&d.info_time, tz),
+    }
+}
+
+fn post_meta_response(post_meta: PostMeta, tz: Tz) -> PostMetaResponse {
+    let meta = post_meta.meta;
+    PostMetaResponse {
+        version: post_meta.version,
+        owner_id: meta.owner_id,
+        from_id: meta.from_id,
+        published_at: format_timestamp(&meta.published_at, tz),
+        post_type: meta.post_type,
+        text: meta.text,
+        attachments: meta.attachments,
+        edited_at: meta.edited_at.map(|dt| format_timestamp(&dt, tz)),
+        is_deleted: meta.is_deleted,
+        fetched_at: format_timestamp(&post_meta.fetched_at, tz),
+    }
+}
+
+// Times without an offset are taken in the display zone of the request
+fn parse_query_time(
+    name: &str,
+    value: Option<String>,
+    tz: Tz,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    value
+        .map(|value| {
+            parse_timestamp(&value, tz).ok_or_else(|| {
+                AppError::Validation(format!(
+                    "Invalid {}, expected format: 2026-02-25T21:52:04Z",
+                    name
+                ))
+            })
+        })
+        .transpose()
+}
+
+fn parse_query_timezone(tz: Option<String>) -> Result<Tz, AppError> {
+    parse_timezone(tz.as_deref()).map_err(AppError::Validation)
+}
+
+fn post_not_found(scrapper_id: i32) -> AppError {
+    AppError::NotFound(format!("Post with scrapper_id {} not found", scrapper_id))
+}
+
+fn vk_post_not_found() -> AppError {
+    AppError::NotFound("Post not found in VK".to_string())
+}
+
+// Checks every post with as few wall.getById calls as possible, returns the error of each post that
+// can't be polled
+async fn check_vk_posts(vk: &Arc<dyn VkClient>, vk_ids: &[String]) -> HashMap<String, ErrorBody> {
+    if vk_ids.is_empty() {
+        return HashMap::new();
+    }
+
+    match vk.call_vk_batch(vk_ids).await {
+        // Missing posts and posts with empty stats are not found, as in post_polling
+        Ok(batch) => vk_ids
+            .iter()
+            .filter_map(|vk_id| {
+                let error = match (batch.invalid.get(vk_id), batch.stats.get(vk_id)) {
+                    (Some(reason), _) => AppError::Vk(reason.clone()),
+                    (None, Some(stats)) if !is_post_stats_empty(stats) => return None,
+                    (None, _) => vk_post_not_found(),
+                };
+                Some((vk_id.clone(), error.body()))
+            })
+            .collect(),
+        // One inaccessible post fails the whole request, check posts one by one to find it
+        Err(AppError::VkApi(e)) if e.is_post_error() && vk_ids.len() > 1 => {
+            let mut seen = HashSet::new();
+            let mut failed = HashMap::new();
+            for vk_id in vk_ids.iter().filter(|vk_id| seen.insert(*vk_id)) {
+                let error = match vk.call_vk(vk_id).await {
+                    Ok(stats) if is_post_stats_empty(&stats) => vk_post_not_found(),
+                    Ok(_) => continue,
+                    Err(e) => e,
+                };
+                failed.insert(vk_id.clone(), error.body());
+            }
+            failed
+        }
+        // VK could not check any of them
+        Err(e) => vk_ids
+            .iter()
+            .map(|vk_id| (vk_id.clone(), e.body()))
+            .collect(),
+    }
+}
+
+#[post("/polling?<tz>", data = "<request>")]
 pub async fn post_polling(
     request: Json<PollingRequest>,
+    tz: Option<String>,
+    idempotency_key: IdempotencyKeyHeader,
     pool: &State<Arc<PgPool>>,
-    scheduler: &State<Arc<JobScheduler>>,
-) -> Result<Json<PollingResponse>, status::BadRequest<String>> {
-    // Extract vk_id from vk_link (everything after https://vk.com/wall)
-    let vk_id = request
-        .vk_link
-        .strip_prefix("https://vk.com/wall")
-        .ok_or_else(|| {
-            status::BadRequest(
-                "Invalid VK link format. Expected: https://vk.com/wall...".to_string(),
-            )
-        })?
-        .to_string();
+    vk: &State<Arc<dyn VkClient>>,
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<PollingResponse>, AppError> {
+    // Any link to the post or its bare id comes down to the canonical owner_id_post_id
+    let vk_id = parse_vk_post_link(&request.vk_link).map_err(AppError::Validation)?;
+
+    // Validate requested duration before calling VK
+    let duration_seconds =
+        resolve_duration_seconds(request.duration.as_ref()).map_err(AppError::Validation)?;
+    let tz = parse_query_timezone(tz)?;
+    let idempotency_key =
+        parse_idempotency_key(idempotency_key.0.as_deref()).map_err(AppError::Validation)?;
+
+    // What a retry has to repeat to get the stored response, links to the same post are equal
+    let fingerprint = json!({
+        "vk_id": vk_id,
+        "prolong": request.prolong,
+        "duration_seconds": duration_seconds,
+        "track_audience": request.track_audience,
+        "tz": tz.name(),
+    });
+
+    // A retry of a finished request is answered without calling VK again
+    if let Some(key) = &idempotency_key
+        && let Some(response) =
+            get_idempotent_response(pool, key, &fingerprint, clock.now()).await?
+    {
+        return Ok(Json(response));
+    }
 
     // Validate post exists in VK by calling API
-    let stats = call_vk(&vk_id)
-        .await
-        .map_err(|e| status::BadRequest(format!("VK API error: {:?}", e)))?;
+    let stats = vk.call_vk(&vk_id).await?;
 
     // Check if post stats are empty - post not found
     if is_post_stats_empty(&stats) {
-        return Err(status::BadRequest("Post not found in VK".to_string()));
-    }
-
-    // Get or create post in database with prolong option
-    let post_details = get_or_create_post_with_prolong(pool, &vk_id, request.prolong)
-        .await
-        .map_err(|e| status::BadRequest(format!("Failed to get or create post: {}", e)))?;
-
-    // Get pooling delta from utils
-    let pooling_delta = get_pooling_delta_seconds();
-
-    // Check if there's a recent post_info entry (within 2*pooling_delta)
-    let has_recent = has_recent_post_info(pool, post_details.id, pooling_delta as i64)
-        .await
-        .map_err(|e| status::BadRequest(format!("Failed to check recent post info: {}", e)))?;
-
-    if !has_recent {
-        // Create cron job for polling only if there's no recent polling
-        let pool_inner = pool.inner().clone();
-        let db_post_id = post_details.id;
-        let job = Job::new_async(
-            format!("*/{} * * * * *", pooling_delta).as_str(),
-            move |job_id, locked_scheduler| {
-                let pool = pool_inner.clone();
-                let db_post_id = db_post_id;
-                Box::pin(async move {
-                    if let Err(e) =
-                        poll_post_stats(&job_id, &locked_scheduler, &pool, db_post_id).await
-                    {
-                        eprintln!("Error polling post stats: {}", e);
-                    }
-                })
+        return Err(vk_post_not_found());
+    }
+
+    // Get or create post in database with prolong option, with a key the response is stored
+    // in the same transaction
+    let response = match idempotency_key {
+        Some(key) => {
+            get_or_create_post_idempotent(
+                pool,
+                &key,
+                &fingerprint,
+                &vk_id,
+                request.prolong,
+                duration_seconds,
+                request.track_audience,
+                clock.now(),
+                |post_details| polling_response(post_details, tz),
+            )
+            .await?
+        }
+        None => {
+            let post_details = get_or_create_post_with_prolong(
+                pool,
+                &vk_id,
+                request.prolong,
+                duration_seconds,
+                request.track_audience,
+                clock.now(),
+            )
+            .await?;
+            polling_response(post_details, tz)
+        }
+    };
+
+    // No job is created here: the polling task picks the post up on its next tick
+
+    // Return response
+    Ok(Json(response))
+}
+
+#[post("/polling/batch?<tz>", data = "<request>")]
+pub async fn post_polling_batch(
+    request: Json<BatchPollingRequest>,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+    vk: &State<Arc<dyn VkClient>>,
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<BatchPollingResponse>, AppError> {
+    let posts = request.into_inner().posts;
+    let max_posts = get_polling_batch_max_posts();
+    if posts.is_empty() || posts.len() > max_posts {
+        return Err(AppError::Validation(format!(
+            "Batch must contain from 1 to {} posts, got {}",
+            max_posts,
+            posts.len()
+        )));
+    }
+    let tz = parse_query_timezone(tz)?;
+
+    // A bad link or duration fails only its own item, before VK is called
+    let checked: Vec<Result<(String, i64), AppError>> = posts
+        .iter()
+        .map(|item| {
+            let vk_id = parse_vk_post_link(&item.vk_link).map_err(AppError::Validation)?;
+            let duration_seconds =
+                resolve_duration_seconds(item.duration.as_ref()).map_err(AppError::Validation)?;
+            Ok((vk_id, duration_seconds))
+        })
+        .collect();
+
+    let vk_ids: Vec<String> = checked
+        .iter()
+        .filter_map(|item| item.as_ref().ok())
+        .map(|(vk_id, _)| vk_id.clone())
+        .collect();
+    let failed = check_vk_posts(vk, &vk_ids).await;
+
+    // Every post gets its own transaction, so one failed item does not roll back the others
+    let now = clock.now();
+    let mut results = Vec::with_capacity(posts.len());
+    for (item, checked) in posts.into_iter().zip(checked) {
+        let result = match checked {
+            Ok((vk_id, duration_seconds)) => match failed.get(&vk_id) {
+                Some(error) => Err(error.clone()),
+                None => get_or_create_post_with_prolong(
+                    pool,
+                    &vk_id,
+                    item.prolong,
+                    duration_seconds,
+                    item.track_audience,
+                    now,
+                )
+                .await
+                .map_err(|e| {
+                    eprintln!("Failed to create polling task for {}: {}", vk_id, e);
+                    e.body()
+                }),
             },
-        )
-        .map_err(|e| status::BadRequest(format!("Failed to create job: {}", e)))?;
+            Err(e) => Err(e.body()),
+        };
 
-        // Add job to the scheduler
-        scheduler
-            .add(job)
-            .await
-            .map_err(|e| status::BadRequest(format!("Failed to add job: {}", e)))?;
+        results.push(match result {
+            Ok(post_details) => BatchPollingItemResponse {
+                vk_link: item.vk_link,
+                post: Some(polling_response(post_details, tz)),
+                error: None,
+            },
+            Err(error) => BatchPollingItemResponse {
+                vk_link: item.vk_link,
+                post: None,
+                error: Some(error),
+            },
+        });
     }
 
-    // Return response
-    Ok(Json(PollingResponse {
-        scrapper_id: post_details.id,
-        vk_id: post_details.vk_id,
-        dt_parse_begin: post_details
-            .dt_parse_begin
-            .format("%Y-%m-%dT%H:%M:%S")
-            .to_string(),
-        dt_parse_end: post_details
-            .dt_parse_end
-            .format("%Y-%m-%dT%H:%M:%S")
-            .to_string(),
-    }))
+    Ok(Json(BatchPollingResponse { results }))
 }
 
-#[get("/polling?<scrapper_id>")]
+#[allow(clippy::too_many_arguments)]
+#[get("/polling?<scrapper_id>&<from>&<to>&<step>&<agg>&<tz>")]
 pub async fn get_polling(
     scrapper_id: i32,
+    from: Option<String>,
+    to: Option<String>,
+    step: Option<String>,
+    agg: Option<String>,
+    tz: Option<String>,
     pool: &State<Arc<PgPool>>,
-) -> Result<Json<GetPollingResponse>, status::NotFound<String>> {
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<GetPollingResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+    let from = parse_query_time("from", from, tz)?;
+    let to = parse_query_time("to", to, tz)?;
+
+    // Snapshots are bucketed only when step is given, agg defaults to the last snapshot
+    let step_seconds = step
+        .map(|step| parse_step_seconds(&step))
+        .transpose()
+        .map_err(AppError::Validation)?;
+    let aggregation = match (&agg, step_seconds) {
+        (Some(_), None) => {
+            return Err(AppError::Validation(
+                "Parameter agg requires step".to_string(),
+            ));
+        }
+        (Some(agg), Some(_)) => parse_aggregation(agg).map_err(AppError::Validation)?,
+        (None, _) => Aggregation::Last,
+    };
+
     // Get post with data
-    let post_with_data = get_post_with_data(pool, scrapper_id)
-        .await
-        .map_err(|e| status::NotFound(format!("Database error: {}", e)))?
-        .ok_or_else(|| {
-            status::NotFound(format!("Post with scrapper_id {} not found", scrapper_id))
-        })?;
+    let mut post_with_data = get_post_with_data(pool, scrapper_id, from, to)
+        .await?
+        .ok_or_else(|| post_not_found(scrapper_id))?;
+
+    if let Some(step_seconds) = step_seconds {
+        post_with_data.data = bucket_post_info(post_with_data.data, step_seconds, aggregation);
+    }
 
     // Get current timestamp
-    let dt_current = chrono::Local::now().naive_local();
+    let dt_current = clock.now();
 
     // Convert data to response format
     let data: Vec<PostInfoDataResponse> = post_with_data
         .data
         .into_iter()
-        .map(|d| PostInfoDataResponse {
-            comments_count: d.comments_count,
-            likes_count: d.likes_count,
-            views_count: d.views_count,
-            reposts_count: d.reposts_count,
-            info_time: d.info_time.format("%Y-%m-%dT%H:%M:%S").to_string(),
-        })
+        .map(|d| post_info_response(d, tz))
         .collect();
 
     Ok(Json(GetPollingResponse {
         scrapper_id: post_with_data.id,
         vk_id: post_with_data.vk_id,
-        dt_parse_begin: post_with_data
-            .dt_parse_begin
-            .format("%Y-%m-%dT%H:%M:%S")
-            .to_string(),
-        dt_parse_end: post_with_data
-            .dt_parse_end
-            .format("%Y-%m-%dT%H:%M:%S")
-            .to_string(),
-        dt_current: dt_current.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        dt_parse_begin: format_timestamp(&post_with_data.dt_parse_begin, tz),
+        dt_parse_end: format_timestamp(&post_with_data.dt_parse_end, tz),
+        paused_at: post_with_data.paused_at.map(|dt| format_timestamp(&dt, tz)),
+        dt_current: format_timestamp(&dt_current, tz),
+        meta: post_with_data.meta.map(|meta| post_meta_response(meta, tz)),
         data,
     }))
 }
+
+#[get("/polling/meta?<scrapper_id>&<tz>")]
+pub async fn get_polling_meta(
+    scrapper_id: i32,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+) -> Result<Json<PostMetaHistoryResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+
+    if get_post_details(pool, scrapper_id).await?.is_none() {
+        return Err(post_not_found(scrapper_id));
+    }
+
+    let versions = get_post_meta_versions(pool, scrapper_id)
+        .await?
+        .into_iter()
+        .map(|meta| post_meta_response(meta, tz))
+        .collect();
+
+    Ok(Json(PostMetaHistoryResponse {
+        scrapper_id,
+        versions,
+    }))
+}
+
+#[get("/polling/comments?<scrapper_id>&<cursor>&<since>&<tz>")]
+pub async fn get_polling_comments(
+    scrapper_id: i32,
+    cursor: Option<i64>,
+    since: Option<String>,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+) -> Result<Json<CommentsResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+    let since = parse_query_time("since", since, tz)?;
+
+    if get_post_details(pool, scrapper_id).await?.is_none() {
+        return Err(post_not_found(scrapper_id));
+    }
+
+    let comments = get_post_comments(pool, scrapper_id, cursor, since).await?;
+    // Without new comments the client keeps its cursor
+    let next_cursor = comments
+        .iter()
+        .map(|comment| comment.seq)
+        .max()
+        .or(cursor)
+        .unwrap_or(0);
+
+    let comments = comments
+        .into_iter()
+        .map(|comment| CommentResponse {
+            comment_id: comment.comment_id,
+            from_id: comment.from_id,
+            published_at: format_timestamp(&comment.published_at, tz),
+            text: comment.text,
+            likes_count: comment.likes_count,
+            parent_id: comment.parent_id,
+            first_seen_at: format_timestamp(&comment.first_seen_at, tz),
+        })
+        .collect();
+
+    Ok(Json(CommentsResponse {
+        scrapper_id,
+        next_cursor,
+        comments,
+    }))
+}
+
+#[get("/polling/audience?<scrapper_id>&<kind>&<cursor>&<since>&<tz>")]
+pub async fn get_polling_audience(
+    scrapper_id: i32,
+    kind: Option<String>,
+    cursor: Option<i64>,
+    since: Option<String>,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+) -> Result<Json<AudienceResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+    let since = parse_query_time("since", since, tz)?;
+    let kind = kind
+        .map(|kind| parse_audience_kind(&kind))
+        .transpose()
+        .map_err(AppError::Validation)?;
+
+    if get_post_details(pool, scrapper_id).await?.is_none() {
+        return Err(post_not_found(scrapper_id));
+    }
+
+    let audience = get_post_audience(pool, scrapper_id, kind, cursor, since).await?;
+    // Without new users the client keeps its cursor
+    let next_cursor = audience
+        .iter()
+        .map(|entry| entry.seq)
+        .max()
+        .or(cursor)
+        .unwrap_or(0);
+
+    let audience = audience
+        .into_iter()
+        .map(|entry| AudienceEntryResponse {
+            user_id: entry.user_id,
+            kind: entry.kind,
+            engaged_at: entry.engaged_at.map(|dt| format_timestamp(&dt, tz)),
+            first_seen_at: format_timestamp(&entry.first_seen_at, tz),
+            last_seen_at: format_timestamp(&entry.last_seen_at, tz),
+        })
+        .collect();
+
+    Ok(Json(AudienceResponse {
+        scrapper_id,
+        next_cursor,
+        audience,
+    }))
+}
+
+#[delete("/polling?<scrapper_id>&<tz>")]
+pub async fn delete_polling(
+    scrapper_id: i32,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+    registry: &State<JobRegistry>,
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<PollingResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+
+    // End the parsing window now, collected data stays available through get_polling
+    let post_details = finish_post(pool, scrapper_id, clock.now())
+        .await?
+        .ok_or_else(|| post_not_found(scrapper_id))?;
+
+    // Stop the job that may be polling it right now, so it makes no more VK calls
+    if let Some(job_id) = registry.cancel(post_details.id) {
+        println!(
+            "Stopped polling job {} for post {}",
+            job_id, post_details.id
+        );
+    }
+
+    Ok(Json(polling_response(post_details, tz)))
+}
+
+#[post("/polling/pause?<scrapper_id>&<tz>")]
+pub async fn pause_polling(
+    scrapper_id: i32,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<PollingResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+
+    if let Some(post_details) = pause_post(pool, scrapper_id, clock.now()).await? {
+        return Ok(Json(polling_response(post_details, tz)));
+    }
+
+    // Nothing was paused: either there is no such post or its window is over
+    match get_post_details(pool, scrapper_id).await? {
+        Some(_) => Err(AppError::Conflict(format!(
+            "Post with scrapper_id {} is already finished",
+            scrapper_id
+        ))),
+        None => Err(post_not_found(scrapper_id)),
+    }
+}
+
+#[post("/polling/resume?<scrapper_id>&<extend>&<tz>")]
+pub async fn resume_polling(
+    scrapper_id: i32,
+    extend: Option<bool>,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<PollingResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+
+    // By default the window is shifted by the pause length, extend=false keeps the original end
+    let resumed = resume_post(pool, scrapper_id, extend.unwrap_or(true), clock.now()).await?;
+
+    // Resuming a post that is not paused returns it unchanged
+    let post_details = match resumed {
+        Some(post_details) => Some(post_details),
+        None => get_post_details(pool, scrapper_id).await?,
+    };
+
+    post_details
+        .map(|post_details| Json(polling_response(post_details, tz)))
+        .ok_or_else(|| post_not_found(scrapper_id))
+}
+
+#[allow(clippy::too_many_arguments)]
+#[get("/posts?<vk_id>&<owner>&<active_at>&<created_from>&<created_to>&<cursor>&<limit>&<tz>")]
+pub async fn list_posts(
+    vk_id: Option<String>,
+    owner: Option<String>,
+    active_at: Option<String>,
+    created_from: Option<String>,
+    created_to: Option<String>,
+    cursor: Option<i32>,
+    limit: Option<i64>,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<PostListResponse>, AppError> {
+    let limit = limit.unwrap_or(50).clamp(1, 200);
+    let tz = parse_query_timezone(tz)?;
+
+    // One extra row tells whether there is a next page
+    let filter = PostListFilter {
+        vk_id: vk_id
+            .map(|vk_id| parse_vk_post_link(&vk_id))
+            .transpose()
+            .map_err(AppError::Validation)?,
+        owner_id: owner
+            .map(|owner| parse_vk_owner_id(&owner).map(|owner_id| owner_id.to_string()))
+            .transpose()
+            .map_err(AppError::Validation)?,
+        active_at: parse_query_time("active_at", active_at, tz)?,
+        created_from: parse_query_time("created_from", created_from, tz)?,
+        created_to: parse_query_time("created_to", created_to, tz)?,
+        cursor,
+        limit: limit + 1,
+    };
+
+    let mut posts = get_posts(pool, &filter, clock.now()).await?;
+
+    let next_cursor = if posts.len() as i64 > limit {
+        posts.truncate(limit as usize);
+        posts.last().map(|post| post.id)
+    } else {
+        None
+    };
+
+    let posts = posts
+        .into_iter()
+        .map(|post| PostListItemResponse {
+            scrapper_id: post.id,
+            owner_id: post.vk_id.split('_').next().unwrap_or_default().to_string(),
+            vk_id: post.vk_id,
+            status: post.status,
+            dt_parse_begin: format_timestamp(&post.dt_parse_begin, tz),
+            dt_parse_end: format_timestamp(&post.dt_parse_end, tz),
+            paused_at: post.paused_at.map(|dt| format_timestamp(&dt, tz)),
+            snapshot_count: post.snapshot_count,
+            skipped_count: post.skipped_count,
+            meta: post.meta.map(|meta| post_meta_response(meta, tz)),
+            latest_snapshot: post.latest_snapshot.map(|d| post_info_response(d, tz)),
+        })
+        .collect();
+
+    Ok(Json(PostListResponse { posts, next_cursor }))
+}
+
+#[post("/watchers?<tz>", data = "<request>")]
+pub async fn create_watcher(
+    request: Json<WallWatcherRequest>,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+    vk: &State<Arc<dyn VkClient>>,
+    clock: &State<Arc<dyn Clock>>,
+) -> Result<Json<WallWatcherResponse>, AppError> {
+    let request = request.into_inner();
+    if request.owner_id == 0 {
+        return Err(AppError::Validation(
+            "Invalid owner_id, expected a community id with a minus sign or a user id".to_string(),
+        ));
+    }
+
+    let keywords: Vec<String> = request
+        .keywords
+        .iter()
+        .map(|keyword| keyword.trim().to_string())
+        .filter(|keyword| !keyword.is_empty())
+        .collect();
+    let watcher = NewWallWatcher {
+        owner_id: request.owner_id,
+        interval_seconds: resolve_watch_interval_seconds(request.interval.as_ref())
+            .map_err(AppError::Validation)?,
+        duration_seconds: resolve_duration_seconds(request.duration.as_ref())
+            .map_err(AppError::Validation)?,
+        track_audience: request.track_audience,
+        include_pinned: request.include_pinned,
+        min_age_seconds: resolve_min_age_seconds(request.min_age.as_ref())
+            .map_err(AppError::Validation)?,
+        keywords,
+    };
+    let tz = parse_query_timezone(tz)?;
+
+    // A wall that can't be read now would fail every check, so it is rejected right away
+    vk.get_wall(watcher.owner_id, 0).await?;
+
+    let watcher = create_wall_watcher(pool, &watcher, clock.now()).await?;
+
+    // The first check happens on the next dispatcher tick
+    Ok(Json(wall_watcher_response(watcher, tz)))
+}
+
+#[get("/watchers?<tz>")]
+pub async fn list_watchers(
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+) -> Result<Json<WallWatcherListResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+
+    let watchers = get_wall_watchers(pool)
+        .await?
+        .into_iter()
+        .map(|watcher| wall_watcher_response(watcher, tz))
+        .collect();
+
+    Ok(Json(WallWatcherListResponse { watchers }))
+}
+
+#[delete("/watchers?<watcher_id>&<tz>")]
+pub async fn delete_watcher(
+    watcher_id: i32,
+    tz: Option<String>,
+    pool: &State<Arc<PgPool>>,
+) -> Result<Json<WallWatcherResponse>, AppError> {
+    let tz = parse_query_timezone(tz)?;
+
+    // Posts enrolled by the watcher keep being polled until their windows end
+    let watcher = delete_wall_watcher(pool, watcher_id)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Wall watcher with id {} not found", watcher_id))
+        })?;
+
+    Ok(Json(wall_watcher_response(watcher, tz)))
+}
+
+#[get("/admin/vk_tokens")]
+pub fn vk_tokens() -> Json<VkTokenPoolResponse> {
+    let pool = vk_token_pool();
+
+    let tokens: Vec<VkTokenStatsResponse> = pool
+        .stats()
+        .into_iter()
+        .map(|stats| VkTokenStatsResponse {
+            token: stats.token,
+            status: match stats.status {
+                TokenStatus::Active => "active",
+                TokenStatus::CoolingDown => "cooling_down",
+                TokenStatus::Revoked => "revoked",
+            }
+            .to_string(),
+            uses: stats.uses,
+            failures: stats.failures,
+            rate_per_second: stats.rate_per_second,
+            queue_depth: stats.queue_depth,
+        })
+        .collect();
+
+    Json(VkTokenPoolResponse {
+        rotation: match pool.rotation() {
+            TokenRotation::RoundRobin => "round_robin",
+            TokenRotation::LeastRecentlyUsed => "lru",
+        }
+        .to_string(),
+        queue_depth: tokens.iter().map(|token| token.queue_depth).sum(),
+        tokens,
+    })
+}
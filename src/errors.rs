@@ -0,0 +1,246 @@
+use crate::models::{ErrorBody, ErrorResponse};
+use rocket::http::Status;
+use rocket::response::{self, Responder};
+use rocket::serde::json::Json;
+use rocket::{Request, Response};
+use std::fmt;
+use std::time::Duration;
+
+/// Errors shared by the VK client, the database layer, the polling tasks and the endpoints.
+#[derive(Debug)]
+pub enum AppError {
+    /// VK API is unreachable or returned something unusable
+    Vk(String),
+    /// VK API answered with an `error` object
+    VkApi(VkError),
+    Db(sqlx::Error),
+    /// The request itself is wrong: bad link, duration, query parameter
+    Validation(String),
+    NotFound(String),
+    /// The request is valid but the post is in a state that does not allow it
+    Conflict(String),
+    /// VK requests are throttled on our side: every token rests or the request queue is too long
+    RateLimited {
+        message: String,
+        retry_after: Duration,
+    },
+    /// Polling tasks can't be scheduled right now
+    Scheduler(String),
+}
+
+impl AppError {
+    pub fn status(&self) -> Status {
+        match self {
+            AppError::Vk(_) => Status::BadGateway,
+            AppError::VkApi(e) => e.status(),
+            AppError::Db(e) if is_db_unavailable(e) => Status::ServiceUnavailable,
+            AppError::Db(_) => Status::InternalServerError,
+            AppError::Validation(_) => Status::BadRequest,
+            AppError::NotFound(_) => Status::NotFound,
+            AppError::Conflict(_) => Status::Conflict,
+            AppError::RateLimited { .. } => Status::ServiceUnavailable,
+            AppError::Scheduler(_) => Status::ServiceUnavailable,
+        }
+    }
+
+    /// Machine-readable code returned to the client next to the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Vk(_) => "vk_error",
+            AppError::VkApi(e) => e.code(),
+            AppError::Db(e) if is_db_unavailable(e) => "db_unavailable",
+            AppError::Db(_) => "db_error",
+            AppError::Validation(_) => "validation_error",
+            AppError::NotFound(_) => "not_found",
+            AppError::Conflict(_) => "conflict",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::Scheduler(_) => "scheduler_error",
+        }
+    }
+
+    /// Code and message as returned to the client, for errors reported inside a response.
+    pub fn body(&self) -> ErrorBody {
+        ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+        }
+    }
+
+    /// Whether the same call may succeed later without any change on our side.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            AppError::Vk(_) | AppError::RateLimited { .. } | AppError::Scheduler(_) => true,
+            AppError::VkApi(e) => e.is_retryable(),
+            AppError::Db(e) => is_db_unavailable(e),
+            AppError::Validation(_) | AppError::NotFound(_) | AppError::Conflict(_) => false,
+        }
+    }
+}
+
+/// Error object returned by VK API, classified by its `error_code`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VkError {
+    /// 5: the access token is invalid or expired
+    AuthFailed(String),
+    /// 6: too many requests per second
+    TooManyRequests(String),
+    /// 9: flood control, the same action is repeated too often
+    FloodControl(String),
+    /// 1, 10: unknown or internal server error on the VK side
+    Internal(String),
+    /// 15, 30, 203, 210: the post or its wall is private
+    AccessDenied(String),
+    /// 18: the post owner was deleted or banned
+    PostDeleted(String),
+    Other {
+        code: i64,
+        message: String,
+    },
+}
+
+impl VkError {
+    pub fn from_code(code: i64, message: String) -> Self {
+        match code {
+            5 => VkError::AuthFailed(message),
+            6 => VkError::TooManyRequests(message),
+            9 => VkError::FloodControl(message),
+            1 | 10 => VkError::Internal(message),
+            15 | 30 | 203 | 210 => VkError::AccessDenied(message),
+            18 => VkError::PostDeleted(message),
+            code => VkError::Other { code, message },
+        }
+    }
+
+    /// Rate limits and VK outages pass by themselves, everything else needs a fix first.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            VkError::TooManyRequests(_) | VkError::FloodControl(_) | VkError::Internal(_)
+        )
+    }
+
+    /// The error is caused by a particular post rather than by the token or VK itself.
+    pub fn is_post_error(&self) -> bool {
+        matches!(self, VkError::AccessDenied(_) | VkError::PostDeleted(_))
+    }
+
+    fn status(&self) -> Status {
+        match self {
+            VkError::TooManyRequests(_) | VkError::FloodControl(_) => Status::ServiceUnavailable,
+            VkError::AccessDenied(_) => Status::Forbidden,
+            VkError::PostDeleted(_) => Status::NotFound,
+            VkError::AuthFailed(_) | VkError::Internal(_) | VkError::Other { .. } => {
+                Status::BadGateway
+            }
+        }
+    }
+
+    fn code(&self) -> &'static str {
+        match self {
+            VkError::AuthFailed(_) => "vk_auth_failed",
+            VkError::TooManyRequests(_) | VkError::FloodControl(_) => "vk_rate_limited",
+            VkError::Internal(_) => "vk_internal_error",
+            VkError::AccessDenied(_) => "vk_access_denied",
+            VkError::PostDeleted(_) => "vk_post_deleted",
+            VkError::Other { .. } => "vk_error",
+        }
+    }
+}
+
+impl fmt::Display for VkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VkError::AuthFailed(message) => write!(f, "authorization failed: {}", message),
+            VkError::TooManyRequests(message) => write!(f, "too many requests: {}", message),
+            VkError::FloodControl(message) => write!(f, "flood control: {}", message),
+            VkError::Internal(message) => write!(f, "internal error: {}", message),
+            VkError::AccessDenied(message) => write!(f, "access denied: {}", message),
+            VkError::PostDeleted(message) => write!(f, "post deleted: {}", message),
+            VkError::Other { code, message } => write!(f, "error {}: {}", code, message),
+        }
+    }
+}
+
+// The pool can't hand out a connection, retrying later may succeed
+fn is_db_unavailable(e: &sqlx::Error) -> bool {
+    matches!(
+        e,
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_)
+    )
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Vk(message) => write!(f, "VK API error: {}", message),
+            AppError::VkApi(e) => write!(f, "VK API error: {}", e),
+            AppError::Db(e) => write!(f, "Database error: {}", e),
+            AppError::Validation(message)
+            | AppError::NotFound(message)
+            | AppError::Conflict(message) => write!(f, "{}", message),
+            AppError::RateLimited { message, .. } => write!(f, "Rate limited: {}", message),
+            AppError::Scheduler(message) => write!(f, "Scheduler error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for AppError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            AppError::Db(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<VkError> for AppError {
+    fn from(e: VkError) -> Self {
+        AppError::VkApi(e)
+    }
+}
+
+impl From<sqlx::Error> for AppError {
+    fn from(e: sqlx::Error) -> Self {
+        AppError::Db(e)
+    }
+}
+
+fn error_json(code: &str, message: String) -> Json<ErrorResponse> {
+    Json(ErrorResponse {
+        error: ErrorBody {
+            code: code.to_string(),
+            message,
+        },
+    })
+}
+
+impl<'r> Responder<'r, 'static> for AppError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        if status.class().is_server_error() {
+            eprintln!("{} {}: {}", request.method(), request.uri(), self);
+        }
+
+        let mut response =
+            Response::build_from(Json(ErrorResponse { error: self.body() }).respond_to(request)?);
+        if let AppError::RateLimited { retry_after, .. } = &self {
+            // Whole seconds, rounded up so the client does not come back too early
+            let seconds = retry_after.as_millis().div_ceil(1000).max(1);
+            response.raw_header("Retry-After", seconds.to_string());
+        }
+
+        response.status(status).ok()
+    }
+}
+
+/// Keeps errors raised by Rocket itself (unknown route, malformed body) in the same JSON shape.
+#[rocket::catch(default)]
+pub fn default_catcher(status: Status, _request: &Request) -> Json<ErrorResponse> {
+    let code = match status.code {
+        400 | 422 => "validation_error",
+        404 => "not_found",
+        _ => "http_error",
+    };
+
+    error_json(code, status.reason_lossy().to_string())
+}
@@ -0,0 +1,356 @@
+use crate::errors::{AppError, VkError};
+use crate::models::{
+    AudienceKind, VkAudienceEntry, VkAudiencePage, VkBatchStats, VkComment, VkCommentsPage,
+    VkPostMeta, VkPostStats, VkWallPage, VkWallPost,
+};
+use crate::vk_api::{AUDIENCE_PAGE_SIZE, COMMENTS_PAGE_SIZE, VkClient, WALL_PAGE_SIZE};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Scripted answer of the fake client for one post.
+#[derive(Debug, Clone)]
+pub enum FakeReply {
+    Stats(VkPostStats),
+    // VK does not return the post
+    Missing,
+    // VK answers with an error object, which fails the whole request
+    Error(VkError),
+    // VK can't be reached, which fails the whole request
+    Unavailable(String),
+    // The request did not get a rate limit slot in time
+    Throttled(String),
+}
+
+/// In-memory VK client answering with replies scripted per post id.
+///
+/// Replies of a post are used in order and the last one repeats, posts without
+/// replies get the default stats or are missing when there are none.
+#[derive(Default)]
+pub struct FakeVkClient {
+    state: Mutex<FakeState>,
+}
+
+#[derive(Default)]
+struct FakeState {
+    replies: HashMap<String, VecDeque<FakeReply>>,
+    default_stats: Option<VkPostStats>,
+    // Returned next to the stats of the post, whatever reply they come from
+    meta: HashMap<String, VkPostMeta>,
+    // Every comment of the post, replies included, in the order they were written
+    comments: HashMap<String, Vec<VkComment>>,
+    // Comments VK still pages over but returns as deleted
+    deleted_comments: HashMap<String, HashSet<i64>>,
+    // Users who liked or reposted the post
+    audience: HashMap<(String, AudienceKind), Vec<VkAudienceEntry>>,
+    // Users VK pages over but returns in a form that can't be read
+    unreadable_audience: HashMap<(String, AudienceKind), HashSet<i64>>,
+    // Posts of the wall in the order wall.get returns them
+    walls: HashMap<i64, Vec<VkWallPost>>,
+    // Posts VK pages over but returns in a form that can't be read
+    unreadable_wall_posts: HashMap<i64, HashSet<String>>,
+    // Walls VK refuses to show, such as closed communities
+    wall_errors: HashMap<i64, VkError>,
+    latency: Duration,
+    // Post ids of every call, in call order
+    calls: Vec<Vec<String>>,
+    // Post id, thread and offset of every comments call
+    comment_calls: Vec<(String, Option<i64>, usize)>,
+    // Post id, kind and offset of every audience call
+    audience_calls: Vec<(String, AudienceKind, usize)>,
+    // Owner id and offset of every wall call
+    wall_calls: Vec<(i64, usize)>,
+}
+
+impl FakeVkClient {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every call for the post with the same stats.
+    pub fn set_stats(&self, post_id: &str, stats: VkPostStats) {
+        self.set_replies(post_id, vec![FakeReply::Stats(stats)]);
+    }
+
+    pub fn set_replies(&self, post_id: &str, replies: Vec<FakeReply>) {
+        let mut state = self.state.lock().unwrap();
+        state.replies.insert(post_id.to_string(), replies.into());
+    }
+
+    pub fn set_meta(&self, post_id: &str, meta: VkPostMeta) {
+        let mut state = self.state.lock().unwrap();
+        state.meta.insert(post_id.to_string(), meta);
+    }
+
+    /// Comments of the post, paged and split into threads like wall.getComments does.
+    pub fn set_comments(&self, post_id: &str, comments: Vec<VkComment>) {
+        let mut state = self.state.lock().unwrap();
+        state.comments.insert(post_id.to_string(), comments);
+    }
+
+    /// Marks comments as deleted: they keep their place in the paging but are not returned.
+    pub fn delete_comments(&self, post_id: &str, ids: &[i64]) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .deleted_comments
+            .entry(post_id.to_string())
+            .or_default()
+            .extend(ids);
+    }
+
+    /// Users who liked or reposted the post, paged like likes.getList and wall.getReposts do.
+    pub fn set_audience(&self, post_id: &str, kind: AudienceKind, entries: Vec<VkAudienceEntry>) {
+        let mut state = self.state.lock().unwrap();
+        state.audience.insert((post_id.to_string(), kind), entries);
+    }
+
+    /// Marks users as unreadable: they keep their place in the paging but are not returned.
+    pub fn set_unreadable_audience(&self, post_id: &str, kind: AudienceKind, user_ids: &[i64]) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .unreadable_audience
+            .entry((post_id.to_string(), kind))
+            .or_default()
+            .extend(user_ids);
+    }
+
+    /// Posts of the wall, pinned one first and then newest first, as wall.get returns them.
+    pub fn set_wall(&self, owner_id: i64, posts: Vec<VkWallPost>) {
+        let mut state = self.state.lock().unwrap();
+        state.walls.insert(owner_id, posts);
+    }
+
+    /// Marks posts as unreadable: they keep their place in the paging but are not returned.
+    pub fn set_unreadable_wall_posts(&self, owner_id: i64, vk_ids: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        state
+            .unreadable_wall_posts
+            .entry(owner_id)
+            .or_default()
+            .extend(vk_ids.iter().map(|vk_id| vk_id.to_string()));
+    }
+
+    pub fn set_wall_error(&self, owner_id: i64, error: VkError) {
+        let mut state = self.state.lock().unwrap();
+        state.wall_errors.insert(owner_id, error);
+    }
+
+    pub fn set_default_stats(&self, stats: Option<VkPostStats>) {
+        self.state.lock().unwrap().default_stats = stats;
+    }
+
+    /// Delay of every call, as if VK answered slowly.
+    pub fn set_latency(&self, latency: Duration) {
+        self.state.lock().unwrap().latency = latency;
+    }
+
+    pub fn call_count(&self) -> usize {
+        self.state.lock().unwrap().calls.len()
+    }
+
+    pub fn calls(&self) -> Vec<Vec<String>> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn comment_calls(&self) -> Vec<(String, Option<i64>, usize)> {
+        self.state.lock().unwrap().comment_calls.clone()
+    }
+
+    pub fn audience_calls(&self) -> Vec<(String, AudienceKind, usize)> {
+        self.state.lock().unwrap().audience_calls.clone()
+    }
+
+    pub fn wall_calls(&self) -> Vec<(i64, usize)> {
+        self.state.lock().unwrap().wall_calls.clone()
+    }
+
+    // Records the call and takes the next reply of every post
+    fn next_replies(
+        &self,
+        post_ids: &[String],
+    ) -> (Duration, Vec<(String, FakeReply, Option<VkPostMeta>)>) {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(post_ids.to_vec());
+
+        let replies = post_ids
+            .iter()
+            .map(|post_id| {
+                let reply = match state.replies.get_mut(post_id) {
+                    Some(replies) if replies.len() > 1 => replies.pop_front().unwrap(),
+                    Some(replies) => replies.front().cloned().unwrap_or(FakeReply::Missing),
+                    None => state
+                        .default_stats
+                        .clone()
+                        .map_or(FakeReply::Missing, FakeReply::Stats),
+                };
+                (post_id.clone(), reply, state.meta.get(post_id).cloned())
+            })
+            .collect();
+
+        (state.latency, replies)
+    }
+}
+
+#[rocket::async_trait]
+impl VkClient for FakeVkClient {
+    async fn call_vk(&self, post_id: &str) -> Result<VkPostStats, AppError> {
+        let batch = self.call_vk_batch(&[post_id.to_string()]).await?;
+
+        Ok(batch.stats.get(post_id).cloned().unwrap_or_default())
+    }
+
+    async fn call_vk_batch(&self, post_ids: &[String]) -> Result<VkBatchStats, AppError> {
+        let mut seen = HashSet::new();
+        let unique_ids: Vec<String> = post_ids
+            .iter()
+            .filter(|id| seen.insert(*id))
+            .cloned()
+            .collect();
+
+        let (latency, replies) = self.next_replies(&unique_ids);
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        let mut stats = HashMap::new();
+        let mut meta = HashMap::new();
+        let mut missing = Vec::new();
+        for (post_id, reply, post_meta) in replies {
+            match reply {
+                FakeReply::Stats(post_stats) => {
+                    if let Some(post_meta) = post_meta {
+                        meta.insert(post_id.clone(), post_meta);
+                    }
+                    stats.insert(post_id, post_stats);
+                }
+                FakeReply::Missing => missing.push(post_id),
+                FakeReply::Error(e) => return Err(e.into()),
+                FakeReply::Unavailable(message) => return Err(AppError::Vk(message)),
+                FakeReply::Throttled(message) => {
+                    return Err(AppError::RateLimited {
+                        message,
+                        retry_after: Duration::from_secs(1),
+                    });
+                }
+            }
+        }
+
+        Ok(VkBatchStats {
+            stats,
+            meta,
+            missing,
+            invalid: HashMap::new(),
+        })
+    }
+
+    async fn get_comments(
+        &self,
+        post_id: &str,
+        thread_id: Option<i64>,
+        offset: usize,
+    ) -> Result<VkCommentsPage, AppError> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .comment_calls
+            .push((post_id.to_string(), thread_id, offset));
+
+        let comments = state.comments.get(post_id).cloned().unwrap_or_default();
+        let parents: HashMap<i64, Option<i64>> = comments
+            .iter()
+            .map(|comment| (comment.id, comment.parent_id))
+            .collect();
+        // Replies to replies still belong to the thread of their top-level comment
+        let thread_of = |comment: &VkComment| {
+            let mut root = comment.parent_id?;
+            while let Some(Some(parent)) = parents.get(&root) {
+                root = *parent;
+            }
+            Some(root)
+        };
+
+        let level: Vec<VkComment> = comments
+            .iter()
+            .filter(|comment| thread_of(comment) == thread_id)
+            .map(|comment| VkComment {
+                thread_count: comments
+                    .iter()
+                    .filter(|reply| thread_of(reply) == Some(comment.id))
+                    .count(),
+                ..comment.clone()
+            })
+            .collect();
+
+        let deleted = state.deleted_comments.get(post_id);
+        let page: Vec<VkComment> = level
+            .iter()
+            .skip(offset)
+            .take(COMMENTS_PAGE_SIZE)
+            .cloned()
+            .collect();
+
+        Ok(VkCommentsPage {
+            total: level.len(),
+            raw_count: page.len(),
+            comments: page
+                .into_iter()
+                .filter(|comment| deleted.is_none_or(|deleted| !deleted.contains(&comment.id)))
+                .collect(),
+        })
+    }
+    async fn get_audience(
+        &self,
+        post_id: &str,
+        kind: AudienceKind,
+        offset: usize,
+    ) -> Result<VkAudiencePage, AppError> {
+        let mut state = self.state.lock().unwrap();
+        state
+            .audience_calls
+            .push((post_id.to_string(), kind, offset));
+
+        let key = (post_id.to_string(), kind);
+        let entries = state.audience.get(&key).cloned().unwrap_or_default();
+        let unreadable = state.unreadable_audience.get(&key);
+        let page: Vec<VkAudienceEntry> = entries
+            .iter()
+            .skip(offset)
+            .take(AUDIENCE_PAGE_SIZE)
+            .cloned()
+            .collect();
+
+        Ok(VkAudiencePage {
+            // Like wall.getReposts, reposts come without a total
+            total: (kind == AudienceKind::Like).then_some(entries.len()),
+            raw_count: page.len(),
+            entries: page
+                .into_iter()
+                .filter(|entry| unreadable.is_none_or(|users| !users.contains(&entry.user_id)))
+                .collect(),
+        })
+    }
+    async fn get_wall(&self, owner_id: i64, offset: usize) -> Result<VkWallPage, AppError> {
+        let mut state = self.state.lock().unwrap();
+        state.wall_calls.push((owner_id, offset));
+        if let Some(error) = state.wall_errors.get(&owner_id) {
+            return Err(error.clone().into());
+        }
+
+        let posts = state.walls.get(&owner_id).cloned().unwrap_or_default();
+        let unreadable = state.unreadable_wall_posts.get(&owner_id);
+        let page: Vec<VkWallPost> = posts
+            .iter()
+            .skip(offset)
+            .take(WALL_PAGE_SIZE)
+            .cloned()
+            .collect();
+
+        Ok(VkWallPage {
+            total: posts.len(),
+            raw_count: page.len(),
+            posts: page
+                .into_iter()
+                .filter(|post| unreadable.is_none_or(|vk_ids| !vk_ids.contains(&post.vk_id)))
+                .collect(),
+        })
+    }
+}
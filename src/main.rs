@@ -1,19 +1,38 @@
 #[macro_use]
 extern crate rocket;
 
+mod clock;
 mod db_commands;
 mod endpoints;
+mod errors;
+// Scriptable VK client for tests, not used by the service itself
+#[allow(dead_code)]
+mod fake_vk_client;
+// Deterministic scheduler and clock for tests, not used by the service itself
+#[allow(dead_code)]
+mod manual_scheduler;
 mod models;
+mod rate_limiter;
+mod scheduler;
 mod tasks;
+mod token_pool;
 mod utils;
 mod vk_api;
+mod vk_link;
 
+use clock::{Clock, SystemClock};
 use dotenv::dotenv;
-use endpoints::{get_polling, post_polling};
+use endpoints::{
+    create_watcher, delete_polling, delete_watcher, get_polling, get_polling_audience,
+    get_polling_comments, get_polling_meta, list_posts, list_watchers, pause_polling, post_polling,
+    post_polling_batch, resume_polling, vk_tokens,
+};
+use scheduler::IntervalScheduler;
 use std::sync::Arc;
-use tasks::init_all_tasks;
-use tokio_cron_scheduler::JobScheduler;
-use utils::get_db_pool;
+use std::time::Duration;
+use tasks::{JobRegistry, init_all_tasks};
+use utils::{get_db_pool, get_pooling_delta_seconds};
+use vk_api::{ReqwestVkClient, VkClient};
 
 #[launch]
 async fn rocket() -> rocket::Rocket<rocket::Build> {
@@ -27,22 +46,38 @@ async fn rocket() -> rocket::Rocket<rocket::Build> {
         panic!("Database migration failed");
     }
 
-    // Create and start the scheduler
-    let scheduler = JobScheduler::new()
-        .await
-        .expect("Failed to create scheduler");
-
-    scheduler.start().await.expect("Failed to start scheduler");
-
-    // Initialize all active polling tasks
-    if let Err(e) = init_all_tasks(&pool, &scheduler).await {
-        eprintln!("Failed to initialize polling tasks: {}", e);
-    }
-
-    let scheduler = Arc::new(scheduler);
+    // Start the polling dispatcher for all active posts
+    let vk: Arc<dyn VkClient> = Arc::new(ReqwestVkClient::default());
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let registry = JobRegistry::default();
+    let pooling_delta = get_pooling_delta_seconds();
+    println!("Polling due posts every {} seconds", pooling_delta);
+    let scheduler = IntervalScheduler::new(Duration::from_secs(pooling_delta.max(1) as u64));
+    init_all_tasks(&pool, &vk, &clock, &registry, &scheduler);
 
     rocket::build()
         .manage(Arc::new(pool))
-        .manage(scheduler)
-        .mount("/", routes![post_polling, get_polling])
+        .manage(vk)
+        .manage(clock)
+        .manage(registry)
+        .register("/", catchers![errors::default_catcher])
+        .mount(
+            "/",
+            routes![
+                post_polling,
+                post_polling_batch,
+                get_polling,
+                get_polling_meta,
+                get_polling_comments,
+                get_polling_audience,
+                delete_polling,
+                pause_polling,
+                resume_polling,
+                list_posts,
+                create_watcher,
+                list_watchers,
+                delete_watcher,
+                vk_tokens
+            ],
+        )
 }
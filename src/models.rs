@@ -1,4 +1,6 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 
 // Request/Response structures for API endpoints
 #[derive(Deserialize)]
@@ -6,27 +8,117 @@ use serde::{Deserialize, Serialize};
 pub struct PollingRequest {
     pub vk_link: String,
     pub prolong: bool,
+    pub duration: Option<PollingDuration>,
+    // Collect who liked and reposted the post, costs far more VK calls
+    #[serde(default)]
+    pub track_audience: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BatchPollingRequest {
+    // Every item is the same as a POST /polling body
+    pub posts: Vec<PollingRequest>,
+}
+
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde")]
+pub struct WallWatcherRequest {
+    // Community with a minus sign, user without one, as owner_id in VK
+    pub owner_id: i64,
+    // How often the wall is checked
+    pub interval: Option<PollingDuration>,
+    // Parsing window of every enrolled post
+    pub duration: Option<PollingDuration>,
+    #[serde(default)]
+    pub track_audience: bool,
+    #[serde(default)]
+    pub include_pinned: bool,
+    // A post is enrolled once it is at least this old
+    pub min_age: Option<PollingDuration>,
+    // A post is enrolled if its text contains any of them, no keywords let every post in
+    #[serde(default)]
+    pub keywords: Vec<String>,
+}
+
+// Parsing window length: seconds or an ISO-8601 duration such as "PT6H"
+#[derive(Deserialize)]
+#[serde(crate = "rocket::serde", untagged)]
+pub enum PollingDuration {
+    Seconds(i64),
+    Iso8601(String),
+}
+
+// Deserialize reads the response stored for an Idempotency-Key back
+#[derive(Serialize, Deserialize)]
 #[serde(crate = "rocket::serde")]
 pub struct PollingResponse {
     pub scrapper_id: i32,
     pub vk_id: String,
     pub dt_parse_begin: String,
     pub dt_parse_end: String,
+    pub paused_at: Option<String>,
+    pub track_audience: bool,
+}
+
+// Outcome of one item of a batch, exactly one of post and error is set
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BatchPollingItemResponse {
+    pub vk_link: String,
+    pub post: Option<PollingResponse>,
+    pub error: Option<ErrorBody>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct BatchPollingResponse {
+    // In the order of the request
+    pub results: Vec<BatchPollingItemResponse>,
+}
+
+// Body of every error response: {"error": {"code": "not_found", "message": "..."}}
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorResponse {
+    pub error: ErrorBody,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
 }
 
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct PostInfoDataResponse {
-    pub comments_count: i32,
-    pub likes_count: i32,
-    pub views_count: i32,
-    pub reposts_count: i32,
+    pub comments_count: i64,
+    pub likes_count: i64,
+    pub views_count: i64,
+    pub reposts_count: i64,
+    // Extra metrics collected for the snapshot by name, the set depends on the post
+    pub metrics: BTreeMap<String, i64>,
     pub info_time: String,
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PostMetaResponse {
+    pub version: i32,
+    pub owner_id: i64,
+    pub from_id: i64,
+    pub published_at: String,
+    pub post_type: String,
+    pub text: String,
+    pub attachments: serde_json::Value,
+    pub edited_at: Option<String>,
+    pub is_deleted: bool,
+    // When this version was first seen
+    pub fetched_at: String,
+}
+
 #[derive(Serialize)]
 #[serde(crate = "rocket::serde")]
 pub struct GetPollingResponse {
@@ -34,39 +126,365 @@ pub struct GetPollingResponse {
     pub vk_id: String,
     pub dt_parse_begin: String,
     pub dt_parse_end: String,
+    pub paused_at: Option<String>,
     pub dt_current: String,
+    // Latest known version of the post content, null until the post is polled
+    pub meta: Option<PostMetaResponse>,
     pub data: Vec<PostInfoDataResponse>,
 }
 
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CommentResponse {
+    pub comment_id: i64,
+    pub from_id: i64,
+    pub published_at: String,
+    pub text: String,
+    pub likes_count: i64,
+    // Comment this one replies to, null for top-level comments
+    pub parent_id: Option<i64>,
+    pub first_seen_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct CommentsResponse {
+    pub scrapper_id: i32,
+    // Pass as `cursor` to get only the comments saved after this response
+    pub next_cursor: i64,
+    pub comments: Vec<CommentResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct WallWatcherResponse {
+    pub watcher_id: i32,
+    pub owner_id: i64,
+    pub interval_seconds: i64,
+    pub duration_seconds: i64,
+    pub track_audience: bool,
+    pub include_pinned: bool,
+    pub min_age_seconds: i64,
+    pub keywords: Vec<String>,
+    pub created_at: String,
+    pub checked_at: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct WallWatcherListResponse {
+    pub watchers: Vec<WallWatcherResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AudienceEntryResponse {
+    pub user_id: i64,
+    // like or repost
+    pub kind: String,
+    // Time of the repost, VK does not tell when a like was given
+    pub engaged_at: Option<String>,
+    pub first_seen_at: String,
+    pub last_seen_at: String,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct AudienceResponse {
+    pub scrapper_id: i32,
+    // Pass as `cursor` to get only the users saved after this response
+    pub next_cursor: i64,
+    pub audience: Vec<AudienceEntryResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PostMetaHistoryResponse {
+    pub scrapper_id: i32,
+    // Oldest version first
+    pub versions: Vec<PostMetaResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PostListItemResponse {
+    pub scrapper_id: i32,
+    pub vk_id: String,
+    pub owner_id: String,
+    pub status: String,
+    pub dt_parse_begin: String,
+    pub dt_parse_end: String,
+    pub paused_at: Option<String>,
+    pub snapshot_count: i64,
+    // Polls skipped because VK requests could not be sent in time
+    pub skipped_count: i64,
+    pub meta: Option<PostMetaResponse>,
+    pub latest_snapshot: Option<PostInfoDataResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct PostListResponse {
+    pub posts: Vec<PostListItemResponse>,
+    // Pass as `cursor` to get the next page, null on the last page
+    pub next_cursor: Option<i32>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct VkTokenPoolResponse {
+    pub rotation: String,
+    // Requests waiting for any token
+    pub queue_depth: usize,
+    pub tokens: Vec<VkTokenStatsResponse>,
+}
+
+#[derive(Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct VkTokenStatsResponse {
+    // Only the first characters of the token
+    pub token: String,
+    pub status: String,
+    pub uses: u64,
+    pub failures: u64,
+    pub rate_per_second: f64,
+    pub queue_depth: usize,
+}
+
 // VK API structures
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct VkPostStats {
     pub comments_count: u64,
     pub likes_count: u64,
     pub views_count: u64,
     pub reposts_count: u64,
+    // Metrics beyond the four counters, only the ones VK sent for the post
+    pub extra: BTreeMap<String, i64>,
+}
+
+// Post content as returned by wall.getById
+#[derive(Debug, Clone, PartialEq)]
+pub struct VkPostMeta {
+    pub owner_id: i64,
+    // Author of the post, differs from owner_id for posts suggested to a community
+    pub from_id: i64,
+    pub published_at: DateTime<Utc>,
+    // post, copy, reply, postpone or suggest
+    pub post_type: String,
+    pub text: String,
+    pub attachments: serde_json::Value,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub is_deleted: bool,
+}
+
+// One comment as returned by wall.getComments
+#[derive(Debug, Clone, PartialEq)]
+pub struct VkComment {
+    pub id: i64,
+    pub from_id: i64,
+    pub published_at: DateTime<Utc>,
+    pub text: String,
+    pub likes_count: i64,
+    // Comment this one replies to, None for top-level comments
+    pub parent_id: Option<i64>,
+    // Replies in the thread of a top-level comment
+    pub thread_count: usize,
+}
+
+pub struct VkCommentsPage {
+    pub comments: Vec<VkComment>,
+    // Items VK returned, deleted comments included, the next page starts after them
+    pub raw_count: usize,
+    // Comments on the requested level: top-level ones or the replies of one thread
+    pub total: usize,
+}
+
+// How a user engaged with a post
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AudienceKind {
+    Like,
+    Repost,
+}
+
+impl AudienceKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AudienceKind::Like => "like",
+            AudienceKind::Repost => "repost",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VkAudienceEntry {
+    pub user_id: i64,
+    pub engaged_at: Option<DateTime<Utc>>,
+}
+
+pub struct VkAudiencePage {
+    pub entries: Vec<VkAudienceEntry>,
+    // Items VK returned, unreadable ones included, the next page starts after them
+    pub raw_count: usize,
+    // wall.getReposts does not report a total, paging ends on an empty page then
+    pub total: Option<usize>,
+}
+
+// One post of a wall.get page
+#[derive(Debug, Clone, PartialEq)]
+pub struct VkWallPost {
+    pub vk_id: String,
+    pub published_at: DateTime<Utc>,
+    pub text: String,
+    pub is_pinned: bool,
+}
+
+pub struct VkWallPage {
+    pub posts: Vec<VkWallPost>,
+    // Items VK returned, unreadable ones included, the next page starts after them
+    pub raw_count: usize,
+    pub total: usize,
+}
+
+pub struct VkBatchStats {
+    // Keyed by vk_id in `owner_id_post_id` form
+    pub stats: HashMap<String, VkPostStats>,
+    pub meta: HashMap<String, VkPostMeta>,
+    pub missing: Vec<String>,
+    // Posts VK returned with counters that are not valid integers, with the reason
+    pub invalid: HashMap<String, String>,
 }
 
 // Database structures
 pub struct PostDetails {
     pub id: i32,
     pub vk_id: String,
-    pub dt_parse_begin: chrono::NaiveDateTime,
-    pub dt_parse_end: chrono::NaiveDateTime,
+    pub dt_parse_begin: DateTime<Utc>,
+    pub dt_parse_end: DateTime<Utc>,
+    pub paused_at: Option<DateTime<Utc>>,
+    pub track_audience: bool,
+}
+
+// Counters of one snapshot as stored in POST_INFO
+pub struct PostCounters {
+    pub likes_count: i64,
+    pub comments_count: i64,
+    pub reposts_count: i64,
+    pub views_count: i64,
+    pub metrics: BTreeMap<String, i64>,
 }
 
 pub struct PostInfoData {
-    pub comments_count: i32,
-    pub likes_count: i32,
-    pub views_count: i32,
-    pub reposts_count: i32,
-    pub info_time: chrono::NaiveDateTime,
+    pub comments_count: i64,
+    pub likes_count: i64,
+    pub views_count: i64,
+    pub reposts_count: i64,
+    pub metrics: BTreeMap<String, i64>,
+    pub info_time: DateTime<Utc>,
+}
+
+// How snapshots inside one time bucket are reduced to a single value per metric
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    First,
+    Last,
+    Max,
+    Avg,
+}
+
+// Order in which VK tokens are handed out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenRotation {
+    RoundRobin,
+    LeastRecentlyUsed,
+}
+
+// One stored version of the post content
+pub struct PostMeta {
+    pub version: i32,
+    pub meta: VkPostMeta,
+    pub fetched_at: DateTime<Utc>,
+}
+
+// Settings of a new wall watcher, validated by the endpoint
+pub struct NewWallWatcher {
+    pub owner_id: i64,
+    pub interval_seconds: i64,
+    pub duration_seconds: i64,
+    pub track_audience: bool,
+    pub include_pinned: bool,
+    pub min_age_seconds: i64,
+    pub keywords: Vec<String>,
+}
+
+pub struct WallWatcher {
+    pub id: i32,
+    pub owner_id: i64,
+    pub interval_seconds: i64,
+    pub duration_seconds: i64,
+    pub track_audience: bool,
+    pub include_pinned: bool,
+    pub min_age_seconds: i64,
+    pub keywords: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub checked_at: Option<DateTime<Utc>>,
+    pub scanned_until: DateTime<Utc>,
+}
+
+pub struct PostAudienceEntry {
+    // Grows in the order users were committed, the cursor of GET /polling/audience
+    pub seq: i64,
+    pub user_id: i64,
+    pub kind: String,
+    pub engaged_at: Option<DateTime<Utc>>,
+    pub first_seen_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+pub struct PostComment {
+    // Grows in the order comments were committed, the cursor of GET /polling/comments
+    pub seq: i64,
+    pub comment_id: i64,
+    pub from_id: i64,
+    pub published_at: DateTime<Utc>,
+    pub text: String,
+    pub likes_count: i64,
+    pub parent_id: Option<i64>,
+    // When the collector saw the comment for the first time
+    pub first_seen_at: DateTime<Utc>,
 }
 
 pub struct PostWithData {
     pub id: i32,
     pub vk_id: String,
-    pub dt_parse_begin: chrono::NaiveDateTime,
-    pub dt_parse_end: chrono::NaiveDateTime,
+    pub dt_parse_begin: DateTime<Utc>,
+    pub dt_parse_end: DateTime<Utc>,
+    pub paused_at: Option<DateTime<Utc>>,
+    pub meta: Option<PostMeta>,
     pub data: Vec<PostInfoData>,
 }
+
+pub struct PostListFilter {
+    pub vk_id: Option<String>,
+    pub owner_id: Option<String>,
+    pub active_at: Option<DateTime<Utc>>,
+    pub created_from: Option<DateTime<Utc>>,
+    pub created_to: Option<DateTime<Utc>>,
+    // Only posts with a smaller id are returned
+    pub cursor: Option<i32>,
+    pub limit: i64,
+}
+
+pub struct PostListItem {
+    pub id: i32,
+    pub vk_id: String,
+    // One of "active", "finished" or "paused"
+    pub status: String,
+    pub dt_parse_begin: DateTime<Utc>,
+    pub dt_parse_end: DateTime<Utc>,
+    pub paused_at: Option<DateTime<Utc>>,
+    pub snapshot_count: i64,
+    // Polls skipped because VK requests could not be sent in time
+    pub skipped_count: i64,
+    pub meta: Option<PostMeta>,
+    pub latest_snapshot: Option<PostInfoData>,
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Token bucket limiting the rate of VK requests.
+///
+/// Waiters are served strictly in arrival order: the bucket lock is a fair mutex
+/// and the first waiter holds it while sleeping for the next token.
+pub struct RateLimiter {
+    rate_per_second: f64,
+    bucket: Mutex<Bucket>,
+    queued: AtomicUsize,
+}
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate_per_second: f64) -> Self {
+        RateLimiter {
+            rate_per_second,
+            bucket: Mutex::new(Bucket {
+                // A full bucket allows one second worth of requests at once
+                tokens: rate_per_second.max(1.0),
+                refilled_at: Instant::now(),
+            }),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn rate_per_second(&self) -> f64 {
+        self.rate_per_second
+    }
+
+    /// Number of requests waiting for a token right now.
+    pub fn queue_depth(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Takes a token, waiting at most `max_wait`; returns false if the wait was too long.
+    pub async fn acquire(&self, max_wait: Duration) -> bool {
+        let _queued = QueuedGuard::new(&self.queued);
+        tokio::time::timeout(max_wait, self.take_token())
+            .await
+            .is_ok()
+    }
+
+    async fn take_token(&self) {
+        let mut bucket = self.bucket.lock().await;
+        let capacity = self.rate_per_second.max(1.0);
+
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * self.rate_per_second).min(capacity);
+            bucket.refilled_at = now;
+
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                return;
+            }
+
+            let missing = 1.0 - bucket.tokens;
+            tokio::time::sleep(Duration::from_secs_f64(missing / self.rate_per_second)).await;
+        }
+    }
+}
+
+// Keeps the queue depth right even when a waiter is cancelled
+struct QueuedGuard<'a>(&'a AtomicUsize);
+
+impl<'a> QueuedGuard<'a> {
+    fn new(queued: &'a AtomicUsize) -> Self {
+        queued.fetch_add(1, Ordering::SeqCst);
+        QueuedGuard(queued)
+    }
+}
+
+impl Drop for QueuedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::tasks::Dispatcher;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+
+/// Decides when the dispatcher looks for due posts.
+pub trait Scheduler: Send + Sync {
+    /// Takes over the dispatcher, polling stops once the returned handle is aborted.
+    fn start(&self, dispatcher: Dispatcher) -> JoinHandle<()>;
+}
+
+/// Ticks the dispatcher on a fixed period of wall-clock time.
+pub struct IntervalScheduler {
+    period: Duration,
+}
+
+impl IntervalScheduler {
+    pub fn new(period: Duration) -> Self {
+        IntervalScheduler { period }
+    }
+}
+
+impl Scheduler for IntervalScheduler {
+    fn start(&self, dispatcher: Dispatcher) -> JoinHandle<()> {
+        let period = self.period;
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            // A slow tick must not cause a burst of catch-up ticks
+            interval.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            loop {
+                interval.tick().await;
+
+                // Jobs keep running on their own, the next tick skips posts still being polled
+                dispatcher.dispatch_tick().await;
+            }
+        })
+    }
+}
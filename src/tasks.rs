@@ -1,91 +1,644 @@
+use crate::clock::Clock;
 use crate::db_commands::{
-    get_posts_needing_polling, get_vk_id_by_post_id, is_ready_to_finish, save_post_info,
+    claim_due_wall_watchers, claim_posts_for_audience, claim_posts_for_comments, finish_post,
+    get_known_vk_ids, get_or_create_post_with_prolong, get_posts_needing_polling, save_audience,
+    save_comments, save_poll_skips, save_post_info, save_post_meta, set_wall_watcher_scanned_until,
 };
-use crate::utils::get_pooling_delta_seconds;
-use crate::vk_api::call_vk;
+use crate::errors::AppError;
+use crate::models::{AudienceKind, VkAudienceEntry, VkComment, VkWallPost, WallWatcher};
+use crate::scheduler::Scheduler;
+use crate::utils::{
+    get_audience_polling_seconds, get_comments_polling_seconds, get_polling_workers,
+    get_vk_batch_size, post_counters,
+};
+use crate::vk_api::VkClient;
 use sqlx::postgres::PgPool;
-use tokio_cron_scheduler::{Job, JobScheduler};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tokio::task::{AbortHandle, JoinHandle};
+use uuid::Uuid;
 
-pub async fn init_all_tasks(
+pub fn init_all_tasks(
     pool: &PgPool,
-    scheduler: &JobScheduler,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Get pooling delta from utils
-    let pooling_delta = get_pooling_delta_seconds();
+    vk: &Arc<dyn VkClient>,
+    clock: &Arc<dyn Clock>,
+    registry: &JobRegistry,
+    scheduler: &dyn Scheduler,
+) -> JoinHandle<()> {
+    let workers = get_polling_workers();
 
-    // Get all posts that need polling
-    let posts = get_posts_needing_polling(pool).await?;
+    println!("Starting polling dispatcher with {} workers", workers);
 
-    println!("Initializing {} polling tasks on startup", posts.len());
+    let dispatcher = Dispatcher::new(
+        pool.clone(),
+        vk.clone(),
+        clock.clone(),
+        workers,
+        registry.clone(),
+    );
+    scheduler.start(dispatcher)
+}
 
-    for (db_post_id, vk_id) in posts {
-        // Create cron job for polling
-        let pool_clone = pool.clone();
-        let job = Job::new_async(
-            format!("*/{} * * * * *", pooling_delta).as_str(),
-            move |job_id, locked_scheduler| {
-                let pool = pool_clone.clone();
-                let db_post_id = db_post_id;
-                Box::pin(async move {
-                    if let Err(e) =
-                        poll_post_stats(&job_id, &locked_scheduler, &pool, db_post_id).await
-                    {
-                        eprintln!("Error polling post stats: {}", e);
+/// Maps every post that is being polled right now to the id of its polling job.
+#[derive(Clone, Default)]
+pub struct JobRegistry {
+    jobs: Arc<Mutex<RegisteredJobs>>,
+}
+
+#[derive(Default)]
+struct RegisteredJobs {
+    posts: HashMap<i32, Uuid>,
+    // Live jobs that own at least one post, so a cancelled post stops its job
+    handles: HashMap<Uuid, AbortHandle>,
+}
+
+impl JobRegistry {
+    /// Registers the post under the job, returns false if another job already polls it.
+    pub fn register(&self, db_post_id: i32, job_id: Uuid) -> bool {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.posts.contains_key(&db_post_id) {
+            return false;
+        }
+        jobs.posts.insert(db_post_id, job_id);
+        true
+    }
+
+    /// Keeps the handle of the spawned job, a job whose posts were all cancelled meanwhile is aborted.
+    pub fn attach(&self, job_id: Uuid, handle: AbortHandle) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.posts.values().any(|id| *id == job_id) {
+            jobs.handles.insert(job_id, handle);
+        } else {
+            handle.abort();
+        }
+    }
+
+    /// Removes the post once its job is done, unless it was cancelled and re-registered meanwhile.
+    pub fn finish(&self, db_post_id: i32, job_id: Uuid) {
+        let mut jobs = self.jobs.lock().unwrap();
+        if jobs.posts.get(&db_post_id) == Some(&job_id) {
+            jobs.posts.remove(&db_post_id);
+        }
+        if !jobs.posts.values().any(|id| *id == job_id) {
+            jobs.handles.remove(&job_id);
+        }
+    }
+
+    /// Aborts the live job of the post and returns its id, if there was one.
+    ///
+    /// The other posts of the job are released too, they are still due and the next tick polls them.
+    pub fn cancel(&self, db_post_id: i32) -> Option<Uuid> {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job_id = jobs.posts.remove(&db_post_id)?;
+
+        jobs.posts.retain(|_, id| *id != job_id);
+        if let Some(handle) = jobs.handles.remove(&job_id) {
+            handle.abort();
+        }
+
+        Some(job_id)
+    }
+}
+
+/// Jobs started by one dispatcher tick that are still running.
+#[derive(Default)]
+pub struct DispatchedTick {
+    pub handles: Vec<JoinHandle<()>>,
+    // Polling jobs among the handles
+    pub posts: usize,
+}
+
+/// Finds posts that are due for polling on every tick and fans them out to workers.
+///
+/// Posts are tracked only in the database, so creating or finishing a post
+/// does not require touching the dispatcher. Ticks come from a [`Scheduler`].
+pub struct Dispatcher {
+    pool: PgPool,
+    vk: Arc<dyn VkClient>,
+    clock: Arc<dyn Clock>,
+    workers: Arc<Semaphore>,
+    batch_size: usize,
+    // Posts whose poll has been dispatched but not finished yet
+    registry: JobRegistry,
+}
+
+impl Dispatcher {
+    pub fn new(
+        pool: PgPool,
+        vk: Arc<dyn VkClient>,
+        clock: Arc<dyn Clock>,
+        workers: usize,
+        registry: JobRegistry,
+    ) -> Self {
+        Dispatcher {
+            pool,
+            vk,
+            clock,
+            workers: Arc::new(Semaphore::new(workers)),
+            batch_size: get_vk_batch_size(),
+            registry,
+        }
+    }
+
+    /// Runs one tick: wall checks first, then posts, comments and audiences.
+    ///
+    /// Wall checks finish before due posts are picked, so posts they enroll are polled on the same tick.
+    /// A failing step is logged and does not keep the rest of the tick from running.
+    pub async fn dispatch_tick(&self) -> DispatchedTick {
+        match self.dispatch_due_watchers().await {
+            Ok(handles) => {
+                for handle in handles {
+                    if let Err(e) = handle.await {
+                        eprintln!("Wall check failed: {}", e);
+                    }
+                }
+            }
+            Err(e) => eprintln!("Error dispatching wall checks: {}", e),
+        }
+
+        let mut tick = DispatchedTick::default();
+        match self.dispatch_due_posts().await {
+            Ok(handles) => {
+                tick.posts = handles.len();
+                tick.handles.extend(handles);
+            }
+            Err(e) => eprintln!("Error dispatching polling tasks: {}", e),
+        }
+
+        match self.dispatch_due_comments().await {
+            Ok(handles) => tick.handles.extend(handles),
+            Err(e) => eprintln!("Error dispatching comment collection: {}", e),
+        }
+
+        match self.dispatch_due_audience().await {
+            Ok(handles) => tick.handles.extend(handles),
+            Err(e) => eprintln!("Error dispatching audience collection: {}", e),
+        }
+
+        tick
+    }
+
+    /// Spawns one worker job per batch of due posts, skipping posts that are still being polled.
+    pub async fn dispatch_due_posts(&self) -> Result<Vec<JoinHandle<()>>, AppError> {
+        let posts = get_posts_needing_polling(&self.pool, self.clock.now()).await?;
+
+        let mut handles = Vec::new();
+        for chunk in posts.chunks(self.batch_size) {
+            let job_id = Uuid::new_v4();
+            let batch: Vec<(i32, String)> = chunk
+                .iter()
+                .filter(|(db_post_id, _)| self.registry.register(*db_post_id, job_id))
+                .cloned()
+                .collect();
+
+            if batch.is_empty() {
+                continue;
+            }
+
+            let pool = self.pool.clone();
+            let vk = self.vk.clone();
+            let clock = self.clock.clone();
+            let workers = self.workers.clone();
+            let registry = self.registry.clone();
+
+            let handle = tokio::spawn(async move {
+                // Wait for a free worker
+                let result = match workers.acquire_owned().await {
+                    Ok(_permit) => {
+                        poll_post_stats(&pool, vk.as_ref(), clock.as_ref(), &batch).await
+                    }
+                    Err(_) => Err(AppError::Scheduler(
+                        "Polling worker pool is closed".to_string(),
+                    )),
+                };
+
+                if let Err(e) = result {
+                    eprintln!("Error polling post stats in job {}: {}", job_id, e);
+                }
+
+                for (db_post_id, _) in &batch {
+                    registry.finish(*db_post_id, job_id);
+                }
+            });
+            self.registry.attach(job_id, handle.abort_handle());
+            handles.push(handle);
+        }
+
+        Ok(handles)
+    }
+
+    /// Spawns one comment collection per post whose comments are due, on their own cadence.
+    pub async fn dispatch_due_comments(&self) -> Result<Vec<JoinHandle<()>>, AppError> {
+        let interval_seconds = get_comments_polling_seconds();
+        if interval_seconds <= 0 {
+            return Ok(Vec::new());
+        }
+
+        let posts =
+            claim_posts_for_comments(&self.pool, interval_seconds, self.clock.now()).await?;
+
+        let mut handles = Vec::new();
+        for (db_post_id, vk_id) in posts {
+            let pool = self.pool.clone();
+            let vk = self.vk.clone();
+            let clock = self.clock.clone();
+            let workers = self.workers.clone();
+
+            handles.push(tokio::spawn(async move {
+                // Comment collection shares the workers with the stats polls
+                let result = match workers.acquire_owned().await {
+                    Ok(_permit) => {
+                        collect_post_comments(
+                            &pool,
+                            vk.as_ref(),
+                            clock.as_ref(),
+                            db_post_id,
+                            &vk_id,
+                        )
+                        .await
+                    }
+                    Err(_) => Err(AppError::Scheduler(
+                        "Polling worker pool is closed".to_string(),
+                    )),
+                };
+
+                match result {
+                    Ok(0) => {}
+                    Ok(new) => println!("Collected {} new comments of post {}", new, db_post_id),
+                    Err(e) => eprintln!("Error collecting comments of post {}: {}", db_post_id, e),
+                }
+            }));
+        }
+
+        Ok(handles)
+    }
+
+    /// Spawns one wall check per watcher whose interval has passed.
+    pub async fn dispatch_due_watchers(&self) -> Result<Vec<JoinHandle<()>>, AppError> {
+        let watchers = claim_due_wall_watchers(&self.pool, self.clock.now()).await?;
+
+        let mut handles = Vec::new();
+        for watcher in watchers {
+            let pool = self.pool.clone();
+            let vk = self.vk.clone();
+            let clock = self.clock.clone();
+            let workers = self.workers.clone();
+
+            handles.push(tokio::spawn(async move {
+                let result = match workers.acquire_owned().await {
+                    Ok(_permit) => check_wall(&pool, vk.as_ref(), clock.as_ref(), &watcher).await,
+                    Err(_) => Err(AppError::Scheduler(
+                        "Polling worker pool is closed".to_string(),
+                    )),
+                };
+
+                match result {
+                    Ok(0) => {}
+                    Ok(enrolled) => println!(
+                        "Wall watcher {} enrolled {} new posts of {}",
+                        watcher.id, enrolled, watcher.owner_id
+                    ),
+                    Err(e) => eprintln!(
+                        "Error checking wall {} of watcher {}: {}",
+                        watcher.owner_id, watcher.id, e
+                    ),
+                }
+            }));
+        }
+
+        Ok(handles)
+    }
+
+    /// Spawns one audience collection per post that tracks its audience and is due for it.
+    pub async fn dispatch_due_audience(&self) -> Result<Vec<JoinHandle<()>>, AppError> {
+        let interval_seconds = get_audience_polling_seconds();
+        if interval_seconds <= 0 {
+            return Ok(Vec::new());
+        }
+
+        let posts =
+            claim_posts_for_audience(&self.pool, interval_seconds, self.clock.now()).await?;
+
+        let mut handles = Vec::new();
+        for (db_post_id, vk_id) in posts {
+            let pool = self.pool.clone();
+            let vk = self.vk.clone();
+            let clock = self.clock.clone();
+            let workers = self.workers.clone();
+
+            handles.push(tokio::spawn(async move {
+                let result = match workers.acquire_owned().await {
+                    Ok(_permit) => {
+                        collect_post_audience(
+                            &pool,
+                            vk.as_ref(),
+                            clock.as_ref(),
+                            db_post_id,
+                            &vk_id,
+                        )
+                        .await
                     }
+                    Err(_) => Err(AppError::Scheduler(
+                        "Polling worker pool is closed".to_string(),
+                    )),
+                };
+
+                match result {
+                    Ok(0) => {}
+                    Ok(new) => println!("Found {} new users engaged with post {}", new, db_post_id),
+                    Err(e) => eprintln!("Error collecting audience of post {}: {}", db_post_id, e),
+                }
+            }));
+        }
+
+        Ok(handles)
+    }
+}
+
+/// Checks the wall of the watcher and enrols the new posts that pass its filters.
+///
+/// Returns how many posts were enrolled, posts that already have a task are left as they are.
+pub async fn check_wall(
+    pool: &PgPool,
+    vk: &dyn VkClient,
+    clock: &dyn Clock,
+    watcher: &WallWatcher,
+) -> Result<usize, AppError> {
+    let now = clock.now();
+    let min_age = chrono::Duration::seconds(watcher.min_age_seconds);
+
+    // Posts are newest first, so paging stops at the first post that was already looked at
+    let mut candidates: Vec<VkWallPost> = Vec::new();
+    // Posts that can't be read still take their place in VK paging
+    let mut offset = 0;
+    loop {
+        let page = vk.get_wall(watcher.owner_id, offset).await?;
+        if page.raw_count == 0 {
+            break;
+        }
+
+        offset += page.raw_count;
+        let reached_scanned = page
+            .posts
+            .iter()
+            .any(|post| !post.is_pinned && post.published_at < watcher.scanned_until);
+        candidates.extend(page.posts.into_iter().filter(|post| {
+            post.published_at >= watcher.scanned_until
+                && (watcher.include_pinned || !post.is_pinned)
+                && now - post.published_at >= min_age
+                && matches_keywords(&post.text, &watcher.keywords)
+        }));
+
+        if reached_scanned || offset >= page.total {
+            break;
+        }
+    }
+
+    let vk_ids: Vec<String> = candidates.into_iter().map(|post| post.vk_id).collect();
+    let known = get_known_vk_ids(pool, &vk_ids).await?;
+
+    let mut enrolled = 0;
+    for vk_id in vk_ids.iter().filter(|vk_id| !known.contains(*vk_id)) {
+        get_or_create_post_with_prolong(
+            pool,
+            vk_id,
+            false,
+            watcher.duration_seconds,
+            watcher.track_audience,
+            now,
+        )
+        .await?;
+        enrolled += 1;
+    }
+
+    // Younger posts are not decided yet, the next check looks at them again
+    set_wall_watcher_scanned_until(pool, watcher.id, now - min_age).await?;
+
+    Ok(enrolled)
+}
+
+// Case-insensitive, any keyword is enough
+fn matches_keywords(text: &str, keywords: &[String]) -> bool {
+    if keywords.is_empty() {
+        return true;
+    }
+
+    let text = text.to_lowercase();
+    keywords
+        .iter()
+        .any(|keyword| text.contains(&keyword.to_lowercase()))
+}
+
+/// Collects everyone who liked or reposted the post, returns how many were seen for the first time.
+pub async fn collect_post_audience(
+    pool: &PgPool,
+    vk: &dyn VkClient,
+    clock: &dyn Clock,
+    db_post_id: i32,
+    vk_id: &str,
+) -> Result<usize, AppError> {
+    let mut new = 0;
+    for kind in [AudienceKind::Like, AudienceKind::Repost] {
+        let entries = fetch_audience(vk, vk_id, kind).await?;
+        new += save_audience(pool, db_post_id, kind, &entries, clock.now()).await?;
+    }
+
+    Ok(new)
+}
+
+// Pages through the users of one kind, a user who reposted twice is kept with the first repost
+async fn fetch_audience(
+    vk: &dyn VkClient,
+    vk_id: &str,
+    kind: AudienceKind,
+) -> Result<Vec<VkAudienceEntry>, AppError> {
+    // Keyed by user: a like given while paging moves the rest down, so one may come twice
+    let mut entries: BTreeMap<i64, VkAudienceEntry> = BTreeMap::new();
+    // Entries that can't be read still take their place in VK paging
+    let mut offset = 0;
+    loop {
+        let page = vk.get_audience(vk_id, kind, offset).await?;
+        if page.raw_count == 0 {
+            break;
+        }
+
+        offset += page.raw_count;
+        for entry in page.entries {
+            entries
+                .entry(entry.user_id)
+                .and_modify(|seen| {
+                    seen.engaged_at = match (seen.engaged_at, entry.engaged_at) {
+                        (Some(seen_at), Some(engaged_at)) => Some(seen_at.min(engaged_at)),
+                        (seen_at, engaged_at) => seen_at.or(engaged_at),
+                    };
                 })
-            },
-        )?;
+                .or_insert(entry);
+        }
+
+        if page.total.is_some_and(|total| offset >= total) {
+            break;
+        }
+    }
 
-        // Add job to the scheduler
-        scheduler.add(job).await?;
+    Ok(entries.into_values().collect())
+}
 
-        println!(
-            "Started polling task for post {} (db_id: {})",
-            vk_id, db_post_id
-        );
+/// Collects every comment of the post, thread replies included, returns how many were new.
+pub async fn collect_post_comments(
+    pool: &PgPool,
+    vk: &dyn VkClient,
+    clock: &dyn Clock,
+    db_post_id: i32,
+    vk_id: &str,
+) -> Result<usize, AppError> {
+    // Keyed by id: a comment written while paging moves the rest down, so one may come twice
+    let mut comments: BTreeMap<i64, VkComment> = BTreeMap::new();
+
+    let top_level = fetch_comment_level(vk, vk_id, None).await?;
+    let threads: Vec<i64> = top_level
+        .iter()
+        .filter(|comment| comment.thread_count > 0)
+        .map(|comment| comment.id)
+        .collect();
+    comments.extend(top_level.into_iter().map(|comment| (comment.id, comment)));
+
+    for thread_id in threads {
+        let replies = fetch_comment_level(vk, vk_id, Some(thread_id)).await?;
+        comments.extend(replies.into_iter().map(|comment| (comment.id, comment)));
     }
 
-    Ok(())
+    let comments: Vec<VkComment> = comments.into_values().collect();
+    save_comments(pool, db_post_id, &comments, clock.now()).await
+}
+
+// Pages through the top-level comments or through the replies of one thread
+async fn fetch_comment_level(
+    vk: &dyn VkClient,
+    vk_id: &str,
+    thread_id: Option<i64>,
+) -> Result<Vec<VkComment>, AppError> {
+    let mut comments = Vec::new();
+    // Deleted comments are not returned but still take their place in VK paging
+    let mut offset = 0;
+    loop {
+        let page = vk.get_comments(vk_id, thread_id, offset).await?;
+        if page.raw_count == 0 {
+            return Ok(comments);
+        }
+
+        offset += page.raw_count;
+        comments.extend(page.comments);
+        if offset >= page.total {
+            return Ok(comments);
+        }
+    }
 }
 
 pub async fn poll_post_stats(
-    job_id: &uuid::Uuid,
-    locked_scheduler: &JobScheduler,
     pool: &PgPool,
-    db_post_id: i32,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Check if the task should finish
-    if is_ready_to_finish(pool, db_post_id).await? {
-        println!("Post {} is ready to finish, stopping polling", db_post_id);
-        let _ = locked_scheduler.remove(job_id).await;
+    vk: &dyn VkClient,
+    clock: &dyn Clock,
+    posts: &[(i32, String)],
+) -> Result<(), AppError> {
+    if posts.is_empty() {
         return Ok(());
     }
 
-    // Get vk_id from database
-    let vk_id = get_vk_id_by_post_id(pool, db_post_id)
-        .await?
-        .ok_or("Post not found")?;
-
-    // Call VK API
-    let stats = call_vk(&vk_id)
-        .await
-        .map_err(|e| format!("VK API call failed: {:?}", e))?;
-
-    // Save post info to database
-    save_post_info(
-        pool,
-        db_post_id,
-        stats.likes_count as i32,
-        stats.comments_count as i32,
-        stats.reposts_count as i32,
-        stats.views_count as i32,
-    )
-    .await?;
-
-    println!(
-        "Successfully polled stats for post {}: likes={}, comments={}, reposts={}, views={}",
-        db_post_id, stats.likes_count, stats.comments_count, stats.reposts_count, stats.views_count
-    );
+    // Call VK API once per batch of posts
+    let vk_ids: Vec<String> = posts.iter().map(|(_, vk_id)| vk_id.clone()).collect();
+    let batch = match vk.call_vk_batch(&vk_ids).await {
+        Ok(batch) => batch,
+        // One inaccessible post fails the whole request, poll posts one by one to find it
+        Err(AppError::VkApi(e)) if e.is_post_error() && posts.len() > 1 => {
+            for post in posts {
+                // A post that fails on its own must not cost the rest of the batch their snapshot
+                let result = Box::pin(poll_post_stats(pool, vk, clock, std::slice::from_ref(post)));
+                if let Err(e) = result.await {
+                    eprintln!("Error polling post {} ({}): {}", post.0, post.1, e);
+                }
+            }
+            return Ok(());
+        }
+        // The post will never become available again, so its polling is stopped
+        Err(AppError::VkApi(e)) if e.is_post_error() => {
+            let (db_post_id, vk_id) = &posts[0];
+            finish_post(pool, *db_post_id, clock.now()).await?;
+            println!("Stopped polling post {} ({}): {}", db_post_id, vk_id, e);
+            return Ok(());
+        }
+        // VK requests are throttled on our side and can't be sent in time, the poll is recorded as skipped
+        Err(AppError::RateLimited {
+            message: reason, ..
+        }) => {
+            let db_post_ids: Vec<i32> = posts.iter().map(|(db_post_id, _)| *db_post_id).collect();
+            save_poll_skips(pool, &db_post_ids, &reason, clock.now()).await?;
+            println!("Skipped poll of {} posts: {}", posts.len(), reason);
+            return Ok(());
+        }
+        // Due posts are picked up again on the next tick
+        Err(e) if e.is_retryable() => {
+            println!(
+                "Skipping poll of {} posts until the next tick: {}",
+                posts.len(),
+                e
+            );
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+
+    // Posts absent from the response are reported, not saved as zero stats
+    if !batch.missing.is_empty() {
+        eprintln!(
+            "Posts missing from VK response, skipping: {}",
+            batch.missing.join(", ")
+        );
+    }
+    for (vk_id, reason) in &batch.invalid {
+        eprintln!("Rejected stats of post {}: {}", vk_id, reason);
+    }
+
+    // All snapshots of the batch share the time VK answered
+    let info_time = clock.now();
+    for (db_post_id, vk_id) in posts {
+        let Some(stats) = batch.stats.get(vk_id) else {
+            continue;
+        };
+
+        // A snapshot with a counter the database can't hold is dropped, the post is polled again next tick
+        let counters = match post_counters(stats) {
+            Ok(counters) => counters,
+            Err(e) => {
+                eprintln!("Rejected stats of post {} ({}): {}", db_post_id, vk_id, e);
+                continue;
+            }
+        };
+
+        // Save post info to database, posts stopped during the VK call are not saved
+        let saved = save_post_info(pool, *db_post_id, &counters, info_time).await?;
+
+        if !saved {
+            println!("Post {} was stopped while polling, skipping", db_post_id);
+            continue;
+        }
+
+        // Content is stored on the first poll and again only once it changes
+        if let Some(meta) = batch.meta.get(vk_id)
+            && save_post_meta(pool, *db_post_id, meta, info_time).await?
+        {
+            println!("Saved new metadata version of post {}", db_post_id);
+        }
+
+        println!(
+            "Successfully polled stats for post {}: likes={}, comments={}, reposts={}, views={}",
+            db_post_id,
+            stats.likes_count,
+            stats.comments_count,
+            stats.reposts_count,
+            stats.views_count
+        );
+    }
 
     Ok(())
 }
@@ -0,0 +1,241 @@
+use crate::errors::{AppError, VkError};
+use crate::models::TokenRotation;
+use crate::rate_limiter::RateLimiter;
+use crate::utils::{get_vk_token_cooldown_seconds, get_vk_token_rotation, get_vk_tokens};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// VK counts requests per second, so a too-many-requests error only rests the token for a second
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// VK access tokens the client rotates between.
+///
+/// Every token has its own rate limiter, a token is taken out of rotation for
+/// a cooldown after a rate-limit error and for good after an auth error.
+pub struct TokenPool {
+    rotation: TokenRotation,
+    cooldown: Duration,
+    state: Mutex<PoolState>,
+}
+
+struct PoolState {
+    tokens: Vec<TokenState>,
+    // Index of the token to try first with round-robin rotation
+    next: usize,
+}
+
+struct TokenState {
+    token: String,
+    limiter: Arc<RateLimiter>,
+    uses: u64,
+    failures: u64,
+    last_used: Option<Instant>,
+    cooldown_until: Option<Instant>,
+    revoked: bool,
+}
+
+impl PoolState {
+    // Time until the first token that is not revoked ends its cooldown
+    fn cooldown_remaining(&self, now: Instant) -> Option<Duration> {
+        self.tokens
+            .iter()
+            .filter(|token| !token.revoked)
+            .filter_map(|token| token.cooldown_until)
+            .min()
+            .map(|until| until.saturating_duration_since(now))
+    }
+}
+
+impl TokenState {
+    fn is_usable(&self, now: Instant) -> bool {
+        !self.revoked && self.cooldown_until.is_none_or(|until| until <= now)
+    }
+}
+
+/// A token handed out for one VK request.
+pub struct TokenLease {
+    index: usize,
+    pub token: String,
+    pub limiter: Arc<RateLimiter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenStatus {
+    Active,
+    CoolingDown,
+    Revoked,
+}
+
+pub struct TokenStats {
+    pub token: String,
+    pub status: TokenStatus,
+    pub uses: u64,
+    pub failures: u64,
+    pub rate_per_second: f64,
+    pub queue_depth: usize,
+}
+
+impl TokenPool {
+    pub fn new(tokens: Vec<(String, f64)>, rotation: TokenRotation, cooldown: Duration) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|(token, rate_per_second)| TokenState {
+                token,
+                limiter: Arc::new(RateLimiter::new(rate_per_second)),
+                uses: 0,
+                failures: 0,
+                last_used: None,
+                cooldown_until: None,
+                revoked: false,
+            })
+            .collect();
+
+        TokenPool {
+            rotation,
+            cooldown,
+            state: Mutex::new(PoolState { tokens, next: 0 }),
+        }
+    }
+
+    pub fn rotation(&self) -> TokenRotation {
+        self.rotation
+    }
+
+    /// Picks the next usable token according to the rotation strategy.
+    pub fn checkout(&self) -> Result<TokenLease, AppError> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if state.tokens.is_empty() {
+            return Err(AppError::Vk(
+                "No VK tokens configured, set VK_TOKENS or VK_TOKEN".to_string(),
+            ));
+        }
+
+        let count = state.tokens.len();
+        let index = match self.rotation {
+            TokenRotation::RoundRobin => (0..count)
+                .map(|offset| (state.next + offset) % count)
+                .find(|&index| state.tokens[index].is_usable(now)),
+            TokenRotation::LeastRecentlyUsed => (0..count)
+                .filter(|&index| state.tokens[index].is_usable(now))
+                .min_by_key(|&index| state.tokens[index].last_used),
+        };
+
+        let Some(index) = index else {
+            return Err(if state.tokens.iter().all(|token| token.revoked) {
+                VkError::AuthFailed("All VK tokens were rejected".to_string()).into()
+            } else {
+                // Cooldowns end by themselves, the poll is retried on a later tick
+                AppError::RateLimited {
+                    message: "All VK tokens are cooling down".to_string(),
+                    retry_after: state.cooldown_remaining(now).unwrap_or(RATE_LIMIT_WINDOW),
+                }
+            });
+        };
+
+        state.next = (index + 1) % count;
+        let token = &mut state.tokens[index];
+        token.last_used = Some(now);
+
+        Ok(TokenLease {
+            index,
+            token: token.token.clone(),
+            limiter: token.limiter.clone(),
+        })
+    }
+
+    /// Records the outcome of a request made with the leased token.
+    pub fn report<T>(&self, lease: &TokenLease, result: &Result<T, AppError>) {
+        let mut state = self.state.lock().unwrap();
+        let token = &mut state.tokens[lease.index];
+        token.uses += 1;
+
+        let Err(e) = result else {
+            return;
+        };
+        token.failures += 1;
+
+        match e {
+            // VK counts these requests per second, the token is usable again once the second is over
+            AppError::VkApi(VkError::TooManyRequests(_)) => {
+                token.cooldown_until = Some(Instant::now() + self.cooldown.min(RATE_LIMIT_WINDOW));
+            }
+            AppError::VkApi(VkError::FloodControl(_)) => {
+                token.cooldown_until = Some(Instant::now() + self.cooldown);
+            }
+            AppError::VkApi(VkError::AuthFailed(_)) => {
+                eprintln!(
+                    "VK token {} was rejected, removing it from rotation",
+                    mask_token(&token.token)
+                );
+                token.revoked = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether any token can take a request right now.
+    pub fn has_usable_token(&self) -> bool {
+        let now = Instant::now();
+        self.state
+            .lock()
+            .unwrap()
+            .tokens
+            .iter()
+            .any(|token| token.is_usable(now))
+    }
+
+    /// Time until the first token is back from its cooldown, None if one is usable or all are revoked.
+    pub fn cooldown_remaining(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let state = self.state.lock().unwrap();
+        if state.tokens.iter().any(|token| token.is_usable(now)) {
+            return None;
+        }
+
+        state.cooldown_remaining(now)
+    }
+
+    pub fn stats(&self) -> Vec<TokenStats> {
+        let now = Instant::now();
+        self.state
+            .lock()
+            .unwrap()
+            .tokens
+            .iter()
+            .map(|token| TokenStats {
+                token: mask_token(&token.token),
+                status: if token.revoked {
+                    TokenStatus::Revoked
+                } else if token.is_usable(now) {
+                    TokenStatus::Active
+                } else {
+                    TokenStatus::CoolingDown
+                },
+                uses: token.uses,
+                failures: token.failures,
+                rate_per_second: token.limiter.rate_per_second(),
+                queue_depth: token.limiter.queue_depth(),
+            })
+            .collect()
+    }
+}
+
+// Only the start of a token is ever shown or logged
+fn mask_token(token: &str) -> String {
+    let visible: String = token.chars().take(4).collect();
+    format!("{}...", visible)
+}
+
+/// Token pool shared by every VK call of the process.
+pub fn vk_token_pool() -> &'static TokenPool {
+    static POOL: OnceLock<TokenPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        TokenPool::new(
+            get_vk_tokens(),
+            get_vk_token_rotation(),
+            Duration::from_secs(get_vk_token_cooldown_seconds()),
+        )
+    })
+}
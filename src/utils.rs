@@ -1,6 +1,12 @@
-use crate::models::VkPostStats;
+use crate::models::{
+    Aggregation, AudienceKind, PollingDuration, PostCounters, PostInfoData, TokenRotation,
+    VkPostStats,
+};
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
 use sqlx::postgres::PgPool;
 use sqlx::postgres::PgPoolOptions;
+use std::collections::BTreeMap;
 
 pub fn get_pooling_period_seconds() -> i32 {
     std::env::var("POOLING_PERIOD_SECONDS")
@@ -9,6 +15,20 @@ pub fn get_pooling_period_seconds() -> i32 {
         .unwrap_or(300) // Default 5 minutes
 }
 
+pub fn get_min_duration_seconds() -> i64 {
+    std::env::var("POOLING_MIN_DURATION_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(60) // Default 1 minute
+}
+
+pub fn get_max_duration_seconds() -> i64 {
+    std::env::var("POOLING_MAX_DURATION_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(30 * 24 * 60 * 60) // Default 30 days
+}
+
 pub fn get_pooling_delta_seconds() -> i32 {
     std::env::var("POOLING_DELTA_SECONDS")
         .ok()
@@ -16,6 +36,56 @@ pub fn get_pooling_delta_seconds() -> i32 {
         .unwrap_or(30) // Default 30 seconds
 }
 
+/// Seconds between two comment collections of the same post, 0 turns the collector off.
+pub fn get_comments_polling_seconds() -> i64 {
+    std::env::var("COMMENTS_POLLING_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(900) // Default 15 minutes
+}
+
+/// Seconds between two audience collections of the same post, 0 turns the collector off.
+pub fn get_audience_polling_seconds() -> i64 {
+    std::env::var("AUDIENCE_POLLING_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3600) // Default 1 hour
+}
+
+/// Default period between two checks of a watched wall.
+pub fn get_watcher_polling_seconds() -> i64 {
+    std::env::var("WATCHER_POLLING_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(300) // Default 5 minutes
+}
+
+/// Most posts accepted by one POST /polling/batch request.
+pub fn get_polling_batch_max_posts() -> usize {
+    std::env::var("POLLING_BATCH_MAX_POSTS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(200) // Default 200 posts
+        .max(1)
+}
+
+/// How long the response to a request with an Idempotency-Key is kept for its retries.
+pub fn get_idempotency_key_ttl_seconds() -> i64 {
+    std::env::var("IDEMPOTENCY_KEY_TTL_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(24 * 60 * 60) // Default 24 hours
+        .max(1)
+}
+
+pub fn get_polling_workers() -> usize {
+    std::env::var("POLLING_WORKERS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(4) // Default 4 concurrent VK requests
+        .max(1)
+}
+
 pub async fn get_db_pool() -> Result<PgPool, sqlx::Error> {
     let database_url =
         std::env::var("DATABASE_URL").expect("DATABASE_URL must be set in .env file");
@@ -26,21 +96,399 @@ pub async fn get_db_pool() -> Result<PgPool, sqlx::Error> {
         .await
 }
 
-pub fn get_vk_token() -> Result<String, String> {
-    std::env::var("VK_TOKEN").map_err(|e| e.to_string())
+/// Tokens from `VK_TOKENS` (comma separated, `token:rate` overrides the rate limit), or the single `VK_TOKEN`.
+pub fn get_vk_tokens() -> Vec<(String, f64)> {
+    let default_rate = get_vk_rate_limit_per_second();
+    let tokens = std::env::var("VK_TOKENS")
+        .or_else(|_| std::env::var("VK_TOKEN"))
+        .unwrap_or_default();
+
+    tokens
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.split_once(':') {
+            Some((token, rate)) => (
+                token.to_string(),
+                rate.parse()
+                    .ok()
+                    .filter(|rate: &f64| *rate > 0.0)
+                    .unwrap_or(default_rate),
+            ),
+            None => (entry.to_string(), default_rate),
+        })
+        .collect()
+}
+
+pub fn get_vk_token_rotation() -> TokenRotation {
+    match std::env::var("VK_TOKEN_ROTATION").as_deref() {
+        Ok("lru") => TokenRotation::LeastRecentlyUsed,
+        _ => TokenRotation::RoundRobin, // Default round_robin
+    }
+}
+
+pub fn get_vk_token_cooldown_seconds() -> u64 {
+    std::env::var("VK_TOKEN_COOLDOWN_SECONDS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(60) // Default 1 minute
 }
 
 pub fn get_vk_api_domain() -> Result<String, String> {
     std::env::var("VK_API_DOMAIN").map_err(|e| e.to_string())
 }
 
+/// URL of another VK method next to the one in `VK_API_DOMAIN`, which points at wall.getById.
+pub fn get_vk_api_method_url(method: &str) -> Result<String, String> {
+    let domain = get_vk_api_domain()?;
+    let base = domain
+        .rsplit_once('/')
+        .map_or(domain.as_str(), |(base, _)| base);
+    Ok(format!("{}/{}", base, method))
+}
+
 pub fn get_vk_api_version() -> String {
     std::env::var("VK_API_VERSION").unwrap_or_else(|_| "5.199".to_string())
 }
 
+pub fn get_vk_batch_size() -> usize {
+    std::env::var("VK_BATCH_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(100) // wall.getById accepts up to 100 posts
+        .clamp(1, 100)
+}
+
+pub fn get_vk_rate_limit_per_second() -> f64 {
+    std::env::var("VK_RATE_LIMIT_PER_SECOND")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|rate: &f64| *rate > 0.0)
+        .unwrap_or(3.0) // VK allows 3 requests per second for a service key
+}
+
+pub fn get_vk_retry_max_attempts() -> u32 {
+    std::env::var("VK_RETRY_MAX_ATTEMPTS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3) // Default 3 attempts, the first one included
+        .max(1)
+}
+
+pub fn get_vk_retry_base_delay_ms() -> u64 {
+    std::env::var("VK_RETRY_BASE_DELAY_MS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(200) // Default 200 milliseconds
+}
+
+pub fn get_vk_retry_max_delay_ms() -> u64 {
+    std::env::var("VK_RETRY_MAX_DELAY_MS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(5000) // Default 5 seconds
+}
+
+pub fn get_timestamp_precision() -> SecondsFormat {
+    match std::env::var("TIMESTAMP_PRECISION").as_deref() {
+        Ok("s") => SecondsFormat::Secs,
+        Ok("us") => SecondsFormat::Micros,
+        _ => SecondsFormat::Millis, // Default milliseconds
+    }
+}
+
+/// Formats a timestamp as RFC 3339 with the offset of the display zone.
+///
+/// The precision of fractional seconds is set by `TIMESTAMP_PRECISION`.
+pub fn format_timestamp(dt: &DateTime<Utc>, tz: Tz) -> String {
+    dt.with_timezone(&tz)
+        .to_rfc3339_opts(get_timestamp_precision(), false)
+}
+
+/// Parses an RFC 3339 timestamp, one without an offset like `2026-02-25T21:52:04` is taken in `tz`.
+pub fn parse_timestamp(value: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.with_timezone(&Utc));
+    }
+
+    let naive = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%.f").ok()?;
+    // A local time repeated by a DST switch is taken at its first occurrence
+    tz.from_local_datetime(&naive)
+        .earliest()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// Resolves the display zone of a request given as an IANA name like `Europe/Moscow`, UTC by default.
+pub fn parse_timezone(value: Option<&str>) -> Result<Tz, String> {
+    match value {
+        None => Ok(Tz::UTC),
+        Some(name) => name.parse().map_err(|_| {
+            format!(
+                "Invalid timezone {}, expected an IANA name like Europe/Moscow",
+                name
+            )
+        }),
+    }
+}
+
+/// Converts VK stats to database counters, a count above `i64::MAX` is an error rather than a wrapped value.
+pub fn post_counters(stats: &VkPostStats) -> Result<PostCounters, String> {
+    let counter = |name: &str, value: u64| {
+        i64::try_from(value).map_err(|_| format!("{} {} does not fit into BIGINT", name, value))
+    };
+
+    Ok(PostCounters {
+        likes_count: counter("likes_count", stats.likes_count)?,
+        comments_count: counter("comments_count", stats.comments_count)?,
+        reposts_count: counter("reposts_count", stats.reposts_count)?,
+        views_count: counter("views_count", stats.views_count)?,
+        metrics: stats.extra.clone(),
+    })
+}
+
 pub fn is_post_stats_empty(stats: &VkPostStats) -> bool {
     stats.likes_count == 0
         && stats.comments_count == 0
         && stats.reposts_count == 0
         && stats.views_count == 0
 }
+
+/// Checks the Idempotency-Key header, a missing header means the request is not idempotent.
+pub fn parse_idempotency_key(key: Option<&str>) -> Result<Option<String>, String> {
+    let Some(key) = key else {
+        return Ok(None);
+    };
+
+    let key = key.trim();
+    if key.is_empty() || key.len() > 255 {
+        return Err(format!(
+            "Idempotency-Key must be from 1 to 255 characters long, got {}",
+            key.len()
+        ));
+    }
+
+    Ok(Some(key.to_string()))
+}
+
+/// Resolves the requested parsing window length in seconds, falling back to `POOLING_PERIOD_SECONDS`.
+pub fn resolve_duration_seconds(duration: Option<&PollingDuration>) -> Result<i64, String> {
+    // The configured default is held to the same limits as a requested duration
+    let seconds = match duration {
+        None => get_pooling_period_seconds() as i64,
+        Some(duration) => duration_seconds(duration)?,
+    };
+
+    let (min, max) = (get_min_duration_seconds(), get_max_duration_seconds());
+    if seconds < min || seconds > max {
+        return Err(format!(
+            "Duration must be between {} and {} seconds, got {}",
+            min, max, seconds
+        ));
+    }
+
+    Ok(seconds)
+}
+
+/// Period between two checks of a watched wall, `WATCHER_POLLING_SECONDS` when not given.
+pub fn resolve_watch_interval_seconds(interval: Option<&PollingDuration>) -> Result<i64, String> {
+    let seconds = match interval {
+        None => return Ok(get_watcher_polling_seconds()),
+        Some(interval) => duration_seconds(interval)?,
+    };
+
+    if seconds <= 0 {
+        return Err(format!(
+            "Interval must be positive, got {} seconds",
+            seconds
+        ));
+    }
+
+    Ok(seconds)
+}
+
+/// How old a post must be before a wall watcher enrols it, no minimum when not given.
+pub fn resolve_min_age_seconds(min_age: Option<&PollingDuration>) -> Result<i64, String> {
+    let seconds = min_age.map(duration_seconds).transpose()?.unwrap_or(0);
+
+    if seconds < 0 {
+        return Err(format!(
+            "Minimum age can't be negative, got {} seconds",
+            seconds
+        ));
+    }
+
+    Ok(seconds)
+}
+
+fn duration_seconds(duration: &PollingDuration) -> Result<i64, String> {
+    match duration {
+        PollingDuration::Seconds(seconds) => Ok(*seconds),
+        PollingDuration::Iso8601(value) => parse_iso8601_duration(value),
+    }
+}
+
+/// Parses an ISO-8601 duration like `P1DT6H30M` into seconds.
+///
+/// Years and months have no fixed length and are rejected.
+pub fn parse_iso8601_duration(value: &str) -> Result<i64, String> {
+    let invalid = || format!("Invalid ISO-8601 duration: {}", value);
+
+    let rest = value.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+    let mut has_components = false;
+    let mut has_time_components = false;
+    // Units go from the largest to the smallest, each at most once
+    let mut last_rank = None;
+
+    for c in rest.chars() {
+        let (rank, unit) = match (in_time, c) {
+            (_, '0'..='9') => {
+                number.push(c);
+                continue;
+            }
+            (false, 'T') if number.is_empty() => {
+                in_time = true;
+                continue;
+            }
+            (false, 'W') => (0, 7 * 24 * 60 * 60),
+            (false, 'D') => (1, 24 * 60 * 60),
+            (true, 'H') => (2, 60 * 60),
+            (true, 'M') => (3, 60),
+            (true, 'S') => (4, 1),
+            (false, 'Y') | (false, 'M') => {
+                return Err(format!(
+                    "Years and months are not supported in duration: {}",
+                    value
+                ));
+            }
+            _ => return Err(invalid()),
+        };
+
+        if last_rank.is_some_and(|last_rank| rank <= last_rank) {
+            return Err(invalid());
+        }
+        last_rank = Some(rank);
+
+        let amount: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+        has_components = true;
+        has_time_components |= in_time;
+
+        seconds = amount
+            .checked_mul(unit)
+            .and_then(|component| seconds.checked_add(component))
+            .ok_or_else(invalid)?;
+    }
+
+    // A `T` must be followed by at least one of H, M or S
+    if !number.is_empty() || !has_components || in_time && !has_time_components {
+        return Err(invalid());
+    }
+
+    Ok(seconds)
+}
+
+/// Parses a bucket size given either as seconds (`300`) or as an ISO-8601 duration (`PT5M`).
+pub fn parse_step_seconds(value: &str) -> Result<i64, String> {
+    let seconds = match value.parse::<i64>() {
+        Ok(seconds) => seconds,
+        Err(_) => parse_iso8601_duration(value)?,
+    };
+
+    if seconds <= 0 {
+        return Err(format!("Step must be positive, got {}", value));
+    }
+
+    Ok(seconds)
+}
+
+pub fn parse_aggregation(value: &str) -> Result<Aggregation, String> {
+    match value {
+        "first" => Ok(Aggregation::First),
+        "last" => Ok(Aggregation::Last),
+        "max" => Ok(Aggregation::Max),
+        "avg" => Ok(Aggregation::Avg),
+        _ => Err(format!(
+            "Invalid aggregation {}, expected one of: first, last, max, avg",
+            value
+        )),
+    }
+}
+
+pub fn parse_audience_kind(value: &str) -> Result<AudienceKind, String> {
+    match value {
+        "like" => Ok(AudienceKind::Like),
+        "repost" => Ok(AudienceKind::Repost),
+        _ => Err(format!(
+            "Invalid audience kind {}, expected one of: like, repost",
+            value
+        )),
+    }
+}
+
+/// Groups snapshots sorted by time into buckets of `step_seconds` aligned to the Unix epoch.
+///
+/// Every bucket becomes one snapshot stamped with the bucket start.
+pub fn bucket_post_info(
+    data: Vec<PostInfoData>,
+    step_seconds: i64,
+    aggregation: Aggregation,
+) -> Vec<PostInfoData> {
+    let mut buckets: Vec<(i64, Vec<PostInfoData>)> = Vec::new();
+
+    for d in data {
+        let start = d.info_time.timestamp().div_euclid(step_seconds) * step_seconds;
+        match buckets.last_mut() {
+            Some((bucket_start, items)) if *bucket_start == start => items.push(d),
+            _ => buckets.push((start, vec![d])),
+        }
+    }
+
+    buckets
+        .into_iter()
+        .map(|(start, items)| {
+            let metric = |value: fn(&PostInfoData) -> i64| {
+                let values: Vec<i64> = items.iter().map(value).collect();
+                aggregate(&values, aggregation)
+            };
+
+            // An extra metric is reduced over the snapshots that have it
+            let mut extra: BTreeMap<&str, Vec<i64>> = BTreeMap::new();
+            for d in &items {
+                for (name, value) in &d.metrics {
+                    extra.entry(name).or_default().push(*value);
+                }
+            }
+
+            PostInfoData {
+                comments_count: metric(|d| d.comments_count),
+                likes_count: metric(|d| d.likes_count),
+                views_count: metric(|d| d.views_count),
+                reposts_count: metric(|d| d.reposts_count),
+                metrics: extra
+                    .into_iter()
+                    .map(|(name, values)| (name.to_string(), aggregate(&values, aggregation)))
+                    .collect(),
+                info_time: DateTime::from_timestamp(start, 0).unwrap_or_default(),
+            }
+        })
+        .collect()
+}
+
+// Reduces the values of one metric inside a bucket, `values` is never empty
+fn aggregate(values: &[i64], aggregation: Aggregation) -> i64 {
+    match aggregation {
+        Aggregation::First => values[0],
+        Aggregation::Last => values[values.len() - 1],
+        Aggregation::Max => values.iter().copied().max().unwrap_or_default(),
+        Aggregation::Avg => {
+            // The sum of large counters may not fit into i64, the mean always does
+            let sum: i128 = values.iter().map(|&value| value as i128).sum();
+            let len = values.len() as i128;
+            ((sum + len / 2) / len) as i64
+        }
+    }
+}
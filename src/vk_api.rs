@@ -1,44 +1,564 @@
-use crate::models::VkPostStats;
-use crate::utils::{get_vk_api_domain, get_vk_api_version, get_vk_token};
-use reqwest;
-use rocket::response::status;
-use serde_json::Value;
-
-pub async fn call_vk(post_id: &str) -> Result<VkPostStats, status::BadRequest<String>> {
-    let token = get_vk_token().map_err(|e| status::BadRequest(e))?;
-    let domain = get_vk_api_domain().map_err(|e| status::BadRequest(e))?;
-    let version = get_vk_api_version();
-
-    let url = format!(
-        "{}?access_token={}&v={}&posts={}",
-        domain, token, version, post_id
-    );
-
-    let response = reqwest::get(&url)
-        .await
-        .map_err(|e| status::BadRequest(format!("Request failed: {}", e)))?;
-
-    let data = response
-        .text()
-        .await
-        .map_err(|e| status::BadRequest(format!("Failed to read response: {}", e)))?;
-
-    // Parse JSON response
-    let json_data: Value = serde_json::from_str(&data)
-        .map_err(|e| status::BadRequest(format!("Failed to parse JSON: {}", e)))?;
-
-    // Extract the required fields from the first post in the response array
-    let post = &json_data["response"][0];
-
-    let comments_count = post["comments"]["count"].as_u64().unwrap_or(0);
-    let likes_count = post["likes"]["count"].as_u64().unwrap_or(0);
-    let views_count = post["views"]["count"].as_u64().unwrap_or(0);
-    let reposts_count = post["reposts"]["count"].as_u64().unwrap_or(0);
+use crate::errors::{AppError, VkError};
+use crate::models::{
+    AudienceKind, VkAudienceEntry, VkAudiencePage, VkBatchStats, VkComment, VkCommentsPage,
+    VkPostMeta, VkPostStats, VkWallPage, VkWallPost,
+};
+use crate::token_pool::{TokenPool, vk_token_pool};
+use crate::utils::{
+    get_pooling_delta_seconds, get_vk_api_method_url, get_vk_api_version, get_vk_batch_size,
+    get_vk_retry_base_delay_ms, get_vk_retry_max_attempts, get_vk_retry_max_delay_ms,
+};
+use chrono::DateTime;
+use rand::Rng;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+// Extra metrics read from the post as they are, a field VK did not send is left out.
+// A new metric only needs a name and the JSON pointer to its value
+const EXTRA_METRICS: &[(&str, &str)] = &[
+    ("reposts_wall", "/reposts/wall_count"),
+    ("reposts_mail", "/reposts/mail_count"),
+    ("can_comment", "/comments/can_post"),
+    ("is_donut", "/donut/is_donut"),
+    ("marked_as_ads", "/marked_as_ads"),
+    ("is_pinned", "/is_pinned"),
+    ("edited_at", "/edited"),
+];
+
+/// wall.getComments returns at most 100 comments per call.
+pub const COMMENTS_PAGE_SIZE: usize = 100;
+
+/// likes.getList and wall.getReposts return at most 1000 entries per call.
+pub const AUDIENCE_PAGE_SIZE: usize = 1000;
+
+/// wall.get returns at most 100 posts per call.
+pub const WALL_PAGE_SIZE: usize = 100;
+
+// VK counts requests per second, retrying a rate-limited call sooner hits the same limit
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(1);
+
+/// How failed VK calls are retried: exponential backoff with jitter, limited by attempts.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn from_env() -> Self {
+        RetryPolicy {
+            max_attempts: get_vk_retry_max_attempts(),
+            base_delay: Duration::from_millis(get_vk_retry_base_delay_ms()),
+            max_delay: Duration::from_millis(get_vk_retry_max_delay_ms()),
+        }
+    }
+
+    /// Delay before the attempt following the failed `attempt` (counted from 1).
+    pub fn delay(&self, attempt: u32, error: &AppError) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        // Half of the backoff is fixed, the other half is random so parallel jobs spread out
+        let half = backoff / 2;
+        let delay = half + jitter(backoff - half);
+
+        match error {
+            AppError::VkApi(VkError::TooManyRequests(_) | VkError::FloodControl(_)) => {
+                delay.max(RATE_LIMIT_WINDOW.min(self.max_delay))
+            }
+            _ => delay,
+        }
+    }
+}
+
+// Random duration in [0, max] from the thread-local generator
+fn jitter(max: Duration) -> Duration {
+    let max_nanos = u64::try_from(max.as_nanos()).unwrap_or(u64::MAX);
+
+    Duration::from_nanos(rand::rng().random_range(0..=max_nanos))
+}
+
+/// VK backend used by the endpoints and the polling tasks.
+#[rocket::async_trait]
+pub trait VkClient: Send + Sync {
+    /// Fetches stats of one post, a post VK did not return has zero stats.
+    async fn call_vk(&self, post_id: &str) -> Result<VkPostStats, AppError>;
+
+    /// Fetches stats for many posts, ids VK did not return are collected in `missing`.
+    async fn call_vk_batch(&self, post_ids: &[String]) -> Result<VkBatchStats, AppError>;
+
+    /// Fetches one page of `COMMENTS_PAGE_SIZE` comments from `offset`, oldest first.
+    ///
+    /// Without `thread_id` the page holds top-level comments, with it the replies of that comment.
+    async fn get_comments(
+        &self,
+        post_id: &str,
+        thread_id: Option<i64>,
+        offset: usize,
+    ) -> Result<VkCommentsPage, AppError>;
+
+    /// Fetches one page of `AUDIENCE_PAGE_SIZE` users who liked or reposted the post from `offset`.
+    async fn get_audience(
+        &self,
+        post_id: &str,
+        kind: AudienceKind,
+        offset: usize,
+    ) -> Result<VkAudiencePage, AppError>;
+
+    /// Fetches one page of `WALL_PAGE_SIZE` posts of the wall from `offset`, newest first.
+    ///
+    /// A pinned post comes first, whatever its date.
+    async fn get_wall(&self, owner_id: i64, offset: usize) -> Result<VkWallPage, AppError>;
+}
+
+/// Client of the real VK API, configured from env.
+pub struct ReqwestVkClient {
+    http: reqwest::Client,
+    tokens: &'static TokenPool,
+}
+
+impl Default for ReqwestVkClient {
+    fn default() -> Self {
+        Self::with_tokens(vk_token_pool())
+    }
+}
+
+impl ReqwestVkClient {
+    /// Client that takes its tokens from the given pool instead of the one shared by the process.
+    pub fn with_tokens(tokens: &'static TokenPool) -> Self {
+        ReqwestVkClient {
+            http: reqwest::Client::default(),
+            tokens,
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl VkClient for ReqwestVkClient {
+    async fn call_vk(&self, post_id: &str) -> Result<VkPostStats, AppError> {
+        let json_data = self.fetch_posts(post_id).await?;
+
+        // Extract the required fields from the first post in the response array
+        let post = response_items(&json_data).first().unwrap_or(&Value::Null);
+
+        parse_post_stats(post).map_err(AppError::Vk)
+    }
+
+    /// Sends at most `VK_BATCH_SIZE` ids per wall.getById request.
+    ///
+    /// Every item of the response is mapped back to the requested id by its `owner_id`/`id`,
+    /// ids that VK did not return are collected in `missing`, ids with unusable stats in `invalid`.
+    async fn call_vk_batch(&self, post_ids: &[String]) -> Result<VkBatchStats, AppError> {
+        let mut stats = HashMap::new();
+        let mut meta = HashMap::new();
+        let mut missing = Vec::new();
+        let mut invalid = HashMap::new();
+
+        // The same post may be requested twice, VK would only return it once
+        let mut seen = HashSet::new();
+        let unique_ids: Vec<&String> = post_ids.iter().filter(|id| seen.insert(*id)).collect();
+
+        for chunk in unique_ids.chunks(get_vk_batch_size()) {
+            let posts = chunk
+                .iter()
+                .map(|id| id.as_str())
+                .collect::<Vec<_>>()
+                .join(",");
+            let json_data = self.fetch_posts(&posts).await?;
+
+            let mut returned: HashMap<String, &Value> = response_items(&json_data)
+                .iter()
+                .filter_map(|post| {
+                    let owner_id = post["owner_id"].as_i64()?;
+                    let id = post["id"].as_i64()?;
+                    Some((format!("{}_{}", owner_id, id), post))
+                })
+                .collect();
+
+            for post_id in chunk {
+                match returned.remove(post_id.as_str()) {
+                    Some(post) => {
+                        match parse_post_stats(post) {
+                            Ok(post_stats) => {
+                                stats.insert(post_id.to_string(), post_stats);
+                            }
+                            Err(e) => {
+                                invalid.insert(post_id.to_string(), e);
+                            }
+                        }
+                        if let Some(post_meta) = parse_post_meta(post) {
+                            meta.insert(post_id.to_string(), post_meta);
+                        }
+                    }
+                    None => missing.push(post_id.to_string()),
+                }
+            }
+        }
+
+        Ok(VkBatchStats {
+            stats,
+            meta,
+            missing,
+            invalid,
+        })
+    }
+
+    async fn get_comments(
+        &self,
+        post_id: &str,
+        thread_id: Option<i64>,
+        offset: usize,
+    ) -> Result<VkCommentsPage, AppError> {
+        let (owner_id, id) = post_id
+            .split_once('_')
+            .ok_or_else(|| AppError::Validation(format!("Invalid VK post id {}", post_id)))?;
+
+        let mut params = format!(
+            "owner_id={}&post_id={}&offset={}&count={}&sort=asc&need_likes=1",
+            owner_id, id, offset, COMMENTS_PAGE_SIZE
+        );
+        if let Some(thread_id) = thread_id {
+            params.push_str(&format!("&comment_id={}", thread_id));
+        }
+
+        let json_data = self.fetch_method("wall.getComments", &params).await?;
+        Ok(parse_comments_page(&json_data, thread_id))
+    }
+
+    async fn get_audience(
+        &self,
+        post_id: &str,
+        kind: AudienceKind,
+        offset: usize,
+    ) -> Result<VkAudiencePage, AppError> {
+        let (owner_id, id) = post_id
+            .split_once('_')
+            .ok_or_else(|| AppError::Validation(format!("Invalid VK post id {}", post_id)))?;
+
+        let json_data = match kind {
+            AudienceKind::Like => {
+                let params = format!(
+                    "type=post&owner_id={}&item_id={}&filter=likes&offset={}&count={}",
+                    owner_id, id, offset, AUDIENCE_PAGE_SIZE
+                );
+                self.fetch_method("likes.getList", &params).await?
+            }
+            AudienceKind::Repost => {
+                let params = format!(
+                    "owner_id={}&post_id={}&offset={}&count={}",
+                    owner_id, id, offset, AUDIENCE_PAGE_SIZE
+                );
+                self.fetch_method("wall.getReposts", &params).await?
+            }
+        };
+
+        Ok(parse_audience_page(&json_data, kind))
+    }
+
+    async fn get_wall(&self, owner_id: i64, offset: usize) -> Result<VkWallPage, AppError> {
+        let params = format!(
+            "owner_id={}&offset={}&count={}",
+            owner_id, offset, WALL_PAGE_SIZE
+        );
+
+        let json_data = self.fetch_method("wall.get", &params).await?;
+        Ok(parse_wall_page(&json_data))
+    }
+}
+
+impl ReqwestVkClient {
+    async fn fetch_posts(&self, posts: &str) -> Result<Value, AppError> {
+        self.fetch_method("wall.getById", &format!("posts={}", posts))
+            .await
+    }
+
+    async fn fetch_method(&self, method: &str, params: &str) -> Result<Value, AppError> {
+        let method_url = get_vk_api_method_url(method).map_err(AppError::Vk)?;
+        let version = get_vk_api_version();
+        let tokens = self.tokens;
+
+        let policy = RetryPolicy::from_env();
+        // A request still queued by the next dispatcher tick is late, so it is given up
+        let max_wait = Duration::from_secs(get_pooling_delta_seconds().max(1) as u64);
+        let mut attempt = 1;
+        loop {
+            // Every attempt may go with another token, a rejected one is out of rotation already
+            let lease = match tokens.checkout() {
+                Ok(lease) => lease,
+                // Every token rests after a rate limit, the first one to come back is waited for
+                // like a retry delay, a longer cooldown fails the call
+                Err(e) => match tokens.cooldown_remaining() {
+                    Some(wait) if wait <= policy.max_delay && attempt < policy.max_attempts => {
+                        eprintln!(
+                            "All VK tokens are cooling down on attempt {}/{}, waiting {:?}",
+                            attempt, policy.max_attempts, wait
+                        );
+                        tokio::time::sleep(wait).await;
+                        attempt += 1;
+                        continue;
+                    }
+                    _ => return Err(e),
+                },
+            };
+            if !lease.limiter.acquire(max_wait).await {
+                return Err(AppError::RateLimited {
+                    message: format!(
+                        "No free VK request slot within {} seconds",
+                        max_wait.as_secs()
+                    ),
+                    retry_after: max_wait,
+                });
+            }
+
+            let url = format!(
+                "{}?access_token={}&v={}&{}",
+                method_url, lease.token, version, params
+            );
+            let result = self.fetch_url(&url).await;
+            tokens.report(&lease, &result);
+
+            match result {
+                // A rejected or rate-limited token is replaced by the next one at once, this is not
+                // counted as an attempt: the failed token is out of rotation, so switching ends
+                Err(AppError::VkApi(
+                    VkError::AuthFailed(_) | VkError::TooManyRequests(_) | VkError::FloodControl(_),
+                )) if tokens.has_usable_token() => {}
+                // Only transient failures are retried, a bad token or a private post fails at once
+                Err(e) if e.is_retryable() && attempt < policy.max_attempts => {
+                    let delay = policy.delay(attempt, &e);
+                    eprintln!(
+                        "VK API call failed on attempt {}/{}, retrying in {:?}: {}",
+                        attempt, policy.max_attempts, delay, e
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn fetch_url(&self, url: &str) -> Result<Value, AppError> {
+        // The URL carries the access token, it must not end up in logs or error responses
+        let response = self
+            .http
+            .get(url)
+            .send()
+            .await
+            .map_err(|e| AppError::Vk(format!("Request failed: {}", e.without_url())))?;
+
+        let data = response
+            .text()
+            .await
+            .map_err(|e| AppError::Vk(format!("Failed to read response: {}", e.without_url())))?;
+
+        // Parse JSON response
+        let json_data: Value = serde_json::from_str(&data)
+            .map_err(|e| AppError::Vk(format!("Failed to parse JSON: {}", e)))?;
+
+        // Errors come with HTTP 200 and an `error` object instead of `response`
+        match response_error(&json_data) {
+            Some(e) => Err(e.into()),
+            None => Ok(json_data),
+        }
+    }
+}
+
+fn response_error(json_data: &Value) -> Option<VkError> {
+    let error = json_data.get("error")?;
+    let code = error["error_code"].as_i64().unwrap_or_default();
+    let message = error["error_msg"].as_str().unwrap_or_default().to_string();
+
+    Some(VkError::from_code(code, message))
+}
+
+// Newer API versions wrap the posts into `response.items`, older ones return a plain array
+fn response_items(json_data: &Value) -> &[Value] {
+    let response = &json_data["response"];
+    let items = if response["items"].is_array() {
+        &response["items"]
+    } else {
+        response
+    };
+
+    items.as_array().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Reads the four counters, a missing counter is 0 (old posts have no `views`).
+///
+/// A counter that is present but is not a non-negative integer fitting into i64 is rejected.
+pub fn parse_post_stats(post: &Value) -> Result<VkPostStats, String> {
+    let counter = |name: &str| {
+        let value = &post[name]["count"];
+        if value.is_null() {
+            return Ok(0);
+        }
+
+        value
+            .as_i64()
+            .and_then(|count| u64::try_from(count).ok())
+            .ok_or_else(|| format!("Invalid {} count in VK response: {}", name, value))
+    };
 
     Ok(VkPostStats {
-        comments_count,
-        likes_count,
-        views_count,
-        reposts_count,
+        comments_count: counter("comments")?,
+        likes_count: counter("likes")?,
+        views_count: counter("views")?,
+        reposts_count: counter("reposts")?,
+        extra: parse_extra_metrics(post),
+    })
+}
+
+/// Reads the metrics beyond the four counters, flags are stored as 0 or 1.
+pub fn parse_extra_metrics(post: &Value) -> BTreeMap<String, i64> {
+    let mut metrics: BTreeMap<String, i64> = EXTRA_METRICS
+        .iter()
+        .filter_map(|(name, pointer)| {
+            let value = post.pointer(pointer)?;
+            let value = value.as_i64().or_else(|| value.as_bool().map(i64::from))?;
+            Some((name.to_string(), value))
+        })
+        .collect();
+
+    // Views of the videos and clips attached to the post, summed up
+    let video_views: Vec<i64> = post["attachments"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+        .iter()
+        .filter(|attachment| attachment["type"] == "video")
+        .filter_map(|attachment| attachment["video"]["views"].as_i64())
+        .collect();
+    if !video_views.is_empty() {
+        metrics.insert("video_views".to_string(), video_views.iter().sum());
+    }
+
+    metrics
+}
+
+/// Reads the post content, a post without an owner or a publish date has no usable metadata.
+pub fn parse_post_meta(post: &Value) -> Option<VkPostMeta> {
+    let timestamp = |value: &Value| {
+        value
+            .as_i64()
+            .and_then(|ts| DateTime::from_timestamp(ts, 0))
+    };
+    let owner_id = post["owner_id"].as_i64()?;
+
+    Some(VkPostMeta {
+        owner_id,
+        from_id: post["from_id"].as_i64().unwrap_or(owner_id),
+        published_at: timestamp(&post["date"])?,
+        post_type: post["post_type"].as_str().unwrap_or("post").to_string(),
+        text: post["text"].as_str().unwrap_or_default().to_string(),
+        attachments: post
+            .get("attachments")
+            .cloned()
+            .unwrap_or_else(|| json!([])),
+        edited_at: timestamp(&post["edited"]),
+        is_deleted: post["is_deleted"].as_bool().unwrap_or(false),
     })
 }
+
+/// Reads a wall.getComments page, deleted comments are left out.
+pub fn parse_comments_page(json_data: &Value, thread_id: Option<i64>) -> VkCommentsPage {
+    let response = &json_data["response"];
+    let items = response_items(json_data);
+
+    let comments = items
+        .iter()
+        .filter(|item| !item["deleted"].as_bool().unwrap_or(false))
+        .filter_map(|item| {
+            Some(VkComment {
+                id: item["id"].as_i64()?,
+                from_id: item["from_id"].as_i64().unwrap_or_default(),
+                published_at: DateTime::from_timestamp(item["date"].as_i64()?, 0)?,
+                text: item["text"].as_str().unwrap_or_default().to_string(),
+                likes_count: item["likes"]["count"].as_i64().unwrap_or_default(),
+                // A reply without an explicit target answers the thread itself
+                parent_id: item["reply_to_comment"].as_i64().or(thread_id),
+                thread_count: item["thread"]["count"].as_u64().unwrap_or_default() as usize,
+            })
+        })
+        .collect();
+
+    // `count` includes the replies, only `current_level_count` matches the paging
+    let total = response["current_level_count"]
+        .as_u64()
+        .or_else(|| response["count"].as_u64())
+        .unwrap_or_default() as usize;
+
+    VkCommentsPage {
+        comments,
+        raw_count: items.len(),
+        total,
+    }
+}
+
+/// Reads a likes.getList or wall.getReposts page.
+///
+/// likes.getList lists bare user ids, wall.getReposts lists the reposts themselves,
+/// so only a repost knows when it was made.
+pub fn parse_audience_page(json_data: &Value, kind: AudienceKind) -> VkAudiencePage {
+    let items = response_items(json_data);
+
+    match kind {
+        AudienceKind::Like => VkAudiencePage {
+            entries: items
+                .iter()
+                .filter_map(|item| {
+                    Some(VkAudienceEntry {
+                        user_id: item.as_i64()?,
+                        engaged_at: None,
+                    })
+                })
+                .collect(),
+            raw_count: items.len(),
+            total: json_data["response"]["count"]
+                .as_u64()
+                .map(|count| count as usize),
+        },
+        AudienceKind::Repost => VkAudiencePage {
+            entries: items
+                .iter()
+                .filter_map(|item| {
+                    Some(VkAudienceEntry {
+                        user_id: item["from_id"].as_i64().or(item["owner_id"].as_i64())?,
+                        engaged_at: item["date"]
+                            .as_i64()
+                            .and_then(|ts| DateTime::from_timestamp(ts, 0)),
+                    })
+                })
+                .collect(),
+            raw_count: items.len(),
+            total: None,
+        },
+    }
+}
+
+/// Reads a wall.get page, posts without an owner, id or date are left out.
+pub fn parse_wall_page(json_data: &Value) -> VkWallPage {
+    let items = response_items(json_data);
+    let posts = items
+        .iter()
+        .filter_map(|item| {
+            Some(VkWallPost {
+                vk_id: format!("{}_{}", item["owner_id"].as_i64()?, item["id"].as_i64()?),
+                published_at: DateTime::from_timestamp(item["date"].as_i64()?, 0)?,
+                text: item["text"].as_str().unwrap_or_default().to_string(),
+                // VK sends 1 for the pinned post and leaves the field out otherwise
+                is_pinned: item["is_pinned"].as_i64().unwrap_or_default() == 1,
+            })
+        })
+        .collect();
+
+    VkWallPage {
+        posts,
+        raw_count: items.len(),
+        total: json_data["response"]["count"].as_u64().unwrap_or_default() as usize,
+    }
+}
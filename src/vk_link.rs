@@ -0,0 +1,109 @@
+// Hosts that serve the same posts, compared in lower case
+const VK_HOSTS: &[&str] = &[
+    "vk.com",
+    "www.vk.com",
+    "m.vk.com",
+    "vk.ru",
+    "www.vk.ru",
+    "m.vk.ru",
+];
+
+/// Turns a link to a VK post or a bare post id into the canonical `owner_id_post_id`.
+///
+/// Accepts `https://vk.com/wall-1_2` on any VK host, with or without the scheme,
+/// feed and community links with `?w=wall-1_2`, other query parameters and fragments,
+/// and bare ids such as `-1_2`.
+pub fn parse_vk_post_link(link: &str) -> Result<String, String> {
+    let link = link.trim();
+    let invalid = || {
+        format!(
+            "Invalid VK link format. Expected a post link such as https://vk.com/wall-1_2 or an id such as -1_2, got: {}",
+            link
+        )
+    };
+
+    if let Some(vk_id) = parse_post_id(link) {
+        return Ok(vk_id);
+    }
+
+    let without_scheme = link
+        .strip_prefix("https://")
+        .or_else(|| link.strip_prefix("http://"))
+        .unwrap_or(link);
+
+    // The fragment never names the post, the query may: vk.com/feed?w=wall-1_2
+    let without_fragment = without_scheme.split('#').next().unwrap_or_default();
+    let (address, query) = without_fragment
+        .split_once('?')
+        .unwrap_or((without_fragment, ""));
+    let (host, path) = address.split_once('/').unwrap_or((address, ""));
+
+    if !VK_HOSTS.contains(&host.to_lowercase().as_str()) {
+        return Err(invalid());
+    }
+
+    let from_query = query
+        .split('&')
+        .filter_map(|pair| pair.strip_prefix("w="))
+        .find_map(|value| value.strip_prefix("wall"));
+    let from_path = path.trim_end_matches('/').strip_prefix("wall");
+
+    from_query
+        .and_then(parse_post_id)
+        .or_else(|| from_path.and_then(parse_post_id))
+        .ok_or_else(invalid)
+}
+
+/// Turns a wall owner into its signed VK id.
+///
+/// Accepts a signed id such as `-1` or `1` and community names such as `club1` or `public1`,
+/// which resolve to the negative id of the community.
+pub fn parse_vk_owner_id(owner: &str) -> Result<i64, String> {
+    let owner = owner.trim();
+    let invalid = || {
+        format!(
+            "Invalid VK owner. Expected an id such as -1 or a community such as club1, got: {}",
+            owner
+        )
+    };
+
+    let lower = owner.to_lowercase();
+    let community = lower
+        .strip_prefix("club")
+        .or_else(|| lower.strip_prefix("public"));
+    let (sign, digits) = match community {
+        Some(digits) => (-1, digits),
+        None => match owner.strip_prefix('-') {
+            Some(digits) => (-1, digits),
+            None => (1, owner),
+        },
+    };
+
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid());
+    }
+
+    match digits.parse::<i64>() {
+        Ok(id) if id != 0 => Ok(sign * id),
+        _ => Err(invalid()),
+    }
+}
+
+// `owner_id_post_id` with a non-zero owner and a positive post id, leading zeros are dropped
+fn parse_post_id(value: &str) -> Option<String> {
+    let (owner, post) = value.split_once('_')?;
+
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let owner_digits = owner.strip_prefix('-').unwrap_or(owner);
+    if !digits(owner_digits) || !digits(post) {
+        return None;
+    }
+
+    let owner_id: i64 = owner.parse().ok()?;
+    let post_id: i64 = post.parse().ok()?;
+    if owner_id == 0 || post_id == 0 {
+        return None;
+    }
+
+    Some(format!("{}_{}", owner_id, post_id))
+}
@@ -0,0 +1,233 @@
+#[macro_use]
+extern crate rocket;
+
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+use rstest::rstest;
+use serde_json::Value;
+use std::sync::Arc;
+
+// Include all necessary modules for testing
+#[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
+#[path = "../src/db_commands.rs"]
+mod db_commands;
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/fake_vk_client.rs"]
+mod fake_vk_client;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
+#[path = "../src/tasks.rs"]
+mod tasks;
+#[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+#[allow(dead_code)]
+#[path = "../src/vk_link.rs"]
+mod vk_link;
+
+#[allow(dead_code)]
+#[path = "../src/endpoints.rs"]
+mod endpoints;
+
+use clock::{Clock, SystemClock};
+use endpoints::{delete_polling, get_polling};
+use errors::VkError;
+use fake_vk_client::{FakeReply, FakeVkClient};
+use models::VkPostStats;
+use tasks::{Dispatcher, JobRegistry};
+use vk_api::VkClient;
+
+mod test_utils;
+use test_utils::{get_post_by_id, insert_post, insert_post_info, setup_test_db};
+
+fn create_test_rocket(pool: sqlx::PgPool, registry: JobRegistry) -> rocket::Rocket<rocket::Build> {
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    rocket::build()
+        .manage(Arc::new(pool))
+        .manage(clock)
+        .manage(registry)
+        .mount("/", rocket::routes![get_polling, delete_polling])
+}
+
+#[rstest]
+#[case::not_found(99999)]
+#[case::invalid_id(0)]
+fn test_delete_polling_not_found(#[case] scrapper_id: i32) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool, JobRegistry::default()))
+        .expect("valid rocket instance");
+
+    let response = client
+        .delete(format!("/polling?scrapper_id={}", scrapper_id))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::NotFound);
+    assert!(response.into_string().unwrap().contains("not found"));
+}
+
+#[test]
+fn test_delete_polling_ends_window_and_keeps_data() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let now = chrono::Utc::now();
+    let post_id = rt.block_on(async {
+        let post_id = insert_post(&pool, "-123_456", now, now + chrono::Duration::hours(1))
+            .await
+            .expect("Failed to insert post");
+
+        insert_post_info(&pool, post_id, 10, 5, 2, 100, now)
+            .await
+            .expect("Failed to insert post_info");
+
+        post_id
+    });
+
+    // The post is part of a running job
+    let registry = JobRegistry::default();
+    let job_id = uuid::Uuid::new_v4();
+    assert!(registry.register(post_id, job_id));
+
+    let client = Client::tracked(create_test_rocket(pool.clone(), registry.clone()))
+        .expect("valid rocket instance");
+
+    let response = client
+        .delete(format!("/polling?scrapper_id={}", post_id))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body["scrapper_id"], post_id);
+    assert_eq!(body["vk_id"], "-123_456");
+
+    // The window is closed in the database
+    let (_, _, _, dt_parse_end) = rt
+        .block_on(get_post_by_id(&pool, post_id))
+        .expect("Failed to get post")
+        .expect("Post should still exist");
+    assert!(dt_parse_end < now + chrono::Duration::minutes(1));
+
+    // The live job no longer owns the post
+    assert!(registry.cancel(post_id).is_none());
+
+    // Collected history is still readable
+    let response = client
+        .get(format!("/polling?scrapper_id={}", post_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
+
+#[test]
+fn test_delete_polling_keeps_end_of_finished_post() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let now = chrono::Utc::now();
+    let begin = now - chrono::Duration::hours(2);
+    let end = now - chrono::Duration::hours(1);
+    let post_id = rt
+        .block_on(insert_post(&pool, "-123_789", begin, end))
+        .expect("Failed to insert post");
+
+    let client = Client::tracked(create_test_rocket(pool, JobRegistry::default()))
+        .expect("valid rocket instance");
+
+    let response = client
+        .delete(format!("/polling?scrapper_id={}", post_id))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(
+        body["dt_parse_end"],
+        utils::format_timestamp(&end, chrono_tz::Tz::UTC)
+    );
+}
+
+#[test]
+fn test_delete_polling_stops_job_in_flight() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let now = chrono::Utc::now();
+    let (post_id, private_post_id) = rt.block_on(async {
+        let end = now + chrono::Duration::hours(1);
+        (
+            insert_post(&pool, "-1_1", now, end).await.unwrap(),
+            insert_post(&pool, "-403_403", now, end).await.unwrap(),
+        )
+    });
+
+    // The private post fails the batch after a while, the job would then poll both posts one by one
+    let vk = Arc::new(FakeVkClient::new());
+    vk.set_default_stats(Some(VkPostStats {
+        views_count: 1,
+        ..Default::default()
+    }));
+    vk.set_replies(
+        "-403_403",
+        vec![FakeReply::Error(VkError::AccessDenied(
+            "Access to post denied".to_string(),
+        ))],
+    );
+    vk.set_latency(std::time::Duration::from_millis(300));
+
+    let registry = JobRegistry::default();
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let dispatcher = Dispatcher::new(
+        pool.clone(),
+        vk.clone() as Arc<dyn VkClient>,
+        clock,
+        1,
+        registry.clone(),
+    );
+    let handles = rt.block_on(dispatcher.dispatch_due_posts()).unwrap();
+    assert_eq!(handles.len(), 1);
+
+    // Wait until the batch call is on its way
+    while vk.call_count() == 0 {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let client = Client::tracked(create_test_rocket(pool.clone(), registry.clone()))
+        .expect("valid rocket instance");
+    let response = client
+        .delete(format!("/polling?scrapper_id={}", post_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    for handle in handles {
+        assert!(rt.block_on(handle).unwrap_err().is_cancelled());
+    }
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    assert_eq!(vk.call_count(), 1, "The stopped job must not call VK again");
+
+    // The other post of the job is free for the next tick
+    assert!(registry.register(private_post_id, uuid::Uuid::new_v4()));
+}
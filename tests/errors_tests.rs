@@ -0,0 +1,60 @@
+use rocket::http::Status;
+use rstest::rstest;
+
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+
+use errors::{AppError, VkError};
+
+#[rstest]
+#[case::auth_failed(5, Status::BadGateway, "vk_auth_failed", false)]
+#[case::too_many_requests(6, Status::ServiceUnavailable, "vk_rate_limited", true)]
+#[case::flood_control(9, Status::ServiceUnavailable, "vk_rate_limited", true)]
+#[case::unknown_error(1, Status::BadGateway, "vk_internal_error", true)]
+#[case::internal_error(10, Status::BadGateway, "vk_internal_error", true)]
+#[case::private_profile(30, Status::Forbidden, "vk_access_denied", false)]
+#[case::wall_access_denied(210, Status::Forbidden, "vk_access_denied", false)]
+#[case::owner_deleted(18, Status::NotFound, "vk_post_deleted", false)]
+#[case::other(100, Status::BadGateway, "vk_error", false)]
+fn test_vk_error_classification(
+    #[case] error_code: i64,
+    #[case] expected_status: Status,
+    #[case] expected_code: &str,
+    #[case] retryable: bool,
+) {
+    let error = AppError::from(VkError::from_code(error_code, "message".to_string()));
+
+    assert_eq!(error.status(), expected_status);
+    assert_eq!(error.code(), expected_code);
+    assert_eq!(error.is_retryable(), retryable);
+}
+
+#[test]
+fn test_vk_error_keeps_unknown_code() {
+    assert_eq!(
+        VkError::from_code(
+            100,
+            "One of the parameters specified was missing".to_string()
+        ),
+        VkError::Other {
+            code: 100,
+            message: "One of the parameters specified was missing".to_string()
+        }
+    );
+}
+
+#[test]
+fn test_rate_limited_is_not_a_scheduler_error() {
+    let error = AppError::RateLimited {
+        message: "All VK tokens are cooling down".to_string(),
+        retry_after: std::time::Duration::from_millis(1500),
+    };
+
+    assert_eq!(error.status(), Status::ServiceUnavailable);
+    assert_eq!(error.code(), "rate_limited");
+    assert!(error.is_retryable());
+}
@@ -9,66 +9,86 @@ use std::sync::Arc;
 
 // Include all necessary modules for testing
 #[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
 #[path = "../src/db_commands.rs"]
 mod db_commands;
 #[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/manual_scheduler.rs"]
+mod manual_scheduler;
+#[allow(dead_code)]
 #[path = "../src/models.rs"]
 mod models;
 #[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
 #[path = "../src/tasks.rs"]
 mod tasks;
 #[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
 #[path = "../src/utils.rs"]
 mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+#[allow(dead_code)]
+#[path = "../src/vk_link.rs"]
+mod vk_link;
 
-// Mock VK API module using models::VkPostStats
-mod vk_api {
-    use crate::models::VkPostStats;
-    use rocket::response::status;
-
-    pub async fn call_vk(_post_id: &str) -> Result<VkPostStats, status::BadRequest<String>> {
-        // Simple mock - just return some data
-        Ok(VkPostStats {
-            comments_count: 0,
-            likes_count: 0,
-            views_count: 1,
-            reposts_count: 0,
-        })
-    }
-}
-
-// Include endpoints with our mocked vk_api
 #[allow(dead_code)]
 #[path = "../src/endpoints.rs"]
 mod endpoints;
 
-use endpoints::get_polling;
+use clock::{Clock, SystemClock};
+use db_commands::{save_audience, save_comments, save_post_meta};
+use endpoints::{get_polling, get_polling_audience, get_polling_comments, get_polling_meta};
+use manual_scheduler::ManualClock;
+use models::{AudienceKind, VkAudienceEntry, VkComment, VkPostMeta};
 
 mod test_utils;
 use test_utils::{insert_post, insert_post_info, setup_test_db};
 
 fn create_test_rocket(pool: sqlx::PgPool) -> rocket::Rocket<rocket::Build> {
-    let scheduler = tokio::runtime::Runtime::new().unwrap().block_on(async {
-        tokio_cron_scheduler::JobScheduler::new()
-            .await
-            .expect("Failed to create scheduler")
-    });
-
-    let scheduler = Arc::new(scheduler);
+    create_test_rocket_with_clock(pool, Arc::new(SystemClock))
+}
 
+fn create_test_rocket_with_clock(
+    pool: sqlx::PgPool,
+    clock: Arc<dyn Clock>,
+) -> rocket::Rocket<rocket::Build> {
     rocket::build()
         .manage(Arc::new(pool))
-        .manage(scheduler)
-        .mount("/", rocket::routes![get_polling])
+        .manage(clock)
+        .mount(
+            "/",
+            rocket::routes![
+                get_polling,
+                get_polling_comments,
+                get_polling_meta,
+                get_polling_audience
+            ],
+        )
+        .register("/", rocket::catchers![errors::default_catcher])
 }
 
 #[rstest]
-#[case::not_found(99999, Status::NotFound, "not found")]
-#[case::invalid_id(0, Status::NotFound, "not found")]
+#[case::not_found("99999", Status::NotFound, "not_found")]
+#[case::invalid_id("0", Status::NotFound, "not_found")]
+#[case::non_numeric_id("abc", Status::UnprocessableEntity, "validation_error")]
 fn test_get_polling_error_cases(
-    #[case] scrapper_id: i32,
+    #[case] scrapper_id: &str,
     #[case] expected_status: Status,
-    #[case] expected_body_contains: &str,
+    #[case] expected_code: &str,
 ) {
     let rt = tokio::runtime::Runtime::new().unwrap();
     let pool = rt.block_on(setup_test_db());
@@ -80,8 +100,11 @@ fn test_get_polling_error_cases(
         .dispatch();
 
     assert_eq!(response.status(), expected_status);
-    let body = response.into_string().unwrap();
-    assert!(body.contains(expected_body_contains));
+
+    // Errors from endpoints and from Rocket itself share the same JSON shape
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["error"]["code"], expected_code);
+    assert!(json["error"]["message"].is_string());
 }
 
 #[test]
@@ -91,7 +114,7 @@ fn test_get_polling_with_data() {
 
     // Insert test data
     let post_id = rt.block_on(async {
-        let now = chrono::Local::now().naive_local();
+        let now = chrono::Utc::now();
         let end_time = now + chrono::Duration::hours(1);
 
         // Insert post
@@ -172,3 +195,635 @@ fn test_get_polling_with_data() {
     assert_eq!(data[2]["reposts_count"], 6);
     assert_eq!(data[2]["views_count"], 300);
 }
+
+fn insert_hourly_data(rt: &tokio::runtime::Runtime, pool: &sqlx::PgPool) -> i32 {
+    let at = |time: &str| {
+        chrono::NaiveDateTime::parse_from_str(&format!("2026-01-01T{}", time), "%Y-%m-%dT%H:%M:%S")
+            .unwrap()
+            .and_utc()
+    };
+
+    rt.block_on(async {
+        let post_id = insert_post(pool, "-123_456", at("10:00:00"), at("13:00:00"))
+            .await
+            .expect("Failed to insert post");
+
+        // Three snapshots in the 10:00 hour and one in the 11:00 hour
+        for (likes, time) in [
+            (10, "10:00:00"),
+            (40, "10:20:00"),
+            (20, "10:40:00"),
+            (50, "11:10:00"),
+        ] {
+            insert_post_info(pool, post_id, likes, 1, 1, likes * 10, at(time))
+                .await
+                .expect("Failed to insert post_info");
+        }
+
+        post_id
+    })
+}
+
+#[rstest]
+#[case::first("first", [10, 50])]
+#[case::last("last", [20, 50])]
+#[case::max("max", [40, 50])]
+#[case::avg("avg", [23, 50])]
+fn test_get_polling_with_step_aggregation(#[case] agg: &str, #[case] expected_likes: [i64; 2]) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!(
+            "/polling?scrapper_id={}&step=PT1H&agg={}",
+            post_id, agg
+        ))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let data = json["data"].as_array().expect("data should be an array");
+
+    assert_eq!(data.len(), 2, "Should have one entry per hour");
+    assert_eq!(data[0]["info_time"], "2026-01-01T10:00:00.000+00:00");
+    assert_eq!(data[1]["info_time"], "2026-01-01T11:00:00.000+00:00");
+    assert_eq!(data[0]["likes_count"], expected_likes[0]);
+    assert_eq!(data[1]["likes_count"], expected_likes[1]);
+}
+
+#[test]
+fn test_get_polling_returns_extra_metrics() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    // The first snapshot was taken before the metric was collected
+    rt.block_on(
+        sqlx::query(
+            "UPDATE POST_INFO SET metrics = jsonb_build_object('reposts_wall', likes_count) WHERE likes_count > 10",
+        )
+        .execute(&pool),
+    )
+    .expect("Failed to update metrics");
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!("/polling?scrapper_id={}", post_id))
+        .dispatch();
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["data"][0]["metrics"], serde_json::json!({}));
+    assert_eq!(
+        json["data"][1]["metrics"],
+        serde_json::json!({"reposts_wall": 40})
+    );
+
+    // Buckets reduce extra metrics the same way as the counters
+    let response = client
+        .get(format!(
+            "/polling?scrapper_id={}&step=PT1H&agg=first",
+            post_id
+        ))
+        .dispatch();
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["data"][0]["likes_count"], 10);
+    assert_eq!(json["data"][0]["metrics"]["reposts_wall"], 40);
+    assert_eq!(json["data"][1]["metrics"]["reposts_wall"], 50);
+}
+
+#[test]
+fn test_get_polling_with_time_range() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!(
+            "/polling?scrapper_id={}&from=2026-01-01T10:20:00&to=2026-01-01T11:00:00",
+            post_id
+        ))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let data = json["data"].as_array().expect("data should be an array");
+
+    // `from` is inclusive and `to` is exclusive
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[0]["info_time"], "2026-01-01T10:20:00.000+00:00");
+    assert_eq!(data[1]["info_time"], "2026-01-01T10:40:00.000+00:00");
+}
+
+#[test]
+fn test_get_polling_uses_clock_with_milliseconds() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let now = chrono::DateTime::parse_from_rfc3339("2026-01-01T12:00:00.250Z")
+        .unwrap()
+        .to_utc();
+    let clock = Arc::new(ManualClock::new(now));
+    let client =
+        Client::tracked(create_test_rocket_with_clock(pool, clock)).expect("valid rocket instance");
+
+    // Fractional seconds are accepted in the query as well
+    let response = client
+        .get(format!(
+            "/polling?scrapper_id={}&from=2026-01-01T10:20:00.001",
+            post_id
+        ))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["dt_current"], "2026-01-01T12:00:00.250+00:00");
+    assert_eq!(
+        json["data"][0]["info_time"],
+        "2026-01-01T10:40:00.000+00:00"
+    );
+}
+
+#[test]
+fn test_get_polling_in_timezone() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    // 13:20 in Moscow is 10:20 UTC, the bounds without an offset are read in `tz`
+    let response = client
+        .get(format!(
+            "/polling?scrapper_id={}&from=2026-01-01T13:20:00&to=2026-01-01T11:00:00Z&tz=Europe/Moscow",
+            post_id
+        ))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let data = json["data"].as_array().expect("data should be an array");
+
+    assert_eq!(data.len(), 2);
+    assert_eq!(data[0]["info_time"], "2026-01-01T13:20:00.000+03:00");
+    assert_eq!(data[1]["info_time"], "2026-01-01T13:40:00.000+03:00");
+    assert!(
+        json["dt_current"].as_str().unwrap().ends_with("+03:00"),
+        "dt_current should be shown in the requested zone"
+    );
+}
+
+#[rstest]
+#[case::invalid_from("from=yesterday", "Invalid from")]
+#[case::invalid_step("step=hourly", "Invalid ISO-8601 duration")]
+#[case::zero_step("step=0", "Step must be positive")]
+#[case::invalid_agg("step=60&agg=median", "Invalid aggregation")]
+#[case::agg_without_step("agg=max", "requires step")]
+#[case::invalid_tz("tz=Moscow", "Invalid timezone Moscow")]
+fn test_get_polling_invalid_query(#[case] query: &str, #[case] expected_body_contains: &str) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!("/polling?scrapper_id={}&{}", post_id, query))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let body = response.into_string().unwrap();
+    assert!(
+        body.contains(expected_body_contains),
+        "Expected body to contain '{}', but got: {}",
+        expected_body_contains,
+        body
+    );
+}
+
+fn post_meta(text: &str, edited_at: Option<&str>) -> VkPostMeta {
+    let at = |value: &str| {
+        chrono::DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .to_utc()
+    };
+
+    VkPostMeta {
+        owner_id: -123,
+        from_id: 42,
+        published_at: at("2026-01-01T09:00:00Z"),
+        post_type: "post".to_string(),
+        text: text.to_string(),
+        attachments: serde_json::json!([{"type": "photo"}]),
+        edited_at: edited_at.map(at),
+        is_deleted: false,
+    }
+}
+
+#[test]
+fn test_get_polling_meta_versions() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    rt.block_on(async {
+        let now = chrono::Utc::now();
+        let original = post_meta("Original", None);
+        let edited = post_meta("Edited", Some("2026-01-01T11:30:00Z"));
+
+        assert!(
+            save_post_meta(&pool, post_id, &original, now)
+                .await
+                .unwrap()
+        );
+        // The same content is not stored twice
+        assert!(
+            !save_post_meta(&pool, post_id, &original, now)
+                .await
+                .unwrap()
+        );
+        assert!(save_post_meta(&pool, post_id, &edited, now).await.unwrap());
+    });
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!("/polling/meta?scrapper_id={}", post_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let versions = json["versions"]
+        .as_array()
+        .expect("versions should be an array");
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0]["version"], 1);
+    assert_eq!(versions[0]["text"], "Original");
+    assert_eq!(versions[0]["edited_at"], Value::Null);
+    assert_eq!(versions[1]["version"], 2);
+    assert_eq!(versions[1]["text"], "Edited");
+    assert_eq!(versions[1]["edited_at"], "2026-01-01T11:30:00.000+00:00");
+    assert_eq!(versions[1]["attachments"][0]["type"], "photo");
+
+    // The stats come with the latest version only
+    let response = client
+        .get(format!("/polling?scrapper_id={}&tz=Europe/Moscow", post_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["meta"]["version"], 2);
+    assert_eq!(json["meta"]["from_id"], 42);
+    assert_eq!(
+        json["meta"]["published_at"],
+        "2026-01-01T12:00:00.000+03:00"
+    );
+}
+
+#[test]
+fn test_get_polling_meta_not_polled_yet() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!("/polling?scrapper_id={}", post_id))
+        .dispatch();
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["meta"], Value::Null);
+
+    let response = client
+        .get(format!("/polling/meta?scrapper_id={}", post_id))
+        .dispatch();
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["versions"], serde_json::json!([]));
+
+    let response = client.get("/polling/meta?scrapper_id=99999").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_get_polling_comments_since() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let at = |value: &str| {
+        chrono::DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .to_utc()
+    };
+    let comment = |id: i64, parent_id: Option<i64>| VkComment {
+        id,
+        from_id: 42,
+        published_at: at("2026-01-01T10:05:00Z"),
+        text: format!("Comment {}", id),
+        likes_count: id,
+        parent_id,
+        thread_count: 0,
+    };
+
+    rt.block_on(async {
+        save_comments(
+            &pool,
+            post_id,
+            &[comment(1, None)],
+            at("2026-01-01T10:10:00Z"),
+        )
+        .await
+        .unwrap();
+        save_comments(
+            &pool,
+            post_id,
+            &[comment(1, None), comment(2, Some(1))],
+            at("2026-01-01T10:20:00Z"),
+        )
+        .await
+        .unwrap();
+    });
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!("/polling/comments?scrapper_id={}", post_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let comments = json["comments"]
+        .as_array()
+        .expect("comments should be an array");
+    assert_eq!(comments.len(), 2);
+    // A comment seen again keeps the time it was first collected
+    assert_eq!(
+        comments[0]["first_seen_at"],
+        "2026-01-01T10:10:00.000+00:00"
+    );
+    assert_eq!(comments[1]["parent_id"], 1);
+    assert_eq!(comments[1]["likes_count"], 2);
+
+    let response = client
+        .get(format!(
+            "/polling/comments?scrapper_id={}&since=2026-01-01T10:15:00Z",
+            post_id
+        ))
+        .dispatch();
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let comments = json["comments"]
+        .as_array()
+        .expect("comments should be an array");
+    assert_eq!(comments.len(), 1);
+    assert_eq!(comments[0]["comment_id"], 2);
+    assert_eq!(comments[0]["text"], "Comment 2");
+
+    let response = client.get("/polling/comments?scrapper_id=99999").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_get_polling_comments_cursor_follows_commit_order() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let at = |value: &str| {
+        chrono::DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .to_utc()
+    };
+    let comment = |id: i64| VkComment {
+        id,
+        from_id: 42,
+        published_at: at("2026-01-01T10:05:00Z"),
+        text: format!("Comment {}", id),
+        likes_count: 0,
+        parent_id: None,
+        thread_count: 0,
+    };
+    let save = |comments: &[VkComment], now: &str| {
+        rt.block_on(save_comments(&pool, post_id, comments, at(now)))
+            .unwrap();
+    };
+
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+    let read = |cursor: Option<i64>| {
+        let uri = match cursor {
+            Some(cursor) => format!(
+                "/polling/comments?scrapper_id={}&cursor={}",
+                post_id, cursor
+            ),
+            None => format!("/polling/comments?scrapper_id={}", post_id),
+        };
+        let json: Value =
+            serde_json::from_str(&client.get(uri).dispatch().into_string().unwrap()).unwrap();
+        let ids: Vec<i64> = json["comments"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|comment| comment["comment_id"].as_i64().unwrap())
+            .collect();
+        (ids, json["next_cursor"].as_i64().unwrap())
+    };
+
+    save(&[comment(1)], "2026-01-01T10:20:00Z");
+    let (ids, cursor) = read(None);
+    assert_eq!(ids, vec![1]);
+
+    // Stamped before the first read by a collection that committed after it
+    save(&[comment(1), comment(2)], "2026-01-01T10:10:00Z");
+    let (ids, cursor) = read(Some(cursor));
+    assert_eq!(ids, vec![2]);
+
+    // Refreshing known comments does not return them again
+    save(&[comment(1), comment(2)], "2026-01-01T10:30:00Z");
+    let (ids, next_cursor) = read(Some(cursor));
+    assert!(ids.is_empty());
+    assert_eq!(next_cursor, cursor);
+}
+
+#[test]
+fn test_get_polling_audience_by_kind_and_since() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let at = |value: &str| {
+        chrono::DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .to_utc()
+    };
+    let liker = |user_id: i64| VkAudienceEntry {
+        user_id,
+        engaged_at: None,
+    };
+
+    rt.block_on(async {
+        save_audience(
+            &pool,
+            post_id,
+            AudienceKind::Like,
+            &[liker(1)],
+            at("2026-01-01T10:10:00Z"),
+        )
+        .await
+        .unwrap();
+        save_audience(
+            &pool,
+            post_id,
+            AudienceKind::Like,
+            &[liker(1), liker(2)],
+            at("2026-01-01T10:20:00Z"),
+        )
+        .await
+        .unwrap();
+        save_audience(
+            &pool,
+            post_id,
+            AudienceKind::Repost,
+            &[VkAudienceEntry {
+                user_id: 3,
+                engaged_at: Some(at("2026-01-01T10:12:00Z")),
+            }],
+            at("2026-01-01T10:20:00Z"),
+        )
+        .await
+        .unwrap();
+    });
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .get(format!("/polling/audience?scrapper_id={}", post_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let audience = json["audience"]
+        .as_array()
+        .expect("audience should be an array");
+    assert_eq!(audience.len(), 3);
+    // A user seen again keeps the time they were first found
+    assert_eq!(audience[0]["user_id"], 1);
+    assert_eq!(
+        audience[0]["first_seen_at"],
+        "2026-01-01T10:10:00.000+00:00"
+    );
+    assert_eq!(audience[0]["last_seen_at"], "2026-01-01T10:20:00.000+00:00");
+    assert_eq!(audience[0]["engaged_at"], Value::Null);
+
+    let response = client
+        .get(format!(
+            "/polling/audience?scrapper_id={}&kind=repost",
+            post_id
+        ))
+        .dispatch();
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let audience = json["audience"]
+        .as_array()
+        .expect("audience should be an array");
+    assert_eq!(audience.len(), 1);
+    assert_eq!(audience[0]["user_id"], 3);
+    assert_eq!(audience[0]["kind"], "repost");
+    assert_eq!(audience[0]["engaged_at"], "2026-01-01T10:12:00.000+00:00");
+
+    let response = client
+        .get(format!(
+            "/polling/audience?scrapper_id={}&kind=like&since=2026-01-01T10:15:00Z",
+            post_id
+        ))
+        .dispatch();
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let audience = json["audience"]
+        .as_array()
+        .expect("audience should be an array");
+    assert_eq!(audience.len(), 1);
+    assert_eq!(audience[0]["user_id"], 2);
+
+    let response = client
+        .get(format!(
+            "/polling/audience?scrapper_id={}&kind=comment",
+            post_id
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let response = client.get("/polling/audience?scrapper_id=99999").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_get_polling_audience_cursor_follows_commit_order() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let post_id = insert_hourly_data(&rt, &pool);
+
+    let at = |value: &str| {
+        chrono::DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .to_utc()
+    };
+    let save = |user_ids: &[i64], now: &str| {
+        let entries: Vec<VkAudienceEntry> = user_ids
+            .iter()
+            .map(|&user_id| VkAudienceEntry {
+                user_id,
+                engaged_at: None,
+            })
+            .collect();
+        rt.block_on(save_audience(
+            &pool,
+            post_id,
+            AudienceKind::Like,
+            &entries,
+            at(now),
+        ))
+        .unwrap();
+    };
+
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+    let read = |cursor: Option<i64>| {
+        let uri = match cursor {
+            Some(cursor) => format!(
+                "/polling/audience?scrapper_id={}&cursor={}",
+                post_id, cursor
+            ),
+            None => format!("/polling/audience?scrapper_id={}", post_id),
+        };
+        let json: Value =
+            serde_json::from_str(&client.get(uri).dispatch().into_string().unwrap()).unwrap();
+        let user_ids: Vec<i64> = json["audience"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["user_id"].as_i64().unwrap())
+            .collect();
+        (user_ids, json["next_cursor"].as_i64().unwrap())
+    };
+
+    save(&[1], "2026-01-01T10:20:00Z");
+    let (user_ids, cursor) = read(None);
+    assert_eq!(user_ids, vec![1]);
+
+    // Stamped before the first read by a collection that committed after it
+    save(&[1, 2], "2026-01-01T10:10:00Z");
+    let (user_ids, cursor) = read(Some(cursor));
+    assert_eq!(user_ids, vec![2]);
+
+    // Users seen again are not returned again
+    save(&[1, 2], "2026-01-01T10:30:00Z");
+    let (user_ids, next_cursor) = read(Some(cursor));
+    assert!(user_ids.is_empty());
+    assert_eq!(next_cursor, cursor);
+}
@@ -0,0 +1,314 @@
+#[macro_use]
+extern crate rocket;
+
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+use rstest::rstest;
+use serde_json::Value;
+use std::sync::Arc;
+
+// Include all necessary modules for testing
+#[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
+#[path = "../src/db_commands.rs"]
+mod db_commands;
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/manual_scheduler.rs"]
+mod manual_scheduler;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
+#[path = "../src/tasks.rs"]
+mod tasks;
+#[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+#[allow(dead_code)]
+#[path = "../src/vk_link.rs"]
+mod vk_link;
+
+#[allow(dead_code)]
+#[path = "../src/endpoints.rs"]
+mod endpoints;
+
+use clock::{Clock, SystemClock};
+use endpoints::{list_posts, vk_tokens};
+use manual_scheduler::OffsetClock;
+
+mod test_utils;
+use test_utils::{insert_post, insert_post_info, setup_test_db};
+
+fn create_test_rocket(pool: sqlx::PgPool) -> rocket::Rocket<rocket::Build> {
+    create_test_rocket_with_clock(pool, Arc::new(SystemClock))
+}
+
+fn create_test_rocket_with_clock(
+    pool: sqlx::PgPool,
+    clock: Arc<dyn Clock>,
+) -> rocket::Rocket<rocket::Build> {
+    rocket::build()
+        .manage(Arc::new(pool))
+        .manage(clock)
+        .mount("/", rocket::routes![list_posts, vk_tokens])
+}
+
+// Creates an active, a finished and a paused post for two owners
+fn setup_posts(rt: &tokio::runtime::Runtime, pool: &sqlx::PgPool) -> (i32, i32, i32) {
+    rt.block_on(async {
+        let now = chrono::Utc::now();
+
+        let active_id = insert_post(pool, "-1_10", now, now + chrono::Duration::hours(1))
+            .await
+            .expect("Failed to insert active post");
+        insert_post_info(
+            pool,
+            active_id,
+            10,
+            5,
+            2,
+            100,
+            now - chrono::Duration::minutes(1),
+        )
+        .await
+        .expect("Failed to insert post_info");
+        insert_post_info(pool, active_id, 20, 10, 4, 200, now)
+            .await
+            .expect("Failed to insert post_info");
+
+        let finished_id = insert_post(
+            pool,
+            "-1_20",
+            now - chrono::Duration::days(2),
+            now - chrono::Duration::days(1),
+        )
+        .await
+        .expect("Failed to insert finished post");
+
+        let paused_id = insert_post(pool, "-2_30", now, now + chrono::Duration::hours(1))
+            .await
+            .expect("Failed to insert paused post");
+        sqlx::query("UPDATE POST SET paused_at = CURRENT_TIMESTAMP WHERE id = $1")
+            .bind(paused_id)
+            .execute(pool)
+            .await
+            .expect("Failed to pause post");
+
+        (active_id, finished_id, paused_id)
+    })
+}
+
+fn get_ids(body: &Value) -> Vec<i64> {
+    body["posts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|post| post["scrapper_id"].as_i64().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_list_posts_returns_status_and_latest_snapshot() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let (active_id, finished_id, paused_id) = setup_posts(&rt, &pool);
+    rt.block_on(db_commands::save_poll_skips(
+        &pool,
+        &[active_id],
+        "No free VK request slot",
+        SystemClock.now(),
+    ))
+    .expect("Failed to save poll skip");
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client.get("/posts").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let posts = body["posts"].as_array().unwrap();
+
+    // Newest first
+    assert_eq!(
+        get_ids(&body),
+        vec![paused_id as i64, finished_id as i64, active_id as i64]
+    );
+    assert!(body["next_cursor"].is_null());
+
+    assert_eq!(posts[0]["status"], "paused");
+    assert_eq!(posts[1]["status"], "finished");
+    assert_eq!(posts[2]["status"], "active");
+
+    assert_eq!(posts[2]["owner_id"], "-1");
+    assert_eq!(posts[2]["snapshot_count"], 2);
+    assert_eq!(posts[2]["skipped_count"], 1);
+    assert_eq!(posts[2]["latest_snapshot"]["likes_count"], 20);
+    assert_eq!(posts[2]["latest_snapshot"]["views_count"], 200);
+
+    assert_eq!(posts[1]["snapshot_count"], 0);
+    assert_eq!(posts[1]["skipped_count"], 0);
+    assert!(posts[1]["latest_snapshot"].is_null());
+}
+
+#[test]
+fn test_list_posts_status_follows_clock() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let (active_id, _, paused_id) = setup_posts(&rt, &pool);
+
+    // A day later the active post's window is over, the paused one stays paused
+    let clock = Arc::new(OffsetClock::new(chrono::Duration::days(1)));
+    let client =
+        Client::tracked(create_test_rocket_with_clock(pool, clock)).expect("valid rocket instance");
+
+    let response = client.get("/posts").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let posts = body["posts"].as_array().unwrap();
+
+    assert_eq!(posts[0]["scrapper_id"], paused_id);
+    assert_eq!(posts[0]["status"], "paused");
+    assert_eq!(posts[2]["scrapper_id"], active_id);
+    assert_eq!(posts[2]["status"], "finished");
+}
+
+#[rstest]
+#[case::by_vk_id("vk_id=-1_20", vec![1])]
+#[case::by_vk_link("vk_id=https%3A%2F%2Fvk.com%2Fwall-1_20", vec![1])]
+#[case::by_owner("owner=-1", vec![1, 0])]
+#[case::by_other_owner("owner=-2", vec![2])]
+#[case::unknown_owner("owner=-3", vec![])]
+#[case::by_community_name("owner=club1", vec![1, 0])]
+#[case::created_from("created_from=2000-01-01T00:00:00", vec![2, 1, 0])]
+#[case::created_to("created_to=2000-01-01T00:00:00", vec![])]
+fn test_list_posts_filters(#[case] query: &str, #[case] expected: Vec<usize>) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let (active_id, finished_id, paused_id) = setup_posts(&rt, &pool);
+    let ids = [active_id as i64, finished_id as i64, paused_id as i64];
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client.get(format!("/posts?{}", query)).dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let expected: Vec<i64> = expected.into_iter().map(|i| ids[i]).collect();
+    assert_eq!(get_ids(&body), expected);
+}
+
+#[test]
+fn test_list_posts_active_at() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let (_, finished_id, _) = setup_posts(&rt, &pool);
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let active_at = (chrono::Utc::now() - chrono::Duration::hours(36))
+        .format("%Y-%m-%dT%H:%M:%S")
+        .to_string();
+    let response = client
+        .get(format!("/posts?active_at={}", active_at))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(get_ids(&body), vec![finished_id as i64]);
+}
+
+#[test]
+fn test_list_posts_cursor_pagination() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let (active_id, finished_id, paused_id) = setup_posts(&rt, &pool);
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client.get("/posts?limit=2").dispatch();
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(get_ids(&body), vec![paused_id as i64, finished_id as i64]);
+    assert_eq!(body["next_cursor"], finished_id);
+
+    let response = client
+        .get(format!("/posts?limit=2&cursor={}", finished_id))
+        .dispatch();
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(get_ids(&body), vec![active_id as i64]);
+    assert!(body["next_cursor"].is_null());
+}
+
+#[rstest]
+#[case::garbage("owner=abc")]
+#[case::post_id("owner=-1_20")]
+fn test_list_posts_invalid_owner(#[case] query: &str) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client.get(format!("/posts?{}", query)).dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body["error"]["code"], "validation_error");
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("Invalid VK owner")
+    );
+}
+
+#[test]
+fn test_list_posts_invalid_time() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client.get("/posts?active_at=yesterday").dispatch();
+    assert_eq!(response.status(), Status::BadRequest);
+    assert!(
+        response
+            .into_string()
+            .unwrap()
+            .contains("Invalid active_at")
+    );
+}
+
+#[test]
+fn test_vk_tokens_reports_pool_state() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client.get("/admin/vk_tokens").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body["rotation"], "round_robin");
+    assert_eq!(body["queue_depth"], 0);
+
+    // Tokens are never shown in full
+    for token in body["tokens"].as_array().unwrap() {
+        assert!(token["token"].as_str().unwrap().ends_with("..."));
+        assert!(token["rate_per_second"].as_f64().unwrap() > 0.0);
+    }
+}
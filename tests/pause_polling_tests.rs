@@ -0,0 +1,335 @@
+#[macro_use]
+extern crate rocket;
+
+use rocket::http::Status;
+use rocket::local::blocking::Client;
+use serde_json::Value;
+use std::sync::Arc;
+
+// Include all necessary modules for testing
+#[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
+#[path = "../src/db_commands.rs"]
+mod db_commands;
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/fake_vk_client.rs"]
+mod fake_vk_client;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
+#[path = "../src/tasks.rs"]
+mod tasks;
+#[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+#[allow(dead_code)]
+#[path = "../src/vk_link.rs"]
+mod vk_link;
+
+#[allow(dead_code)]
+#[path = "../src/endpoints.rs"]
+mod endpoints;
+
+use clock::{Clock, SystemClock};
+use endpoints::{pause_polling, resume_polling};
+use fake_vk_client::FakeVkClient;
+use tasks::{Dispatcher, JobRegistry};
+
+mod test_utils;
+use test_utils::{get_post_by_id, insert_post, setup_test_db};
+
+fn create_test_rocket(pool: sqlx::PgPool) -> rocket::Rocket<rocket::Build> {
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    rocket::build()
+        .manage(Arc::new(pool))
+        .manage(clock)
+        .mount("/", rocket::routes![pause_polling, resume_polling])
+}
+
+fn parse_datetime(value: &Value) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::parse_from_rfc3339(value.as_str().unwrap())
+        .unwrap()
+        .to_utc()
+}
+
+#[test]
+fn test_pause_polling_not_found() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client.post("/polling/pause?scrapper_id=99999").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client.post("/polling/resume?scrapper_id=99999").dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+}
+
+#[test]
+fn test_pause_polling_finished_post() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let now = chrono::Utc::now();
+    let post_id = rt
+        .block_on(insert_post(
+            &pool,
+            "-123_456",
+            now - chrono::Duration::hours(2),
+            now - chrono::Duration::hours(1),
+        ))
+        .expect("Failed to insert post");
+
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let response = client
+        .post(format!("/polling/pause?scrapper_id={}", post_id))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Conflict);
+
+    let json: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["error"]["code"], "conflict");
+    assert!(
+        json["error"]["message"]
+            .as_str()
+            .unwrap()
+            .contains("already finished")
+    );
+}
+
+#[test]
+fn test_paused_post_is_not_polled() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let now = chrono::Utc::now();
+    let post_id = rt
+        .block_on(insert_post(
+            &pool,
+            "-123_456",
+            now,
+            now + chrono::Duration::hours(1),
+        ))
+        .expect("Failed to insert post");
+
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+
+    let response = client
+        .post(format!("/polling/pause?scrapper_id={}", post_id))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert!(body["paused_at"].is_string());
+
+    // Neither the dispatcher nor a poll that was already running saves stats for the paused post
+    let vk = Arc::new(FakeVkClient::new());
+    let handles = rt.block_on(async {
+        Dispatcher::new(
+            pool.clone(),
+            vk.clone(),
+            Arc::new(SystemClock),
+            1,
+            JobRegistry::default(),
+        )
+        .dispatch_due_posts()
+        .await
+        .expect("Failed to dispatch due posts")
+    });
+    assert!(handles.is_empty(), "Paused post should not be dispatched");
+
+    rt.block_on(tasks::poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[(post_id, "-123_456".to_string())],
+    ))
+    .expect("poll_post_stats should succeed");
+
+    let count: i64 = rt.block_on(async {
+        sqlx::query_scalar("SELECT COUNT(*) FROM POST_INFO WHERE post_id = $1")
+            .bind(post_id)
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to query POST_INFO")
+    });
+    assert_eq!(count, 0, "Should NOT have saved stats for paused post");
+}
+
+#[test]
+fn test_resume_polling_restores_remaining_window() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let now = chrono::Utc::now();
+    let post_id = rt
+        .block_on(insert_post(
+            &pool,
+            "-123_456",
+            now - chrono::Duration::hours(1),
+            now + chrono::Duration::hours(1),
+        ))
+        .expect("Failed to insert post");
+
+    // The post was paused 30 minutes ago with 90 minutes of window left
+    rt.block_on(async {
+        sqlx::query(
+            "UPDATE POST SET paused_at = CURRENT_TIMESTAMP - INTERVAL '30 minutes' WHERE id = $1",
+        )
+        .bind(post_id)
+        .execute(&pool)
+        .await
+        .expect("Failed to pause post");
+    });
+
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+
+    let response = client
+        .post(format!("/polling/resume?scrapper_id={}", post_id))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert!(body["paused_at"].is_null());
+
+    let remaining = parse_datetime(&body["dt_parse_end"]) - now;
+    assert!(
+        (remaining - chrono::Duration::minutes(90))
+            .num_seconds()
+            .abs()
+            <= 5,
+        "Remaining window should be restored to 90 minutes, got {}",
+        remaining
+    );
+
+    // Resuming again changes nothing
+    let response = client
+        .post(format!("/polling/resume?scrapper_id={}", post_id))
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+    let body_again: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body_again["dt_parse_end"], body["dt_parse_end"]);
+}
+
+#[test]
+fn test_resume_polling_without_extend_keeps_end() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let now = chrono::Utc::now();
+    let post_id = rt
+        .block_on(insert_post(
+            &pool,
+            "-123_456",
+            now - chrono::Duration::hours(1),
+            now + chrono::Duration::hours(1),
+        ))
+        .expect("Failed to insert post");
+
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+
+    let response = client
+        .post(format!("/polling/pause?scrapper_id={}", post_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let (_, _, _, end_before) = rt
+        .block_on(get_post_by_id(&pool, post_id))
+        .expect("Failed to get post")
+        .expect("Post should exist");
+
+    let response = client
+        .post(format!(
+            "/polling/resume?scrapper_id={}&extend=false",
+            post_id
+        ))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let (_, _, _, end_after) = rt
+        .block_on(get_post_by_id(&pool, post_id))
+        .expect("Failed to get post")
+        .expect("Post should exist");
+
+    assert_eq!(end_before, end_after);
+}
+
+#[test]
+fn test_prolong_resumes_paused_post() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    // Postgres keeps microseconds
+    let now = chrono::SubsecRound::trunc_subsecs(chrono::Utc::now(), 6);
+    let post_id = rt
+        .block_on(insert_post(
+            &pool,
+            "-123_456",
+            now,
+            now + chrono::Duration::hours(1),
+        ))
+        .expect("Failed to insert post");
+
+    let paused_at = now + chrono::Duration::minutes(10);
+    let prolonged_at = now + chrono::Duration::minutes(20);
+    let (prolonged, resumed) = rt.block_on(async {
+        db_commands::pause_post(&pool, post_id, paused_at)
+            .await
+            .unwrap()
+            .expect("Post should be paused");
+        let prolonged = db_commands::get_or_create_post_with_prolong(
+            &pool,
+            "-123_456",
+            true,
+            3600,
+            false,
+            prolonged_at,
+        )
+        .await
+        .unwrap();
+        let resumed = db_commands::resume_post(
+            &pool,
+            post_id,
+            true,
+            prolonged_at + chrono::Duration::minutes(5),
+        )
+        .await
+        .unwrap();
+        (prolonged, resumed)
+    });
+
+    // The window ends one duration after the prolong and is not shifted by the pause later
+    assert_eq!(prolonged.id, post_id);
+    assert!(prolonged.paused_at.is_none());
+    assert_eq!(
+        prolonged.dt_parse_end,
+        prolonged_at + chrono::Duration::hours(1)
+    );
+    assert!(resumed.is_none(), "A prolonged post is no longer paused");
+
+    let (_, _, _, dt_parse_end) = rt
+        .block_on(get_post_by_id(&pool, post_id))
+        .unwrap()
+        .unwrap();
+    assert_eq!(dt_parse_end, prolonged_at + chrono::Duration::hours(1));
+}
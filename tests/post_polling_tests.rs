@@ -1,7 +1,7 @@
 #[macro_use]
 extern crate rocket;
 
-use rocket::http::{ContentType, Status};
+use rocket::http::{ContentType, Header, Status};
 use rocket::local::blocking::Client;
 use rstest::rstest;
 use serde_json::json;
@@ -10,87 +10,119 @@ use std::sync::Arc;
 
 // Include all necessary modules for testing
 #[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
 #[path = "../src/db_commands.rs"]
 mod db_commands;
 #[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/fake_vk_client.rs"]
+mod fake_vk_client;
+#[allow(dead_code)]
+#[path = "../src/manual_scheduler.rs"]
+mod manual_scheduler;
+#[allow(dead_code)]
 #[path = "../src/models.rs"]
 mod models;
 #[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
 #[path = "../src/tasks.rs"]
 mod tasks;
 #[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
 #[path = "../src/utils.rs"]
 mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+#[allow(dead_code)]
+#[path = "../src/vk_link.rs"]
+mod vk_link;
 
-// Mock VK API module using models::VkPostStats
-mod vk_api {
-    use crate::models::VkPostStats;
-    use rocket::response::status;
-    use std::sync::atomic::Ordering;
-
-    // Counter for tracking calls and generating different responses
-    static CALL_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-
-    pub async fn call_vk(post_id: &str) -> Result<VkPostStats, status::BadRequest<String>> {
-        let count = CALL_COUNTER.fetch_add(1, Ordering::SeqCst);
-
-        // Simulate different responses based on post_id
-        if post_id.contains("999_999") {
-            // Empty stats - post not found
-            Ok(VkPostStats {
-                comments_count: 0,
-                likes_count: 0,
-                views_count: 0,
-                reposts_count: 0,
-            })
-        } else if count == 0 {
-            // Initial call: views=1, others=0
-            Ok(VkPostStats {
-                comments_count: 0,
-                likes_count: 0,
-                views_count: 1,
-                reposts_count: 0,
-            })
-        } else {
-            // Subsequent calls: views > likes > comments > reposts
-            let base = count + 1;
-            Ok(VkPostStats {
-                comments_count: (base * 2) as u64,
-                likes_count: (base * 3) as u64,
-                views_count: (base * 4) as u64,
-                reposts_count: base as u64,
-            })
-        }
-    }
-
-    pub fn reset_counter() {
-        CALL_COUNTER.store(0, Ordering::SeqCst);
-    }
-}
-
-// Include endpoints with our mocked vk_api
+#[allow(dead_code)]
 #[path = "../src/endpoints.rs"]
 mod endpoints;
 
-use endpoints::{get_polling, post_polling};
+use clock::{Clock, SystemClock};
+use endpoints::{get_polling, post_polling, post_polling_batch};
+use errors::VkError;
+use fake_vk_client::{FakeReply, FakeVkClient};
+use manual_scheduler::{ManualClock, ManualScheduler};
+use models::VkPostStats;
+use vk_api::VkClient;
 
 mod test_utils;
 use test_utils::setup_test_db;
 
-fn create_test_rocket(pool: sqlx::PgPool) -> rocket::Rocket<rocket::Build> {
-    // Create JobScheduler but DON'T start it - jobs won't execute
-    let scheduler = tokio::runtime::Runtime::new().unwrap().block_on(async {
-        tokio_cron_scheduler::JobScheduler::new()
-            .await
-            .expect("Failed to create scheduler")
-    });
+fn stats(views_count: u64) -> VkPostStats {
+    VkPostStats {
+        comments_count: 0,
+        likes_count: 0,
+        views_count,
+        reposts_count: 0,
+        ..Default::default()
+    }
+}
 
-    let scheduler = Arc::new(scheduler);
+// Posts have one view, except the ones scripted to be missing or to fail
+fn fake_vk() -> Arc<dyn VkClient> {
+    let vk = FakeVkClient::new();
+    vk.set_default_stats(Some(stats(1)));
+    // Empty stats - post not found
+    vk.set_stats("-999_999", stats(0));
+    vk.set_replies(
+        "-403_403",
+        vec![FakeReply::Error(VkError::AccessDenied(
+            "Access to post denied".to_string(),
+        ))],
+    );
+    vk.set_replies(
+        "-429_429",
+        vec![FakeReply::Error(VkError::TooManyRequests(
+            "Too many requests per second".to_string(),
+        ))],
+    );
+    vk.set_replies(
+        "-408_408",
+        vec![FakeReply::Throttled(
+            "No free VK request slot within 2 seconds".to_string(),
+        )],
+    );
+    vk.set_replies(
+        "-401_401",
+        vec![FakeReply::Error(VkError::AuthFailed(
+            "User authorization failed".to_string(),
+        ))],
+    );
+    Arc::new(vk)
+}
+
+fn create_test_rocket(pool: sqlx::PgPool) -> rocket::Rocket<rocket::Build> {
+    create_test_rocket_with_clock(pool, Arc::new(SystemClock))
+}
 
+fn create_test_rocket_with_clock(
+    pool: sqlx::PgPool,
+    clock: Arc<dyn Clock>,
+) -> rocket::Rocket<rocket::Build> {
     rocket::build()
         .manage(Arc::new(pool))
-        .manage(scheduler)
-        .mount("/", rocket::routes![post_polling, get_polling])
+        .manage(clock)
+        .manage(fake_vk())
+        .mount(
+            "/",
+            rocket::routes![post_polling, post_polling_batch, get_polling],
+        )
 }
 
 #[rstest]
@@ -172,8 +204,6 @@ fn test_post_polling_missing_fields() {
 
 #[test]
 fn test_post_polling_post_not_found_in_vk() {
-    vk_api::reset_counter();
-
     let rt = tokio::runtime::Runtime::new().unwrap();
     let pool = rt.block_on(setup_test_db());
     let rocket = create_test_rocket(pool);
@@ -191,21 +221,106 @@ fn test_post_polling_post_not_found_in_vk() {
         )
         .dispatch();
 
-    // Should return BadRequest because post stats are empty (all zeros)
-    assert_eq!(response.status(), Status::BadRequest);
+    // Should return NotFound because post stats are empty (all zeros)
+    assert_eq!(response.status(), Status::NotFound);
     let body = response.into_string().unwrap();
     assert!(body.contains("Post not found in VK"));
+    assert!(body.contains("\"code\":\"not_found\""));
+}
+
+#[rstest]
+#[case::access_denied("-403_403", Status::Forbidden, "vk_access_denied")]
+#[case::rate_limited("-429_429", Status::ServiceUnavailable, "vk_rate_limited")]
+#[case::auth_failed("-401_401", Status::BadGateway, "vk_auth_failed")]
+#[case::throttled("-408_408", Status::ServiceUnavailable, "rate_limited")]
+fn test_post_polling_vk_errors(
+    #[case] vk_id: &str,
+    #[case] expected_status: Status,
+    #[case] expected_code: &str,
+) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+
+    let response = client
+        .post("/polling")
+        .header(ContentType::JSON)
+        .body(
+            json!({
+                "vk_link": format!("https://vk.com/wall{}", vk_id),
+                "prolong": false
+            })
+            .to_string(),
+        )
+        .dispatch();
+
+    assert_eq!(response.status(), expected_status);
+    // Only throttling on our side knows when a slot is free again
+    let retry_after = response
+        .headers()
+        .get_one("Retry-After")
+        .map(str::to_string);
+    assert_eq!(retry_after.is_some(), expected_code == "rate_limited");
+
+    let json: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(json["error"]["code"], expected_code);
+
+    // Nothing is scheduled for a post VK refused to return
+    let count: i64 = rt
+        .block_on(sqlx::query_scalar("SELECT COUNT(*) FROM POST").fetch_one(&pool))
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[test]
+fn test_post_polling_accepts_any_link_to_the_post() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let rocket = create_test_rocket(pool);
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+
+    let links = [
+        "https://vk.com/wall-1_1",
+        "http://m.vk.ru/wall-1_1?reply=3#comments",
+        "https://vk.com/feed?w=wall-1_1",
+        "-1_1",
+    ];
+
+    // Every link names the same post, so they all end up in one task
+    let tasks: Vec<(i64, String)> = links
+        .iter()
+        .map(|link| {
+            let response = client
+                .post("/polling")
+                .header(ContentType::JSON)
+                .body(json!({"vk_link": link, "prolong": false}).to_string())
+                .dispatch();
+            assert_eq!(response.status(), Status::Ok, "{} should be accepted", link);
+
+            let body: serde_json::Value =
+                serde_json::from_str(&response.into_string().unwrap()).unwrap();
+            (
+                body["scrapper_id"].as_i64().unwrap(),
+                body["vk_id"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+
+    assert!(
+        tasks
+            .iter()
+            .all(|task| *task == (tasks[0].0, "-1_1".to_string()))
+    );
 }
 
 #[rstest]
 #[case::without_prolong(false)]
 #[case::with_prolong(true)]
 fn test_post_polling_success_with_mock(#[case] prolong: bool) {
-    vk_api::reset_counter();
-
     let rt = tokio::runtime::Runtime::new().unwrap();
     let pool = rt.block_on(setup_test_db());
-    let rocket = create_test_rocket(pool);
+    let clock = Arc::new(ManualClock::new(SystemClock.now()));
+    let rocket = create_test_rocket_with_clock(pool, clock.clone());
     let client = Client::tracked(rocket).expect("valid rocket instance");
 
     // First call
@@ -233,9 +348,8 @@ fn test_post_polling_success_with_mock(#[case] prolong: bool) {
     let scrapper_id = body1["scrapper_id"].as_i64().unwrap();
     let dt_parse_end_1 = body1["dt_parse_end"].as_str().unwrap().to_string();
 
-    // Add a small delay to ensure timestamp changes
-    // TODO in prod fixing using millisconds
-    std::thread::sleep(std::time::Duration::from_secs(1));
+    // A millisecond later is enough for the end to change
+    clock.advance(chrono::Duration::milliseconds(1));
 
     // Second call with the same vk_link
     let response2 = client
@@ -268,10 +382,8 @@ fn test_post_polling_success_with_mock(#[case] prolong: bool) {
         );
 
         // Parse dates to verify second end time is later
-        let end1 =
-            chrono::NaiveDateTime::parse_from_str(&dt_parse_end_1, "%Y-%m-%dT%H:%M:%S").unwrap();
-        let end2 =
-            chrono::NaiveDateTime::parse_from_str(&dt_parse_end_2, "%Y-%m-%dT%H:%M:%S").unwrap();
+        let end1 = chrono::DateTime::parse_from_rfc3339(&dt_parse_end_1).unwrap();
+        let end2 = chrono::DateTime::parse_from_rfc3339(&dt_parse_end_2).unwrap();
         assert!(
             end2 > end1,
             "Second dt_parse_end should be later than first when prolong=true"
@@ -285,185 +397,602 @@ fn test_post_polling_success_with_mock(#[case] prolong: bool) {
     }
 }
 
+#[rstest]
+#[case::seconds(json!(3600), 3600)]
+#[case::iso8601(json!("PT6H"), 6 * 60 * 60)]
+#[case::iso8601_days(json!("P1DT30M"), 24 * 60 * 60 + 30 * 60)]
+fn test_post_polling_with_duration(#[case] duration: serde_json::Value, #[case] expected: i64) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let rocket = create_test_rocket(pool);
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+
+    let response = client
+        .post("/polling")
+        .header(ContentType::JSON)
+        .body(
+            json!({
+                "vk_link": "https://vk.com/wall-1_1",
+                "prolong": false,
+                "duration": duration
+            })
+            .to_string(),
+        )
+        .dispatch();
+
+    assert_eq!(response.status(), Status::Ok);
+
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let begin =
+        chrono::DateTime::parse_from_rfc3339(body["dt_parse_begin"].as_str().unwrap()).unwrap();
+    let end = chrono::DateTime::parse_from_rfc3339(body["dt_parse_end"].as_str().unwrap()).unwrap();
+
+    assert_eq!((end - begin).num_seconds(), expected);
+}
+
 #[test]
-fn test_async_task_is_scheduled() {
-    vk_api::reset_counter();
+fn test_post_polling_prolong_with_duration() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let rocket = create_test_rocket(pool);
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+
+    let create = |prolong: bool, duration: &str| {
+        let response = client
+            .post("/polling")
+            .header(ContentType::JSON)
+            .body(
+                json!({
+                    "vk_link": "https://vk.com/wall-1_1",
+                    "prolong": prolong,
+                    "duration": duration
+                })
+                .to_string(),
+            )
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        chrono::DateTime::parse_from_rfc3339(body["dt_parse_end"].as_str().unwrap()).unwrap()
+    };
+
+    let end1 = create(false, "PT1H");
+
+    // Extending sets the end relative to the call, not to the previous end
+    let end2 = create(true, "PT6H");
+    assert!(
+        end2 - end1 >= chrono::Duration::hours(5),
+        "dt_parse_end should be extended by the requested duration"
+    );
+
+    // Without prolong the duration of an existing window is left untouched
+    let end3 = create(false, "P1D");
+    assert_eq!(end2, end3);
+}
 
+#[test]
+fn test_post_polling_track_audience_opt_in() {
     let rt = tokio::runtime::Runtime::new().unwrap();
     let pool = rt.block_on(setup_test_db());
+    let rocket = create_test_rocket(pool);
+    let client = Client::tracked(rocket).expect("valid rocket instance");
 
-    // Create scheduler and start it
-    let scheduler = rt.block_on(async {
-        let sched = tokio_cron_scheduler::JobScheduler::new()
-            .await
-            .expect("Failed to create scheduler");
-        sched.start().await.expect("Failed to start scheduler");
-        sched
-    });
+    let create = |request: serde_json::Value| {
+        let response = client
+            .post("/polling")
+            .header(ContentType::JSON)
+            .body(request.to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        body["track_audience"].clone()
+    };
 
-    let scheduler = Arc::new(scheduler);
+    // Off unless asked for
+    assert_eq!(
+        create(json!({"vk_link": "https://vk.com/wall-1_1", "prolong": false})),
+        false
+    );
 
-    let rocket = rocket::build()
-        .manage(Arc::new(pool.clone()))
-        .manage(scheduler.clone())
-        .mount("/", rocket::routes![post_polling, get_polling]);
+    // A running task can opt in without being prolonged
+    assert_eq!(
+        create(json!({
+            "vk_link": "https://vk.com/wall-1_1",
+            "prolong": false,
+            "track_audience": true
+        })),
+        true
+    );
 
+    // Leaving the flag out later does not turn tracking off
+    assert_eq!(
+        create(json!({"vk_link": "https://vk.com/wall-1_1", "prolong": true})),
+        true
+    );
+}
+
+#[rstest]
+#[case::negative(json!(-10), "Duration must be between")]
+#[case::too_long(json!(10 * 365 * 24 * 60 * 60), "Duration must be between")]
+#[case::zero_iso8601(json!("PT0S"), "Duration must be between")]
+#[case::malformed(json!("6 hours"), "Invalid ISO-8601 duration")]
+#[case::months(json!("P1M"), "Years and months are not supported")]
+fn test_post_polling_invalid_duration(
+    #[case] duration: serde_json::Value,
+    #[case] expected_body_contains: &str,
+) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let rocket = create_test_rocket(pool);
     let client = Client::tracked(rocket).expect("valid rocket instance");
 
-    // Make POST request to create a polling task
     let response = client
         .post("/polling")
         .header(ContentType::JSON)
         .body(
             json!({
                 "vk_link": "https://vk.com/wall-1_1",
-                "prolong": false
+                "prolong": false,
+                "duration": duration
             })
             .to_string(),
         )
         .dispatch();
 
-    assert_eq!(
-        response.status(),
-        Status::Ok,
-        "POST /polling should succeed"
+    assert_eq!(response.status(), Status::BadRequest);
+
+    let body = response.into_string().unwrap();
+    assert!(
+        body.contains(expected_body_contains),
+        "Expected body to contain '{}', but got: {}",
+        expected_body_contains,
+        body
     );
+}
 
-    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
-    let scrapper_id = body["scrapper_id"].as_i64().unwrap();
+fn post_batch(client: &Client, posts: serde_json::Value) -> (Status, serde_json::Value) {
+    let response = client
+        .post("/polling/batch")
+        .header(ContentType::JSON)
+        .body(json!({ "posts": posts }).to_string())
+        .dispatch();
+    let status = response.status();
+
+    (
+        status,
+        serde_json::from_str(&response.into_string().unwrap()).unwrap(),
+    )
+}
 
-    println!(
-        "✓ POST /polling succeeded, created post with scrapper_id: {}",
-        scrapper_id
+#[test]
+fn test_post_polling_batch_reports_every_item() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let vk = Arc::new(FakeVkClient::new());
+    vk.set_default_stats(Some(stats(1)));
+    vk.set_stats("-999_999", stats(0));
+    vk.set_replies("-998_998", vec![FakeReply::Missing]);
+    let rocket = rocket::build()
+        .manage(Arc::new(pool.clone()))
+        .manage(Arc::new(SystemClock) as Arc<dyn Clock>)
+        .manage(vk.clone() as Arc<dyn VkClient>)
+        .mount("/", rocket::routes![post_polling_batch]);
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+
+    let (status, body) = post_batch(
+        &client,
+        json!([
+            {"vk_link": "https://vk.com/wall-1_1", "prolong": false, "duration": 3600},
+            {"vk_link": "https://invalid.com/post123", "prolong": false},
+            {"vk_link": "https://vk.com/wall-999_999", "prolong": false},
+            {"vk_link": "-2_2", "prolong": false, "duration": "PT6H", "track_audience": true},
+            {"vk_link": "https://vk.com/wall-3_3", "prolong": false, "duration": -10},
+            {"vk_link": "https://vk.com/wall-998_998", "prolong": false},
+        ]),
     );
 
-    // Wait for the scheduled task to execute at least once
-    // The pooling_delta is 5 seconds by default in test, so we wait a bit longer
-    println!("Waiting for async task to execute (this may take up to 3 seconds)...");
-    std::thread::sleep(std::time::Duration::from_secs(3));
+    // Failed items do not fail the batch
+    assert_eq!(status, Status::Ok);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 6);
 
-    // Check if post_info was created by the async task
-    let post_info_count = rt.block_on(async {
-        let result = sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
-            .bind(scrapper_id as i32)
-            .fetch_one(&pool)
-            .await
-            .expect("Failed to query POST_INFO");
+    let codes: Vec<&str> = results
+        .iter()
+        .map(|item| item["error"]["code"].as_str().unwrap_or("ok"))
+        .collect();
+    assert_eq!(
+        codes,
+        [
+            "ok",
+            "validation_error",
+            "not_found",
+            "ok",
+            "validation_error",
+            "not_found"
+        ]
+    );
+    assert_eq!(results[1]["vk_link"], "https://invalid.com/post123");
+    assert!(results[1]["post"].is_null());
+    assert_eq!(results[0]["post"]["vk_id"], "-1_1");
+    assert!(results[0]["error"].is_null());
+    assert_eq!(results[3]["post"]["vk_id"], "-2_2");
+    assert_eq!(results[3]["post"]["track_audience"], true);
+
+    // Valid posts are checked with one VK call
+    assert_eq!(vk.calls(), [["-1_1", "-999_999", "-2_2", "-998_998"]]);
+
+    let vk_ids: Vec<String> = rt
+        .block_on(sqlx::query_scalar("SELECT vk_id FROM POST ORDER BY id").fetch_all(&pool))
+        .unwrap();
+    assert_eq!(vk_ids, ["-1_1", "-2_2"]);
+}
 
-        result.get::<i64, _>("count")
-    });
+#[test]
+fn test_post_polling_batch_isolates_inaccessible_post() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let rocket = create_test_rocket(pool);
+    let client = Client::tracked(rocket).expect("valid rocket instance");
 
-    assert!(
-        post_info_count > 0,
-        "Expected at least one POST_INFO entry to be created by the async task, but found {}",
-        post_info_count
+    // The private post fails the batched call, the others are still enrolled
+    let (status, body) = post_batch(
+        &client,
+        json!([
+            {"vk_link": "-1_1", "prolong": false},
+            {"vk_link": "-403_403", "prolong": false},
+            {"vk_link": "-999_999", "prolong": false},
+            {"vk_link": "-1_1", "prolong": true},
+        ]),
     );
 
-    println!(
-        "✓ Async task successfully executed and created {} POST_INFO entries",
-        post_info_count
+    assert_eq!(status, Status::Ok);
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results[0]["post"]["vk_id"], "-1_1");
+    assert_eq!(results[1]["error"]["code"], "vk_access_denied");
+    assert_eq!(results[2]["error"]["code"], "not_found");
+
+    // The same post twice ends up in one task, like two POST /polling calls
+    assert_eq!(
+        results[3]["post"]["scrapper_id"],
+        results[0]["post"]["scrapper_id"]
     );
 }
 
 #[test]
-fn test_async_task_not_scheduled_when_recent_polling_exists() {
-    vk_api::reset_counter();
+fn test_post_polling_batch_vk_failure_fails_every_item() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+
+    let (status, body) = post_batch(
+        &client,
+        json!([
+            {"vk_link": "-1_1", "prolong": false},
+            {"vk_link": "-429_429", "prolong": false},
+        ]),
+    );
+
+    assert_eq!(status, Status::Ok);
+    let results = body["results"].as_array().unwrap();
+    assert!(
+        results
+            .iter()
+            .all(|item| item["error"]["code"] == "vk_rate_limited")
+    );
 
+    let count: i64 = rt
+        .block_on(sqlx::query_scalar("SELECT COUNT(*) FROM POST").fetch_one(&pool))
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[rstest]
+#[case::empty(0)]
+#[case::too_many(201)]
+fn test_post_polling_batch_size_limits(#[case] size: usize) {
     let rt = tokio::runtime::Runtime::new().unwrap();
     let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
 
-    let scheduler = rt.block_on(async {
-        let sched = tokio_cron_scheduler::JobScheduler::new()
-            .await
-            .expect("Failed to create scheduler");
-        sched.start().await.expect("Failed to start scheduler");
-        sched
-    });
+    let posts: Vec<serde_json::Value> = (1..=size)
+        .map(|i| json!({"vk_link": format!("-1_{}", i), "prolong": false}))
+        .collect();
+    let (status, body) = post_batch(&client, json!(posts));
 
-    let scheduler = Arc::new(scheduler);
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body["error"]["code"], "validation_error");
+}
 
+fn post_with_key(
+    client: &Client,
+    key: &str,
+    request: serde_json::Value,
+) -> (Status, serde_json::Value) {
+    let response = client
+        .post("/polling")
+        .header(ContentType::JSON)
+        .header(Header::new("Idempotency-Key", key.to_string()))
+        .body(request.to_string())
+        .dispatch();
+    let status = response.status();
+
+    (
+        status,
+        serde_json::from_str(&response.into_string().unwrap()).unwrap(),
+    )
+}
+
+#[test]
+fn test_post_polling_idempotency_key_replays_response() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let vk = Arc::new(FakeVkClient::new());
+    vk.set_default_stats(Some(stats(1)));
+    let clock = Arc::new(ManualClock::new(SystemClock.now()));
     let rocket = rocket::build()
         .manage(Arc::new(pool.clone()))
-        .manage(scheduler.clone())
-        .mount("/", rocket::routes![post_polling, get_polling]);
-
+        .manage(clock.clone() as Arc<dyn Clock>)
+        .manage(vk.clone() as Arc<dyn VkClient>)
+        .mount("/", rocket::routes![post_polling]);
     let client = Client::tracked(rocket).expect("valid rocket instance");
 
-    // First request - should schedule a task
-    let response1 = client
-        .post("/polling")
-        .header(ContentType::JSON)
-        .body(
-            json!({
-                "vk_link": "https://vk.com/wall-2_2",
-                "prolong": false
-            })
-            .to_string(),
+    let request = json!({"vk_link": "https://vk.com/wall-1_1", "prolong": true});
+    let (status, first) = post_with_key(&client, "retry-1", request.clone());
+    assert_eq!(status, Status::Ok);
+
+    // The retry gets the original response: no VK call and the window is not prolonged again
+    clock.advance(chrono::Duration::minutes(1));
+    let (status, retried) = post_with_key(&client, "retry-1", request.clone());
+    assert_eq!(status, Status::Ok);
+    assert_eq!(retried, first);
+    assert_eq!(vk.call_count(), 1);
+
+    // Another link to the same post is the same request
+    let (status, retried) = post_with_key(
+        &client,
+        "retry-1",
+        json!({"vk_link": "-1_1", "prolong": true}),
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(retried, first);
+
+    // A new key is a new request
+    let (status, prolonged) = post_with_key(&client, "retry-2", request.clone());
+    assert_eq!(status, Status::Ok);
+    assert_eq!(prolonged["scrapper_id"], first["scrapper_id"]);
+    assert_ne!(prolonged["dt_parse_end"], first["dt_parse_end"]);
+    assert_eq!(vk.call_count(), 2);
+
+    // Once the key expires the request is carried out again
+    let ttl = utils::get_idempotency_key_ttl_seconds();
+    clock.advance(chrono::Duration::seconds(ttl));
+    let (status, expired) = post_with_key(&client, "retry-1", request);
+    assert_eq!(status, Status::Ok);
+    assert_ne!(expired, first);
+    assert_eq!(vk.call_count(), 3);
+}
+
+#[test]
+fn test_post_polling_idempotency_key_reuse_and_failures() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool.clone())).expect("valid rocket instance");
+
+    let (status, _) = post_with_key(
+        &client,
+        "key-1",
+        json!({"vk_link": "https://vk.com/wall-1_1", "prolong": false}),
+    );
+    assert_eq!(status, Status::Ok);
+
+    // The same key with another request is rejected
+    let (status, body) = post_with_key(
+        &client,
+        "key-1",
+        json!({"vk_link": "https://vk.com/wall-2_2", "prolong": false}),
+    );
+    assert_eq!(status, Status::Conflict);
+    assert_eq!(body["error"]["code"], "conflict");
+
+    // Failed requests are not stored, a retry runs again
+    let (status, _) = post_with_key(
+        &client,
+        "key-2",
+        json!({"vk_link": "https://vk.com/wall-429_429", "prolong": false}),
+    );
+    assert_eq!(status, Status::ServiceUnavailable);
+    let stored: i64 = rt
+        .block_on(
+            sqlx::query_scalar("SELECT COUNT(*) FROM IDEMPOTENCY_KEY WHERE key = 'key-2'")
+                .fetch_one(&pool),
         )
-        .dispatch();
+        .unwrap();
+    assert_eq!(stored, 0);
 
-    assert_eq!(response1.status(), Status::Ok);
-    let body1: serde_json::Value = serde_json::from_str(&response1.into_string().unwrap()).unwrap();
-    let scrapper_id = body1["scrapper_id"].as_i64().unwrap() as i32;
+    let (status, body) = post_with_key(
+        &client,
+        &"k".repeat(256),
+        json!({"vk_link": "https://vk.com/wall-1_1", "prolong": false}),
+    );
+    assert_eq!(status, Status::BadRequest);
+    assert_eq!(body["error"]["code"], "validation_error");
+}
 
-    println!("Waiting for first task execution (this may take up to 3 seconds)...");
-    std::thread::sleep(std::time::Duration::from_secs(3));
+#[test]
+fn test_concurrent_creation_of_the_same_post() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let now = SystemClock.now();
 
-    let post_info_count_after_first = rt.block_on(async {
-        let result = sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
+    // Without a lock both would insert the post and one would fail on the overlap constraint
+    let results = rt.block_on(async {
+        let create =
+            || db_commands::get_or_create_post_with_prolong(&pool, "-5_5", false, 3600, false, now);
+        tokio::join!(create(), create(), create())
+    });
+    let ids = [
+        results.0.unwrap().id,
+        results.1.unwrap().id,
+        results.2.unwrap().id,
+    ];
+    assert!(ids.iter().all(|id| *id == ids[0]));
+
+    // Concurrent requests with one key all get the response of the first one
+    let request = json!({"vk_id": "-6_6"});
+    let results = rt.block_on(async {
+        let create = |prolong: bool| {
+            db_commands::get_or_create_post_idempotent(
+                &pool,
+                "same-key",
+                &request,
+                "-6_6",
+                prolong,
+                3600,
+                false,
+                now,
+                move |post_details| json!({"scrapper_id": post_details.id, "prolong": prolong}),
+            )
+        };
+        tokio::join!(create(false), create(true))
+    });
+    assert_eq!(results.0.unwrap(), results.1.unwrap());
+}
+
+// Dispatcher ticking by hand on a clock that starts now, so no test waits for real time
+fn start_manual_tasks(
+    rt: &tokio::runtime::Runtime,
+    pool: &sqlx::PgPool,
+    vk: &Arc<dyn VkClient>,
+) -> (ManualScheduler, Arc<dyn Clock>) {
+    let clock = Arc::new(ManualClock::new(SystemClock.now()));
+    let scheduler = ManualScheduler::new(clock.clone());
+    let clock: Arc<dyn Clock> = clock;
+
+    let _runtime = rt.enter();
+    tasks::init_all_tasks(pool, vk, &clock, &tasks::JobRegistry::default(), &scheduler);
+
+    (scheduler, clock)
+}
+
+fn count_post_info(rt: &tokio::runtime::Runtime, pool: &sqlx::PgPool, scrapper_id: i32) -> i64 {
+    rt.block_on(async {
+        sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
             .bind(scrapper_id)
-            .fetch_one(&pool)
+            .fetch_one(pool)
             .await
-            .expect("Failed to query POST_INFO");
+            .expect("Failed to query POST_INFO")
+            .get::<i64, _>("count")
+    })
+}
 
-        result.get::<i64, _>("count")
-    });
+#[test]
+fn test_async_task_is_scheduled() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
 
-    println!(
-        "After first request: {} POST_INFO entries",
-        post_info_count_after_first
-    );
+    // Start the polling dispatcher
+    let vk = fake_vk();
+    let (scheduler, clock) = start_manual_tasks(&rt, &pool, &vk);
 
-    // Second request immediately after - should NOT schedule another task
-    // because recent polling exists (within 2*pooling_delta)
-    let response2 = client
+    let rocket = rocket::build()
+        .manage(Arc::new(pool.clone()))
+        .manage(vk)
+        .manage(clock)
+        .mount("/", rocket::routes![post_polling, get_polling]);
+
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+
+    // Make POST request to create a polling task
+    let response = client
         .post("/polling")
         .header(ContentType::JSON)
         .body(
             json!({
-                "vk_link": "https://vk.com/wall-2_2",
+                "vk_link": "https://vk.com/wall-1_1",
                 "prolong": false
             })
             .to_string(),
         )
         .dispatch();
 
-    assert_eq!(response2.status(), Status::Ok);
-
-    // Wait a bit to see if a second task would execute (it shouldn't)
-    std::thread::sleep(std::time::Duration::from_secs(3));
+    assert_eq!(
+        response.status(),
+        Status::Ok,
+        "POST /polling should succeed"
+    );
 
-    let post_info_count_after_second = rt.block_on(async {
-        let result = sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
-            .bind(scrapper_id)
-            .fetch_one(&pool)
-            .await
-            .expect("Failed to query POST_INFO");
+    let body: serde_json::Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let scrapper_id = body["scrapper_id"].as_i64().unwrap() as i32;
 
-        result.get::<i64, _>("count")
-    });
+    // The next tick picks up the new post
+    let dispatched = rt
+        .block_on(scheduler.tick())
+        .expect("Failed to run dispatcher tick");
+    assert_eq!(dispatched, 1, "The new post should be dispatched");
 
-    println!(
-        "After second request: {} POST_INFO entries",
-        post_info_count_after_second
+    assert_eq!(
+        count_post_info(&rt, &pool, scrapper_id),
+        1,
+        "Expected the tick to create a POST_INFO entry"
     );
+}
 
-    // The count should be the same or only slightly increased (from the original task)
-    // but not doubled, proving no new task was scheduled
-    assert!(
-        post_info_count_after_second <= post_info_count_after_first + 1,
-        "Expected POST_INFO count to not significantly increase when recent polling exists"
-    );
+#[test]
+fn test_repeated_request_does_not_duplicate_polling() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+
+    let vk = fake_vk();
+    let (scheduler, clock) = start_manual_tasks(&rt, &pool, &vk);
+
+    let rocket = rocket::build()
+        .manage(Arc::new(pool.clone()))
+        .manage(vk)
+        .manage(clock)
+        .mount("/", rocket::routes![post_polling, get_polling]);
+
+    let client = Client::tracked(rocket).expect("valid rocket instance");
+
+    let create = || {
+        let response = client
+            .post("/polling")
+            .header(ContentType::JSON)
+            .body(
+                json!({
+                    "vk_link": "https://vk.com/wall-2_2",
+                    "prolong": false,
+                    "duration": 3600
+                })
+                .to_string(),
+            )
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let body: serde_json::Value =
+            serde_json::from_str(&response.into_string().unwrap()).unwrap();
+        body["scrapper_id"].as_i64().unwrap() as i32
+    };
 
-    println!("✓ Async task correctly NOT scheduled when recent polling exists");
+    // First request - the first tick polls the post
+    let scrapper_id = create();
+    rt.block_on(scheduler.tick())
+        .expect("Failed to run dispatcher tick");
+    assert_eq!(count_post_info(&rt, &pool, scrapper_id), 1);
+
+    // Second request for the same post - a tick at the same time must not poll it again
+    assert_eq!(create(), scrapper_id);
+    let dispatched = rt
+        .block_on(scheduler.tick())
+        .expect("Failed to run dispatcher tick");
+    assert_eq!(dispatched, 0, "Polled post should not be dispatched again");
+    assert_eq!(count_post_info(&rt, &pool, scrapper_id), 1);
+
+    // One delta later the post is due again, still once per tick
+    let delta = chrono::Duration::seconds(utils::get_pooling_delta_seconds() as i64);
+    rt.block_on(scheduler.advance(delta))
+        .expect("Failed to run dispatcher tick");
+    assert_eq!(count_post_info(&rt, &pool, scrapper_id), 2);
 }
@@ -0,0 +1,75 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+
+use rate_limiter::RateLimiter;
+
+const LONG_WAIT: Duration = Duration::from_secs(5);
+
+#[tokio::test]
+async fn test_burst_then_rate() {
+    let limiter = RateLimiter::new(10.0);
+    let started = Instant::now();
+
+    // One second worth of requests is available at once
+    for _ in 0..10 {
+        assert!(limiter.acquire(LONG_WAIT).await);
+    }
+    assert!(started.elapsed() < Duration::from_millis(50));
+
+    // The next ones are spaced by 1 / rate
+    assert!(limiter.acquire(LONG_WAIT).await);
+    assert!(limiter.acquire(LONG_WAIT).await);
+    assert!(started.elapsed() >= Duration::from_millis(190));
+}
+
+#[tokio::test]
+async fn test_acquire_gives_up_after_max_wait() {
+    let limiter = RateLimiter::new(1.0);
+
+    assert!(limiter.acquire(LONG_WAIT).await);
+    assert!(!limiter.acquire(Duration::from_millis(50)).await);
+    assert_eq!(limiter.queue_depth(), 0, "Cancelled waiter left the queue");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_waiters_are_served_in_order_and_counted() {
+    let limiter = Arc::new(RateLimiter::new(20.0));
+
+    // Drain the burst so every waiter has to queue
+    for _ in 0..20 {
+        assert!(limiter.acquire(LONG_WAIT).await);
+    }
+
+    let served = Arc::new(Mutex::new(Vec::new()));
+    let mut handles = Vec::new();
+    for waiter in 0..5 {
+        let limiter = limiter.clone();
+        let served = served.clone();
+        handles.push(tokio::spawn(async move {
+            assert!(limiter.acquire(LONG_WAIT).await);
+            served.lock().unwrap().push(waiter);
+        }));
+
+        // Let the waiter enter the queue before the next one
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    assert!(limiter.queue_depth() > 0);
+
+    for handle in handles {
+        handle.await.unwrap();
+    }
+
+    assert_eq!(*served.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+    assert_eq!(limiter.queue_depth(), 0);
+}
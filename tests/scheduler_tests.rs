@@ -0,0 +1,298 @@
+use sqlx::Row;
+
+// Include all necessary modules for testing
+#[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
+#[path = "../src/db_commands.rs"]
+mod db_commands;
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/fake_vk_client.rs"]
+mod fake_vk_client;
+#[allow(dead_code)]
+#[path = "../src/manual_scheduler.rs"]
+mod manual_scheduler;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
+#[path = "../src/tasks.rs"]
+mod tasks;
+#[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+
+mod test_utils;
+use test_utils::{insert_post, setup_test_db};
+
+use chrono::{DateTime, Duration, SubsecRound, Utc};
+use clock::{Clock, SystemClock};
+use fake_vk_client::FakeVkClient;
+use manual_scheduler::{ManualClock, ManualScheduler};
+use models::{AudienceKind, NewWallWatcher, VkPostStats, VkWallPost};
+use std::sync::Arc;
+use tasks::{JobRegistry, init_all_tasks};
+use vk_api::VkClient;
+
+fn fake_vk() -> Arc<FakeVkClient> {
+    let vk = FakeVkClient::new();
+    vk.set_default_stats(Some(VkPostStats {
+        comments_count: 2,
+        likes_count: 3,
+        views_count: 4,
+        reposts_count: 1,
+        ..Default::default()
+    }));
+    Arc::new(vk)
+}
+
+// Starts the polling tasks on a manual clock, the time never moves on its own
+fn start_tasks(pool: &sqlx::PgPool, clock: &Arc<ManualClock>) -> ManualScheduler {
+    start_tasks_with_vk(pool, clock, fake_vk())
+}
+
+fn start_tasks_with_vk(
+    pool: &sqlx::PgPool,
+    clock: &Arc<ManualClock>,
+    vk: Arc<FakeVkClient>,
+) -> ManualScheduler {
+    let scheduler = ManualScheduler::new(clock.clone());
+    let dyn_clock: Arc<dyn Clock> = clock.clone();
+    let vk: Arc<dyn VkClient> = vk;
+
+    init_all_tasks(pool, &vk, &dyn_clock, &JobRegistry::default(), &scheduler);
+
+    scheduler
+}
+
+// Postgres keeps microseconds, so the clock starts at a time that survives a round trip
+fn now() -> DateTime<Utc> {
+    SystemClock.now().trunc_subsecs(6)
+}
+
+fn delta() -> Duration {
+    Duration::seconds(utils::get_pooling_delta_seconds() as i64)
+}
+
+async fn snapshot_times(pool: &sqlx::PgPool, post_id: i32) -> Vec<DateTime<Utc>> {
+    sqlx::query("SELECT info_time FROM POST_INFO WHERE post_id = $1 ORDER BY info_time")
+        .bind(post_id)
+        .fetch_all(pool)
+        .await
+        .expect("Failed to query POST_INFO")
+        .iter()
+        .map(|row| row.get("info_time"))
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manual_scheduler_polls_once_per_delta() {
+    let pool = setup_test_db().await;
+
+    let start = now();
+    let clock = Arc::new(ManualClock::new(start));
+    let post_id = insert_post(&pool, "-123_456", start, start + Duration::hours(1))
+        .await
+        .expect("Failed to insert post");
+
+    let scheduler = start_tasks(&pool, &clock);
+
+    assert_eq!(scheduler.tick().await.expect("Tick failed"), 1);
+    // No time has passed, the post is not due yet
+    assert_eq!(scheduler.tick().await.expect("Tick failed"), 0);
+    assert_eq!(scheduler.advance(delta()).await.expect("Tick failed"), 1);
+    assert_eq!(scheduler.advance(delta()).await.expect("Tick failed"), 1);
+
+    // Snapshots are stamped with the clock time of their tick
+    assert_eq!(
+        snapshot_times(&pool, post_id).await,
+        vec![start, start + delta(), start + delta() * 2]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manual_scheduler_stops_polling_finished_window() {
+    let pool = setup_test_db().await;
+
+    let start = now();
+    let clock = Arc::new(ManualClock::new(start));
+    let post_id = insert_post(&pool, "-123_456", start, start + delta() * 3)
+        .await
+        .expect("Failed to insert post");
+
+    let scheduler = start_tasks(&pool, &clock);
+
+    let mut dispatched = Vec::new();
+    for _ in 0..5 {
+        dispatched.push(scheduler.tick().await.expect("Tick failed"));
+        clock.advance(delta());
+    }
+
+    // The window covers ticks at 0, 1 and 2 deltas, the end itself is exclusive
+    assert_eq!(dispatched, vec![1, 1, 1, 0, 0]);
+    assert_eq!(snapshot_times(&pool, post_id).await.len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manual_scheduler_recovers_posts_after_restart() {
+    let pool = setup_test_db().await;
+
+    let start = now();
+    let clock = Arc::new(ManualClock::new(start));
+    let post_id = insert_post(&pool, "-123_456", start, start + Duration::hours(1))
+        .await
+        .expect("Failed to insert post");
+
+    let before_restart = start_tasks(&pool, &clock);
+    assert_eq!(before_restart.tick().await.expect("Tick failed"), 1);
+    drop(before_restart);
+
+    // Nothing but the database survives a restart, the new tasks know nothing about the post
+    clock.advance(delta() / 4);
+    let after_restart = start_tasks(&pool, &clock);
+
+    // The snapshot taken before the restart still counts, so the post is not polled twice
+    assert_eq!(after_restart.tick().await.expect("Tick failed"), 0);
+    assert_eq!(
+        after_restart.advance(delta()).await.expect("Tick failed"),
+        1
+    );
+
+    assert_eq!(snapshot_times(&pool, post_id).await.len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manual_scheduler_collects_comments_on_own_cadence() {
+    let pool = setup_test_db().await;
+
+    let start = now();
+    let clock = Arc::new(ManualClock::new(start));
+    insert_post(&pool, "-123_456", start, start + Duration::hours(1))
+        .await
+        .expect("Failed to insert post");
+
+    let vk = fake_vk();
+    let scheduler = start_tasks_with_vk(&pool, &clock, vk.clone());
+    let comments_interval = Duration::seconds(utils::get_comments_polling_seconds());
+
+    // Comments are collected on the first tick, then once per interval however often stats are polled
+    scheduler.tick().await.expect("Tick failed");
+    let mut elapsed = Duration::zero();
+    while elapsed + delta() < comments_interval {
+        scheduler.advance(delta()).await.expect("Tick failed");
+        elapsed += delta();
+    }
+    assert_eq!(vk.comment_calls().len(), 1);
+
+    scheduler
+        .advance(comments_interval - elapsed)
+        .await
+        .expect("Tick failed");
+    assert_eq!(vk.comment_calls().len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manual_scheduler_collects_audience_only_when_tracked() {
+    let pool = setup_test_db().await;
+
+    let start = now();
+    let clock = Arc::new(ManualClock::new(start));
+    insert_post(&pool, "-123_456", start, start + Duration::hours(3))
+        .await
+        .expect("Failed to insert post");
+    let tracked_id = insert_post(&pool, "-123_789", start, start + Duration::hours(3))
+        .await
+        .expect("Failed to insert post");
+    sqlx::query("UPDATE POST SET track_audience = TRUE WHERE id = $1")
+        .bind(tracked_id)
+        .execute(&pool)
+        .await
+        .expect("Failed to update post");
+
+    let vk = fake_vk();
+    let scheduler = start_tasks_with_vk(&pool, &clock, vk.clone());
+    let audience_interval = Duration::seconds(utils::get_audience_polling_seconds());
+
+    // Likes and reposts of the tracked post only, then nothing until the interval passes
+    scheduler.tick().await.expect("Tick failed");
+    scheduler.advance(delta()).await.expect("Tick failed");
+    assert_eq!(
+        vk.audience_calls(),
+        vec![
+            ("-123_789".to_string(), AudienceKind::Like, 0),
+            ("-123_789".to_string(), AudienceKind::Repost, 0),
+        ]
+    );
+
+    clock.set(start + audience_interval);
+    scheduler.tick().await.expect("Tick failed");
+    assert_eq!(vk.audience_calls().len(), 4);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_manual_scheduler_enrols_posts_from_watched_wall() {
+    let pool = setup_test_db().await;
+
+    let start = now();
+    let clock = Arc::new(ManualClock::new(start));
+    db_commands::create_wall_watcher(
+        &pool,
+        &NewWallWatcher {
+            owner_id: -123,
+            interval_seconds: 600,
+            duration_seconds: 3600,
+            track_audience: false,
+            include_pinned: false,
+            min_age_seconds: 0,
+            keywords: Vec::new(),
+        },
+        start,
+    )
+    .await
+    .expect("Failed to create watcher");
+
+    let vk = fake_vk();
+    let scheduler = start_tasks_with_vk(&pool, &clock, vk.clone());
+
+    // Nothing was published yet
+    assert_eq!(scheduler.tick().await.expect("Tick failed"), 0);
+
+    vk.set_wall(
+        -123,
+        vec![VkWallPost {
+            vk_id: "-123_1".to_string(),
+            published_at: start + delta(),
+            text: String::new(),
+            is_pinned: false,
+        }],
+    );
+
+    // The wall is only checked again once its interval has passed
+    assert_eq!(
+        scheduler.advance(delta() * 2).await.expect("Tick failed"),
+        0
+    );
+    // The enrolled post is polled on the same tick that found it
+    clock.set(start + Duration::seconds(600));
+    assert_eq!(scheduler.tick().await.expect("Tick failed"), 1);
+    assert_eq!(vk.wall_calls().len(), 2);
+
+    assert_eq!(scheduler.advance(delta()).await.expect("Tick failed"), 1);
+    assert_eq!(vk.wall_calls().len(), 2);
+}
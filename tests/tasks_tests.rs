@@ -2,90 +2,148 @@ use sqlx::Row;
 
 // Include all necessary modules for testing
 #[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
 #[path = "../src/db_commands.rs"]
 mod db_commands;
 #[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/fake_vk_client.rs"]
+mod fake_vk_client;
+#[allow(dead_code)]
+#[path = "../src/manual_scheduler.rs"]
+mod manual_scheduler;
+#[allow(dead_code)]
 #[path = "../src/models.rs"]
 mod models;
 #[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
 #[path = "../src/tasks.rs"]
 mod tasks;
 #[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
 #[path = "../src/utils.rs"]
 mod utils;
-
-// Mock VK API module
-mod vk_api {
-    use crate::models::VkPostStats;
-    use rocket::response::status;
-    use std::sync::atomic::Ordering;
-
-    static CALL_COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
-
-    pub async fn call_vk(_post_id: &str) -> Result<VkPostStats, status::BadRequest<String>> {
-        let count = CALL_COUNTER.fetch_add(1, Ordering::SeqCst);
-
-        // Simulate different responses based on call count
-        let base = count + 1;
-        Ok(VkPostStats {
-            comments_count: (base * 2) as u64,
-            likes_count: (base * 3) as u64,
-            views_count: (base * 4) as u64,
-            reposts_count: base as u64,
-        })
-    }
-
-    pub fn reset_counter() {
-        CALL_COUNTER.store(0, Ordering::SeqCst);
-    }
-}
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
 
 mod test_utils;
 use test_utils::setup_test_db;
 
-use tasks::{init_all_tasks, poll_post_stats};
+use chrono::SubsecRound;
+use clock::{Clock, SystemClock};
+use db_commands::{create_wall_watcher, get_post_meta_versions};
+use errors::VkError;
+use fake_vk_client::{FakeReply, FakeVkClient};
+use manual_scheduler::ManualClock;
+use models::{
+    AudienceKind, NewWallWatcher, VkAudienceEntry, VkComment, VkPostMeta, VkPostStats, VkWallPost,
+};
+use scheduler::IntervalScheduler;
+use std::sync::Arc;
+use tasks::{
+    Dispatcher, JobRegistry, check_wall, collect_post_audience, collect_post_comments,
+    init_all_tasks, poll_post_stats,
+};
+use vk_api::VkClient;
 
-#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
-async fn test_poll_post_stats_calls_vk_and_saves_to_db() {
-    vk_api::reset_counter();
+fn stats(base: u64) -> VkPostStats {
+    VkPostStats {
+        comments_count: base * 2,
+        likes_count: base * 3,
+        views_count: base * 4,
+        reposts_count: base,
+        ..Default::default()
+    }
+}
 
-    let pool = setup_test_db().await;
+// Every post not scripted otherwise has likes=3, comments=2, reposts=1, views=4
+fn fake_vk() -> Arc<FakeVkClient> {
+    let vk = FakeVkClient::new();
+    vk.set_default_stats(Some(stats(1)));
+    Arc::new(vk)
+}
 
-    // Create a post that needs polling (expires in 10 minutes to be safe)
-    let post_id = sqlx::query(
+async fn create_active_post(pool: &sqlx::PgPool, vk_id: &str) -> i32 {
+    // Expires in 10 minutes to be safe
+    sqlx::query(
         r#"
         INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end)
         VALUES ($1, CURRENT_TIMESTAMP - INTERVAL '60 seconds', CURRENT_TIMESTAMP + INTERVAL '600 seconds')
         RETURNING id
         "#
     )
-    .bind("-123_456")
-    .fetch_one(&pool)
+    .bind(vk_id)
+    .fetch_one(pool)
     .await
     .expect("Failed to create post")
-    .get::<i32, _>("id");
+    .get::<i32, _>("id")
+}
 
-    // Create a mock scheduler
-    let scheduler = tokio_cron_scheduler::JobScheduler::new()
+// Runs a single dispatcher tick and waits for all spawned polls
+async fn poll_due_posts(pool: &sqlx::PgPool, vk: &Arc<FakeVkClient>) -> usize {
+    let dispatcher = Dispatcher::new(
+        pool.clone(),
+        vk.clone(),
+        Arc::new(SystemClock),
+        2,
+        JobRegistry::default(),
+    );
+    let handles = dispatcher
+        .dispatch_due_posts()
         .await
-        .expect("Failed to create scheduler");
+        .expect("Failed to dispatch due posts");
+    let dispatched = handles.len();
 
-    let job_id = uuid::Uuid::new_v4();
+    for handle in handles {
+        handle.await.expect("Polling worker panicked");
+    }
 
-    // Call poll_post_stats
-    let result = poll_post_stats(&job_id, &scheduler, &pool, post_id).await;
-    assert!(result.is_ok(), "poll_post_stats should succeed");
+    dispatched
+}
 
-    // Verify that POST_INFO was created
-    let post_info_count = sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
+async fn count_post_info(pool: &sqlx::PgPool, post_id: i32) -> i64 {
+    sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
         .bind(post_id)
-        .fetch_one(&pool)
+        .fetch_one(pool)
         .await
         .expect("Failed to query POST_INFO")
-        .get::<i64, _>("count");
+        .get::<i64, _>("count")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_calls_vk_and_saves_to_db() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+
+    // Call poll_post_stats
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[(post_id, "-123_456".to_string())],
+    )
+    .await;
+    assert!(result.is_ok(), "poll_post_stats should succeed");
 
+    // Verify that POST_INFO was created
     assert_eq!(
-        post_info_count, 1,
+        count_post_info(&pool, post_id).await,
+        1,
         "Should have created one POST_INFO entry"
     );
 
@@ -98,101 +156,224 @@ async fn test_poll_post_stats_calls_vk_and_saves_to_db() {
     .await
     .expect("Failed to fetch POST_INFO");
 
-    assert_eq!(post_info.get::<i32, _>("likes_count"), 3);
-    assert_eq!(post_info.get::<i32, _>("comments_count"), 2);
-    assert_eq!(post_info.get::<i32, _>("reposts_count"), 1);
-    assert_eq!(post_info.get::<i32, _>("views_count"), 4);
+    assert_eq!(post_info.get::<i64, _>("likes_count"), 3);
+    assert_eq!(post_info.get::<i64, _>("comments_count"), 2);
+    assert_eq!(post_info.get::<i64, _>("reposts_count"), 1);
+    assert_eq!(post_info.get::<i64, _>("views_count"), 4);
 
     println!("✓ poll_post_stats successfully called VK API and saved data to DB");
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
-async fn test_poll_post_stats_stops_when_ready_to_finish() {
-    vk_api::reset_counter();
+async fn test_dispatcher_batches_all_due_posts() {
+    let vk = fake_vk();
 
     let pool = setup_test_db().await;
 
-    // Create a post that has already expired
-    let post_id = sqlx::query(
-        r#"
-        INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end)
-        VALUES ($1, CURRENT_TIMESTAMP - INTERVAL '600 seconds', CURRENT_TIMESTAMP - INTERVAL '300 seconds')
-        RETURNING id
-        "#
-    )
-    .bind("-123_789")
-    .fetch_one(&pool)
-    .await
-    .expect("Failed to create post")
-    .get::<i32, _>("id");
+    let post_ids = vec![
+        create_active_post(&pool, "-111_111").await,
+        create_active_post(&pool, "-222_222").await,
+        create_active_post(&pool, "-333_333").await,
+    ];
 
-    // Create a mock scheduler
-    let scheduler = tokio_cron_scheduler::JobScheduler::new()
-        .await
-        .expect("Failed to create scheduler");
+    let dispatched = poll_due_posts(&pool, &vk).await;
+    assert_eq!(dispatched, 1, "Should have dispatched one batch");
+
+    // All due posts are fetched with a single VK request
+    assert_eq!(vk.call_count(), 1, "Should have called VK API once");
+    assert_eq!(vk.calls()[0].len(), 3);
+
+    for post_id in post_ids {
+        assert_eq!(
+            count_post_info(&pool, post_id).await,
+            1,
+            "Should have created POST_INFO for post {}",
+            post_id
+        );
+    }
 
-    let job_id = uuid::Uuid::new_v4();
+    println!("✓ Dispatcher polls all due posts with one VK request");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_skips_posts_missing_from_response() {
+    let vk = fake_vk();
 
-    // Call poll_post_stats - it should detect the post is expired and not poll
-    let result = poll_post_stats(&job_id, &scheduler, &pool, post_id).await;
+    let pool = setup_test_db().await;
+
+    let existing_post_id = create_active_post(&pool, "-555_555").await;
+    let missing_post_id = create_active_post(&pool, "-404_404").await;
+    vk.set_replies("-404_404", vec![FakeReply::Missing]);
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[
+            (existing_post_id, "-555_555".to_string()),
+            (missing_post_id, "-404_404".to_string()),
+        ],
+    )
+    .await;
     assert!(
         result.is_ok(),
-        "poll_post_stats should succeed even when stopping"
+        "poll_post_stats should succeed when some posts are missing"
     );
 
-    // Verify that NO POST_INFO was created (because post is expired)
-    let post_info_count = sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
-        .bind(post_id)
+    assert_eq!(count_post_info(&pool, existing_post_id).await, 1);
+    assert_eq!(
+        count_post_info(&pool, missing_post_id).await,
+        0,
+        "Should NOT have saved zero stats for a post missing from VK response"
+    );
+
+    println!("✓ poll_post_stats reports missing posts instead of saving them");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_saves_64_bit_counters() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let viral_post_id = create_active_post(&pool, "-777_777").await;
+    let broken_post_id = create_active_post(&pool, "-888_888").await;
+    // More views than INTEGER can hold
+    vk.set_stats("-777_777", stats(1_000_000_000));
+    // More views than BIGINT can hold
+    vk.set_stats(
+        "-888_888",
+        VkPostStats {
+            views_count: u64::MAX,
+            ..stats(1)
+        },
+    );
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[
+            (viral_post_id, "-777_777".to_string()),
+            (broken_post_id, "-888_888".to_string()),
+        ],
+    )
+    .await;
+    assert!(result.is_ok(), "poll_post_stats should succeed");
+
+    let views: i64 = sqlx::query("SELECT views_count FROM POST_INFO WHERE post_id = $1")
+        .bind(viral_post_id)
         .fetch_one(&pool)
         .await
-        .expect("Failed to query POST_INFO")
-        .get::<i64, _>("count");
+        .expect("Failed to fetch POST_INFO")
+        .get("views_count");
+    assert_eq!(views, 4_000_000_000);
 
     assert_eq!(
-        post_info_count, 0,
-        "Should NOT have created POST_INFO for expired post"
+        count_post_info(&pool, broken_post_id).await,
+        0,
+        "Should NOT have saved a wrapped counter"
     );
-
-    println!("✓ poll_post_stats correctly stops polling for expired posts");
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
-async fn test_init_all_tasks_starts_tasks_for_active_posts() {
-    vk_api::reset_counter();
+async fn test_poll_post_stats_saves_extra_metrics() {
+    let vk = fake_vk();
 
     let pool = setup_test_db().await;
 
-    // Create multiple posts with different states
+    let post_id = create_active_post(&pool, "-123_456").await;
+    vk.set_stats(
+        "-123_456",
+        VkPostStats {
+            extra: [
+                ("reposts_wall".to_string(), 7),
+                ("video_views".to_string(), 9000),
+            ]
+            .into(),
+            ..stats(1)
+        },
+    );
 
-    // 1. Active post without recent polling - SHOULD start task
-    let active_post_id = sqlx::query(
-        r#"
-        INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end)
-        VALUES ($1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP + INTERVAL '300 seconds')
-        RETURNING id
-        "#,
+    poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[(post_id, "-123_456".to_string())],
     )
-    .bind("-111_111")
-    .fetch_one(&pool)
     .await
-    .expect("Failed to create active post")
-    .get::<i32, _>("id");
+    .expect("poll_post_stats should succeed");
 
-    // 2. Active post WITH recent polling - SHOULD NOT start task
-    let active_with_recent_id = sqlx::query(
-        r#"
-        INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end)
-        VALUES ($1, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP + INTERVAL '300 seconds')
-        RETURNING id
-        "#,
-    )
-    .bind("-222_222")
-    .fetch_one(&pool)
-    .await
-    .expect("Failed to create active post with recent polling")
-    .get::<i32, _>("id");
+    let metrics: serde_json::Value =
+        sqlx::query("SELECT metrics FROM POST_INFO WHERE post_id = $1")
+            .bind(post_id)
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to fetch POST_INFO")
+            .get::<sqlx::types::Json<serde_json::Value>, _>("metrics")
+            .0;
+    assert_eq!(
+        metrics,
+        serde_json::json!({"reposts_wall": 7, "video_views": 9000})
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_versions_post_meta() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+    let posts = [(post_id, "-123_456".to_string())];
+    let mut meta = VkPostMeta {
+        owner_id: -123,
+        from_id: -123,
+        published_at: SystemClock.now().trunc_subsecs(0),
+        post_type: "post".to_string(),
+        text: "Original".to_string(),
+        attachments: serde_json::json!([]),
+        edited_at: None,
+        is_deleted: false,
+    };
+    vk.set_meta("-123_456", meta.clone());
+
+    // Unchanged content is stored once however many times the post is polled
+    for _ in 0..2 {
+        poll_post_stats(&pool, vk.as_ref(), &SystemClock, &posts)
+            .await
+            .expect("poll_post_stats should succeed");
+    }
+    assert_eq!(
+        get_post_meta_versions(&pool, post_id).await.unwrap().len(),
+        1
+    );
+
+    meta.text = "Edited".to_string();
+    meta.edited_at = Some(meta.published_at + chrono::Duration::minutes(5));
+    vk.set_meta("-123_456", meta.clone());
+    poll_post_stats(&pool, vk.as_ref(), &SystemClock, &posts)
+        .await
+        .expect("poll_post_stats should succeed");
+
+    let versions = get_post_meta_versions(&pool, post_id).await.unwrap();
+    assert_eq!(
+        versions.iter().map(|v| v.version).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(versions[0].meta.text, "Original");
+    assert_eq!(versions[1].meta, meta);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_dispatcher_skips_finished_and_recent_posts() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    // 1. Active post WITH recent polling - SHOULD NOT be polled
+    let active_with_recent_id = create_active_post(&pool, "-222_222").await;
 
-    // Add recent POST_INFO for the second post
     sqlx::query(
         r#"
         INSERT INTO POST_INFO (post_id, likes_count, comments_count, reposts_count, views_count, info_time)
@@ -204,7 +385,7 @@ async fn test_init_all_tasks_starts_tasks_for_active_posts() {
     .await
     .expect("Failed to create recent POST_INFO");
 
-    // 3. Expired post - SHOULD NOT start task
+    // 2. Expired post - SHOULD NOT be polled
     let expired_post_id = sqlx::query(
         r#"
         INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end)
@@ -218,100 +399,207 @@ async fn test_init_all_tasks_starts_tasks_for_active_posts() {
     .expect("Failed to create expired post")
     .get::<i32, _>("id");
 
-    // Create scheduler
-    let scheduler = tokio_cron_scheduler::JobScheduler::new()
-        .await
-        .expect("Failed to create scheduler");
-
-    // Call init_all_tasks
-    let result = init_all_tasks(&pool, &scheduler).await;
-    assert!(result.is_ok(), "init_all_tasks should succeed");
+    let dispatched = poll_due_posts(&pool, &vk).await;
+    assert_eq!(dispatched, 0, "Should NOT have dispatched anything");
 
-    // We can't directly count jobs in the scheduler, but we can verify the function
-    // completed successfully and printed the expected message
-    println!("✓ init_all_tasks completed successfully");
-    println!(
-        "  - Should have started task for post {} (active without recent polling)",
-        active_post_id
-    );
-    println!(
-        "  - Should NOT have started task for post {} (active with recent polling)",
-        active_with_recent_id
+    assert_eq!(
+        vk.call_count(),
+        0,
+        "Should NOT have called VK API without due posts"
     );
-    println!(
-        "  - Should NOT have started task for post {} (expired)",
-        expired_post_id
+    assert_eq!(count_post_info(&pool, active_with_recent_id).await, 1);
+    assert_eq!(
+        count_post_info(&pool, expired_post_id).await,
+        0,
+        "Should NOT have created POST_INFO for expired post"
     );
+
+    println!("✓ Dispatcher correctly skips expired and recently polled posts");
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
-async fn test_init_all_tasks_with_no_posts() {
+async fn test_dispatcher_skips_posts_in_flight() {
+    let vk = fake_vk();
+
     let pool = setup_test_db().await;
 
-    // Create scheduler
-    let scheduler = tokio_cron_scheduler::JobScheduler::new()
+    let post_id = create_active_post(&pool, "-666_666").await;
+
+    // Without free workers the first poll stays in flight
+    let dispatcher = Dispatcher::new(
+        pool.clone(),
+        vk.clone(),
+        Arc::new(SystemClock),
+        0,
+        JobRegistry::default(),
+    );
+
+    let first = dispatcher
+        .dispatch_due_posts()
         .await
-        .expect("Failed to create scheduler");
+        .expect("Failed to dispatch due posts");
+    assert_eq!(first.len(), 1, "Should have dispatched the due post");
 
-    // Call init_all_tasks with empty database
-    let result = init_all_tasks(&pool, &scheduler).await;
+    let second = dispatcher
+        .dispatch_due_posts()
+        .await
+        .expect("Failed to dispatch due posts");
     assert!(
-        result.is_ok(),
-        "init_all_tasks should succeed with no posts"
+        second.is_empty(),
+        "Should NOT dispatch post {} again while it is being polled",
+        post_id
     );
 
-    println!("✓ init_all_tasks handles empty database correctly");
+    for handle in first {
+        handle.abort();
+    }
+
+    assert_eq!(vk.call_count(), 0);
+
+    println!("✓ Dispatcher does not dispatch a post twice");
 }
 
 #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
-async fn test_poll_post_stats_multiple_calls_accumulate_data() {
-    vk_api::reset_counter();
+async fn test_dispatcher_redispatches_cancelled_posts() {
+    let vk = fake_vk();
 
     let pool = setup_test_db().await;
 
-    // Create a post (expires in 10 minutes to be safe)
-    let post_id = sqlx::query(
-        r#"
-        INSERT INTO POST (vk_id, dt_parse_begin, dt_parse_end)
-        VALUES ($1, CURRENT_TIMESTAMP - INTERVAL '60 seconds', CURRENT_TIMESTAMP + INTERVAL '600 seconds')
-        RETURNING id
-        "#
-    )
-    .bind("-444_444")
-    .fetch_one(&pool)
-    .await
-    .expect("Failed to create post")
-    .get::<i32, _>("id");
+    let post_id = create_active_post(&pool, "-888_888").await;
 
-    let scheduler = tokio_cron_scheduler::JobScheduler::new()
+    let registry = JobRegistry::default();
+    let dispatcher = Dispatcher::new(
+        pool.clone(),
+        vk.clone(),
+        Arc::new(SystemClock),
+        0,
+        registry.clone(),
+    );
+
+    let first = dispatcher
+        .dispatch_due_posts()
         .await
-        .expect("Failed to create scheduler");
+        .expect("Failed to dispatch due posts");
+    assert_eq!(first.len(), 1);
 
-    let job_id = uuid::Uuid::new_v4();
+    // Cancelling detaches the post from its stuck job
+    assert!(registry.cancel(post_id).is_some());
+    assert!(registry.cancel(post_id).is_none());
 
-    // Call poll_post_stats multiple times
-    for i in 1..=3 {
-        let result = poll_post_stats(&job_id, &scheduler, &pool, post_id).await;
-        assert!(result.is_ok(), "poll_post_stats call {} should succeed", i);
+    let second = dispatcher
+        .dispatch_due_posts()
+        .await
+        .expect("Failed to dispatch due posts");
+    assert_eq!(
+        second.len(),
+        1,
+        "Cancelled post should be dispatched again while its window is active"
+    );
 
-        // Small delay between calls
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    for handle in first.into_iter().chain(second) {
+        handle.abort();
     }
 
-    // Verify that 3 POST_INFO entries were created
-    let post_info_count = sqlx::query("SELECT COUNT(*) as count FROM POST_INFO WHERE post_id = $1")
+    println!("✓ Dispatcher frees cancelled posts from their jobs");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_skips_finished_posts() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-999_000").await;
+
+    // The post is stopped after it was dispatched but before the stats were saved
+    sqlx::query("UPDATE POST SET dt_parse_end = CURRENT_TIMESTAMP WHERE id = $1")
         .bind(post_id)
-        .fetch_one(&pool)
+        .execute(&pool)
         .await
-        .expect("Failed to query POST_INFO")
-        .get::<i64, _>("count");
+        .expect("Failed to finish post");
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[(post_id, "-999_000".to_string())],
+    )
+    .await;
+    assert!(result.is_ok(), "poll_post_stats should succeed");
+
+    assert_eq!(
+        count_post_info(&pool, post_id).await,
+        0,
+        "Should NOT have saved stats for a stopped post"
+    );
+
+    println!("✓ poll_post_stats does not save stats for stopped posts");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_init_all_tasks_polls_active_posts() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-777_777").await;
+
+    // The dispatcher ticks immediately after start
+    let client: Arc<dyn VkClient> = vk.clone();
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let scheduler = IntervalScheduler::new(std::time::Duration::from_secs(2));
+    let dispatcher = init_all_tasks(&pool, &client, &clock, &JobRegistry::default(), &scheduler);
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+    dispatcher.abort();
 
     assert_eq!(
-        post_info_count, 3,
+        count_post_info(&pool, post_id).await,
+        1,
+        "Should have polled the active post on the first tick"
+    );
+
+    println!("✓ init_all_tasks starts polling active posts");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_dispatcher_multiple_ticks_accumulate_data() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-444_444").await;
+    vk.set_replies(
+        "-444_444",
+        vec![
+            FakeReply::Stats(stats(1)),
+            FakeReply::Stats(stats(2)),
+            FakeReply::Stats(stats(3)),
+        ],
+    );
+
+    // Run the dispatcher tick multiple times
+    for i in 1..=3 {
+        let dispatched = poll_due_posts(&pool, &vk).await;
+        assert_eq!(dispatched, 1, "Tick {} should have dispatched the post", i);
+
+        // Move the snapshots back in time so the post is due again on the next call
+        sqlx::query(
+            "UPDATE POST_INFO SET info_time = info_time - INTERVAL '60 seconds' WHERE post_id = $1",
+        )
+        .bind(post_id)
+        .execute(&pool)
+        .await
+        .expect("Failed to update POST_INFO");
+    }
+
+    // Verify that 3 POST_INFO entries were created
+    assert_eq!(
+        count_post_info(&pool, post_id).await,
+        3,
         "Should have created 3 POST_INFO entries"
     );
 
-    // Verify the data is increasing (mock returns incrementing values)
+    // Verify the data is increasing (the fake returns incrementing values)
     let post_infos = sqlx::query(
         "SELECT likes_count, comments_count, reposts_count, views_count FROM POST_INFO WHERE post_id = $1 ORDER BY info_time ASC"
     )
@@ -321,16 +609,694 @@ async fn test_poll_post_stats_multiple_calls_accumulate_data() {
     .expect("Failed to fetch POST_INFO");
 
     // First call: likes=3, comments=2, reposts=1, views=4
-    assert_eq!(post_infos[0].get::<i32, _>("likes_count"), 3);
-    assert_eq!(post_infos[0].get::<i32, _>("comments_count"), 2);
+    assert_eq!(post_infos[0].get::<i64, _>("likes_count"), 3);
+    assert_eq!(post_infos[0].get::<i64, _>("comments_count"), 2);
 
     // Second call: likes=6, comments=4, reposts=2, views=8
-    assert_eq!(post_infos[1].get::<i32, _>("likes_count"), 6);
-    assert_eq!(post_infos[1].get::<i32, _>("comments_count"), 4);
+    assert_eq!(post_infos[1].get::<i64, _>("likes_count"), 6);
+    assert_eq!(post_infos[1].get::<i64, _>("comments_count"), 4);
 
     // Third call: likes=9, comments=6, reposts=3, views=12
-    assert_eq!(post_infos[2].get::<i32, _>("likes_count"), 9);
-    assert_eq!(post_infos[2].get::<i32, _>("comments_count"), 6);
+    assert_eq!(post_infos[2].get::<i64, _>("likes_count"), 9);
+    assert_eq!(post_infos[2].get::<i64, _>("comments_count"), 6);
+
+    println!("✓ Polling correctly accumulates data over multiple ticks");
+}
+
+async fn is_post_active(pool: &sqlx::PgPool, post_id: i32) -> bool {
+    sqlx::query("SELECT dt_parse_end > CURRENT_TIMESTAMP AS active FROM POST WHERE id = $1")
+        .bind(post_id)
+        .fetch_one(pool)
+        .await
+        .expect("Failed to query POST")
+        .get::<bool, _>("active")
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_stops_inaccessible_post() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+    let denied_post_id = create_active_post(&pool, "-403_403").await;
+    vk.set_replies(
+        "-403_403",
+        vec![FakeReply::Error(VkError::AccessDenied(
+            "Access denied".to_string(),
+        ))],
+    );
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[
+            (post_id, "-123_456".to_string()),
+            (denied_post_id, "-403_403".to_string()),
+        ],
+    )
+    .await;
+    assert!(result.is_ok(), "Access denied should not fail the poll");
+
+    // The batch is retried post by post, so the accessible post is still saved
+    assert_eq!(count_post_info(&pool, post_id).await, 1);
+    assert_eq!(count_post_info(&pool, denied_post_id).await, 0);
+
+    assert!(is_post_active(&pool, post_id).await);
+    assert!(
+        !is_post_active(&pool, denied_post_id).await,
+        "Inaccessible post should be finished"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_failing_post_does_not_stop_the_others() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let first_post_id = create_active_post(&pool, "-1_1").await;
+    let denied_post_id = create_active_post(&pool, "-403_403").await;
+    let failing_post_id = create_active_post(&pool, "-400_400").await;
+    let last_post_id = create_active_post(&pool, "-2_2").await;
+    vk.set_replies(
+        "-403_403",
+        vec![FakeReply::Error(VkError::AccessDenied(
+            "Access denied".to_string(),
+        ))],
+    );
+    // Polled alone, this post fails with an error that is neither transient nor about the post
+    vk.set_replies(
+        "-400_400",
+        vec![
+            FakeReply::Stats(stats(1)),
+            FakeReply::Error(VkError::Other {
+                code: 100,
+                message: "One of the parameters specified was missing or invalid".to_string(),
+            }),
+        ],
+    );
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[
+            (first_post_id, "-1_1".to_string()),
+            (denied_post_id, "-403_403".to_string()),
+            (failing_post_id, "-400_400".to_string()),
+            (last_post_id, "-2_2".to_string()),
+        ],
+    )
+    .await;
+    assert!(result.is_ok(), "A failing post should not fail the batch");
+
+    // Every post after the failing one is still polled
+    assert_eq!(vk.call_count(), 5);
+    assert_eq!(count_post_info(&pool, first_post_id).await, 1);
+    assert_eq!(count_post_info(&pool, failing_post_id).await, 0);
+    assert_eq!(count_post_info(&pool, last_post_id).await, 1);
+    assert!(!is_post_active(&pool, denied_post_id).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_rate_limited_keeps_posts_due() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+    let limited_post_id = create_active_post(&pool, "-429_429").await;
+    vk.set_replies(
+        "-429_429",
+        vec![FakeReply::Error(VkError::TooManyRequests(
+            "Too many requests".to_string(),
+        ))],
+    );
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[
+            (post_id, "-123_456".to_string()),
+            (limited_post_id, "-429_429".to_string()),
+        ],
+    )
+    .await;
+    assert!(result.is_ok(), "Rate limit should not fail the poll");
+
+    assert_eq!(count_post_info(&pool, post_id).await, 0);
+    assert_eq!(count_post_info(&pool, limited_post_id).await, 0);
+
+    // Both posts stay active and are picked up on the next tick
+    assert!(is_post_active(&pool, post_id).await);
+    assert!(is_post_active(&pool, limited_post_id).await);
+    assert_eq!(
+        db_commands::get_posts_needing_polling(&pool, SystemClock.now())
+            .await
+            .expect("Failed to get posts needing polling")
+            .len(),
+        2
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_auth_failed_is_fatal() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-401_401").await;
+    vk.set_replies(
+        "-401_401",
+        vec![FakeReply::Error(VkError::AuthFailed(
+            "Invalid access token".to_string(),
+        ))],
+    );
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[(post_id, "-401_401".to_string())],
+    )
+    .await;
+
+    match result {
+        Err(errors::AppError::VkApi(e)) => {
+            assert!(!e.is_retryable());
+            assert!(!e.is_post_error());
+        }
+        _ => panic!("Expected VK auth error"),
+    }
+
+    // A bad token is not the post's fault, so the post keeps its window
+    assert_eq!(count_post_info(&pool, post_id).await, 0);
+    assert!(is_post_active(&pool, post_id).await);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_poll_post_stats_records_skipped_poll() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+    let late_post_id = create_active_post(&pool, "-408_408").await;
+    vk.set_replies(
+        "-408_408",
+        vec![FakeReply::Throttled(
+            "No free VK request slot within 2 seconds".to_string(),
+        )],
+    );
+
+    let result = poll_post_stats(
+        &pool,
+        vk.as_ref(),
+        &SystemClock,
+        &[
+            (post_id, "-123_456".to_string()),
+            (late_post_id, "-408_408".to_string()),
+        ],
+    )
+    .await;
+    assert!(result.is_ok(), "A skipped poll should not fail");
+
+    // Nothing was polled, both posts of the batch are marked as skipped
+    assert_eq!(count_post_info(&pool, post_id).await, 0);
+    let skips = sqlx::query("SELECT post_id, reason FROM POLL_SKIP ORDER BY post_id")
+        .fetch_all(&pool)
+        .await
+        .expect("Failed to query POLL_SKIP");
+
+    assert_eq!(skips.len(), 2);
+    assert_eq!(skips[0].get::<i32, _>("post_id"), post_id);
+    assert_eq!(skips[1].get::<i32, _>("post_id"), late_post_id);
+    assert!(
+        skips[0]
+            .get::<String, _>("reason")
+            .contains("No free VK request slot")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_dispatcher_skips_posts_during_slow_vk_call() {
+    let vk = fake_vk();
+    vk.set_latency(std::time::Duration::from_millis(300));
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+
+    let dispatcher = Dispatcher::new(
+        pool.clone(),
+        vk.clone(),
+        Arc::new(SystemClock),
+        2,
+        JobRegistry::default(),
+    );
+    let first = dispatcher
+        .dispatch_due_posts()
+        .await
+        .expect("Failed to dispatch due posts");
+
+    // The next tick comes while VK is still answering the first request
+    let second = dispatcher
+        .dispatch_due_posts()
+        .await
+        .expect("Failed to dispatch due posts");
+    assert!(second.is_empty(), "Post should not be dispatched twice");
+
+    for handle in first {
+        handle.await.expect("Polling worker panicked");
+    }
+
+    assert_eq!(vk.call_count(), 1);
+    assert_eq!(count_post_info(&pool, post_id).await, 1);
+}
+
+fn comment(id: i64, parent_id: Option<i64>) -> VkComment {
+    VkComment {
+        id,
+        from_id: id * 10,
+        published_at: SystemClock.now().trunc_subsecs(0),
+        text: format!("Comment {}", id),
+        likes_count: 0,
+        parent_id,
+        thread_count: 0,
+    }
+}
+
+async fn comment_ids(pool: &sqlx::PgPool, post_id: i32) -> Vec<(i64, Option<i64>)> {
+    sqlx::query(
+        "SELECT comment_id, parent_id FROM POST_COMMENT WHERE post_id = $1 ORDER BY comment_id",
+    )
+    .bind(post_id)
+    .fetch_all(pool)
+    .await
+    .expect("Failed to query POST_COMMENT")
+    .iter()
+    .map(|row| (row.get("comment_id"), row.get("parent_id")))
+    .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_collect_post_comments_pages_and_expands_threads() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+
+    // More top-level comments than fit on one page, one thread with a reply to a reply
+    let mut comments: Vec<VkComment> = (1..=150).map(|id| comment(id, None)).collect();
+    comments.push(comment(1001, Some(7)));
+    comments.push(comment(1002, Some(1001)));
+    vk.set_comments("-123_456", comments);
+
+    let new = collect_post_comments(&pool, vk.as_ref(), &SystemClock, post_id, "-123_456")
+        .await
+        .expect("collect_post_comments should succeed");
+    assert_eq!(new, 152);
+
+    assert_eq!(
+        vk.comment_calls(),
+        vec![
+            ("-123_456".to_string(), None, 0),
+            ("-123_456".to_string(), None, 100),
+            ("-123_456".to_string(), Some(7), 0),
+        ]
+    );
+
+    let saved = comment_ids(&pool, post_id).await;
+    assert_eq!(saved.len(), 152);
+    assert_eq!(saved[150], (1001, Some(7)));
+    assert_eq!(saved[151], (1002, Some(1001)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_collect_post_comments_pages_over_deleted_comments() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+
+    // The whole first page is deleted, the real comments are on the second and third ones
+    vk.set_comments("-123_456", (1..=250).map(|id| comment(id, None)).collect());
+    vk.delete_comments(
+        "-123_456",
+        &(1..=100).chain(150..=160).collect::<Vec<i64>>(),
+    );
+
+    let new = collect_post_comments(&pool, vk.as_ref(), &SystemClock, post_id, "-123_456")
+        .await
+        .expect("collect_post_comments should succeed");
+    assert_eq!(new, 139);
+
+    // Offsets follow what VK returned, not what was kept
+    assert_eq!(
+        vk.comment_calls(),
+        vec![
+            ("-123_456".to_string(), None, 0),
+            ("-123_456".to_string(), None, 100),
+            ("-123_456".to_string(), None, 200),
+        ]
+    );
+
+    let saved = comment_ids(&pool, post_id).await;
+    assert_eq!(saved.first(), Some(&(101, None)));
+    assert_eq!(saved.last(), Some(&(250, None)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_collect_post_comments_deduplicates_by_id() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+    vk.set_comments("-123_456", vec![comment(1, None), comment(2, None)]);
+
+    collect_post_comments(&pool, vk.as_ref(), &SystemClock, post_id, "-123_456")
+        .await
+        .expect("collect_post_comments should succeed");
+
+    // Known comments are refreshed, only the third one is new
+    let mut liked = comment(1, None);
+    liked.likes_count = 5;
+    vk.set_comments("-123_456", vec![liked, comment(2, None), comment(3, None)]);
+
+    let new = collect_post_comments(&pool, vk.as_ref(), &SystemClock, post_id, "-123_456")
+        .await
+        .expect("collect_post_comments should succeed");
+    assert_eq!(new, 1);
+    assert_eq!(comment_ids(&pool, post_id).await.len(), 3);
+
+    let likes: i64 =
+        sqlx::query("SELECT likes_count FROM POST_COMMENT WHERE post_id = $1 AND comment_id = 1")
+            .bind(post_id)
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to query POST_COMMENT")
+            .get("likes_count");
+    assert_eq!(likes, 5);
+}
+
+fn liker(user_id: i64) -> VkAudienceEntry {
+    VkAudienceEntry {
+        user_id,
+        engaged_at: None,
+    }
+}
+
+async fn audience_users(pool: &sqlx::PgPool, post_id: i32, kind: &str) -> Vec<i64> {
+    sqlx::query(
+        "SELECT user_id FROM POST_AUDIENCE WHERE post_id = $1 AND kind = $2 ORDER BY user_id",
+    )
+    .bind(post_id)
+    .bind(kind)
+    .fetch_all(pool)
+    .await
+    .expect("Failed to query POST_AUDIENCE")
+    .iter()
+    .map(|row| row.get("user_id"))
+    .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_collect_post_audience_pages_likes_and_reposts() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+
+    // More likes than fit on one page, reposts are paged until VK returns an empty page
+    vk.set_audience(
+        "-123_456",
+        AudienceKind::Like,
+        (1..=1500).map(liker).collect(),
+    );
+    let reposted_at = SystemClock.now().trunc_subsecs(0);
+    vk.set_audience(
+        "-123_456",
+        AudienceKind::Repost,
+        vec![VkAudienceEntry {
+            user_id: 42,
+            engaged_at: Some(reposted_at),
+        }],
+    );
+
+    let new = collect_post_audience(&pool, vk.as_ref(), &SystemClock, post_id, "-123_456")
+        .await
+        .expect("collect_post_audience should succeed");
+    assert_eq!(new, 1501);
+
+    assert_eq!(
+        vk.audience_calls(),
+        vec![
+            ("-123_456".to_string(), AudienceKind::Like, 0),
+            ("-123_456".to_string(), AudienceKind::Like, 1000),
+            ("-123_456".to_string(), AudienceKind::Repost, 0),
+            ("-123_456".to_string(), AudienceKind::Repost, 1),
+        ]
+    );
+
+    assert_eq!(audience_users(&pool, post_id, "like").await.len(), 1500);
+    assert_eq!(audience_users(&pool, post_id, "repost").await, vec![42]);
+
+    let engaged_at: Option<chrono::DateTime<chrono::Utc>> =
+        sqlx::query("SELECT engaged_at FROM POST_AUDIENCE WHERE post_id = $1 AND kind = 'repost'")
+            .bind(post_id)
+            .fetch_one(&pool)
+            .await
+            .expect("Failed to query POST_AUDIENCE")
+            .get("engaged_at");
+    assert_eq!(engaged_at, Some(reposted_at));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_collect_post_audience_pages_over_unreadable_entries() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+
+    // Nothing on the first page of likes can be read, and the only repost on its page neither
+    vk.set_audience(
+        "-123_456",
+        AudienceKind::Like,
+        (1..=1500).map(liker).collect(),
+    );
+    vk.set_unreadable_audience(
+        "-123_456",
+        AudienceKind::Like,
+        &(1..=1000).collect::<Vec<i64>>(),
+    );
+    vk.set_audience("-123_456", AudienceKind::Repost, vec![liker(7), liker(8)]);
+    vk.set_unreadable_audience("-123_456", AudienceKind::Repost, &[7]);
+
+    let new = collect_post_audience(&pool, vk.as_ref(), &SystemClock, post_id, "-123_456")
+        .await
+        .expect("collect_post_audience should succeed");
+    assert_eq!(new, 501);
+
+    // Offsets follow what VK returned, not what was kept
+    assert_eq!(
+        vk.audience_calls(),
+        vec![
+            ("-123_456".to_string(), AudienceKind::Like, 0),
+            ("-123_456".to_string(), AudienceKind::Like, 1000),
+            ("-123_456".to_string(), AudienceKind::Repost, 0),
+            ("-123_456".to_string(), AudienceKind::Repost, 2),
+        ]
+    );
+    assert_eq!(audience_users(&pool, post_id, "repost").await, vec![8]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_collect_post_audience_keeps_first_seen() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let post_id = create_active_post(&pool, "-123_456").await;
+    vk.set_audience("-123_456", AudienceKind::Like, vec![liker(1), liker(2)]);
+
+    let first_run = SystemClock.now().trunc_subsecs(6);
+    let clock = ManualClock::new(first_run);
+    collect_post_audience(&pool, vk.as_ref(), &clock, post_id, "-123_456")
+        .await
+        .expect("collect_post_audience should succeed");
+
+    // A like that was taken back stays in the table, only the third user is new
+    vk.set_audience("-123_456", AudienceKind::Like, vec![liker(2), liker(3)]);
+    let second_run = first_run + chrono::Duration::hours(1);
+    clock.set(second_run);
+
+    let new = collect_post_audience(&pool, vk.as_ref(), &clock, post_id, "-123_456")
+        .await
+        .expect("collect_post_audience should succeed");
+    assert_eq!(new, 1);
+    assert_eq!(audience_users(&pool, post_id, "like").await, vec![1, 2, 3]);
+
+    let seen: Vec<(i64, chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)> =
+        sqlx::query(
+            "SELECT user_id, first_seen_at, last_seen_at FROM POST_AUDIENCE WHERE post_id = $1 ORDER BY user_id",
+        )
+        .bind(post_id)
+        .fetch_all(&pool)
+        .await
+        .expect("Failed to query POST_AUDIENCE")
+        .iter()
+        .map(|row| (row.get("user_id"), row.get("first_seen_at"), row.get("last_seen_at")))
+        .collect();
+    assert_eq!(
+        seen,
+        vec![
+            (1, first_run, first_run),
+            (2, first_run, second_run),
+            (3, second_run, second_run),
+        ]
+    );
+}
+
+fn wall_post(id: i64, published_at: chrono::DateTime<chrono::Utc>, text: &str) -> VkWallPost {
+    VkWallPost {
+        vk_id: format!("-1_{}", id),
+        published_at,
+        text: text.to_string(),
+        is_pinned: false,
+    }
+}
+
+async fn post_vk_ids(pool: &sqlx::PgPool) -> Vec<String> {
+    sqlx::query("SELECT vk_id FROM POST ORDER BY vk_id")
+        .fetch_all(pool)
+        .await
+        .expect("Failed to query POST")
+        .iter()
+        .map(|row| row.get("vk_id"))
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_check_wall_enrols_new_posts_that_pass_filters() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let created = SystemClock.now().trunc_subsecs(0);
+    let clock = ManualClock::new(created);
+    let watcher = create_wall_watcher(
+        &pool,
+        &NewWallWatcher {
+            owner_id: -1,
+            interval_seconds: 600,
+            duration_seconds: 3600,
+            track_audience: true,
+            include_pinned: false,
+            min_age_seconds: 300,
+            keywords: vec!["выборы".to_string()],
+        },
+        created,
+    )
+    .await
+    .expect("Failed to create watcher");
+
+    let minute = chrono::Duration::minutes(1);
+    let mut pinned = wall_post(9, created + minute, "Выборы: закреплено");
+    pinned.is_pinned = true;
+    vk.set_wall(
+        -1,
+        vec![
+            pinned,
+            wall_post(8, created + minute * 9, "Выборы, свежий пост"),
+            wall_post(7, created + minute * 2, "Про погоду"),
+            wall_post(6, created + minute, "ВЫБОРЫ в пятницу"),
+            // Published before the watcher was created
+            wall_post(5, created - minute, "Выборы в прошлом"),
+        ],
+    );
+
+    // Ten minutes after creation: the post published a minute ago is still too young
+    clock.set(created + minute * 10);
+    let enrolled = check_wall(&pool, vk.as_ref(), &clock, &watcher)
+        .await
+        .expect("check_wall should succeed");
+    assert_eq!(enrolled, 1);
+    assert_eq!(post_vk_ids(&pool).await, vec!["-1_6"]);
+
+    // Paging stopped at the first post older than the watcher
+    assert_eq!(vk.wall_calls(), vec![(-1, 0)]);
+
+    let track_audience: bool = sqlx::query("SELECT track_audience FROM POST WHERE vk_id = '-1_6'")
+        .fetch_one(&pool)
+        .await
+        .expect("Failed to query POST")
+        .get("track_audience");
+    assert!(track_audience);
+
+    // Once old enough the young post is enrolled, the others are not looked at again
+    clock.set(created + minute * 20);
+    let watcher = db_commands::get_wall_watchers(&pool)
+        .await
+        .expect("Failed to read watchers")
+        .remove(0);
+    let enrolled = check_wall(&pool, vk.as_ref(), &clock, &watcher)
+        .await
+        .expect("check_wall should succeed");
+    assert_eq!(enrolled, 1);
+    assert_eq!(post_vk_ids(&pool).await, vec!["-1_6", "-1_8"]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+async fn test_check_wall_pages_over_unreadable_posts() {
+    let vk = fake_vk();
+
+    let pool = setup_test_db().await;
+
+    let created = SystemClock.now().trunc_subsecs(0);
+    let clock = ManualClock::new(created);
+    let watcher = create_wall_watcher(
+        &pool,
+        &NewWallWatcher {
+            owner_id: -1,
+            interval_seconds: 600,
+            duration_seconds: 3600,
+            track_audience: false,
+            include_pinned: false,
+            min_age_seconds: 0,
+            keywords: Vec::new(),
+        },
+        created,
+    )
+    .await
+    .expect("Failed to create watcher");
+
+    // The whole first page can't be read, the new posts on the second one still count
+    let minute = chrono::Duration::minutes(1);
+    let posts: Vec<VkWallPost> = (1..=150)
+        .rev()
+        .map(|id| wall_post(id, created + minute * id as i32, "Пост"))
+        .collect();
+    let unreadable: Vec<String> = posts[..100].iter().map(|post| post.vk_id.clone()).collect();
+    vk.set_wall(-1, posts);
+    vk.set_unreadable_wall_posts(
+        -1,
+        &unreadable.iter().map(String::as_str).collect::<Vec<&str>>(),
+    );
+
+    clock.set(created + minute * 200);
+    let enrolled = check_wall(&pool, vk.as_ref(), &clock, &watcher)
+        .await
+        .expect("check_wall should succeed");
+    assert_eq!(enrolled, 50);
 
-    println!("✓ poll_post_stats correctly accumulates data over multiple calls");
+    // Offsets follow what VK returned, not what was kept
+    assert_eq!(vk.wall_calls(), vec![(-1, 0), (-1, 100)]);
 }
@@ -1,4 +1,4 @@
-use chrono::NaiveDateTime;
+use chrono::{DateTime, Utc};
 use sqlx::postgres::PgPoolOptions;
 use sqlx::{PgPool, Row};
 
@@ -21,10 +21,12 @@ pub async fn setup_test_db() -> sqlx::PgPool {
         .expect("Failed to run migrations");
 
     // Clean up existing data
-    sqlx::query("TRUNCATE TABLE POST_INFO, POST RESTART IDENTITY CASCADE")
-        .execute(&pool)
-        .await
-        .expect("Failed to clean test database");
+    sqlx::query(
+        "TRUNCATE TABLE POST_INFO, POST, WALL_WATCHER, IDEMPOTENCY_KEY RESTART IDENTITY CASCADE",
+    )
+    .execute(&pool)
+    .await
+    .expect("Failed to clean test database");
 
     pool
 }
@@ -34,8 +36,8 @@ pub async fn setup_test_db() -> sqlx::PgPool {
 pub async fn insert_post(
     pool: &PgPool,
     vk_id: &str,
-    dt_parse_begin: NaiveDateTime,
-    dt_parse_end: NaiveDateTime,
+    dt_parse_begin: DateTime<Utc>,
+    dt_parse_end: DateTime<Utc>,
 ) -> Result<i32, sqlx::Error> {
     let result = sqlx::query(
         r#"
@@ -57,11 +59,11 @@ pub async fn insert_post(
 pub async fn insert_post_info(
     pool: &PgPool,
     post_id: i32,
-    likes_count: i32,
-    comments_count: i32,
-    reposts_count: i32,
-    views_count: i32,
-    info_time: NaiveDateTime,
+    likes_count: i64,
+    comments_count: i64,
+    reposts_count: i64,
+    views_count: i64,
+    info_time: DateTime<Utc>,
 ) -> Result<(), sqlx::Error> {
     sqlx::query(
         r#"
@@ -85,7 +87,7 @@ pub async fn insert_post_info(
 pub async fn get_post_by_id(
     pool: &PgPool,
     post_id: i32,
-) -> Result<Option<(i32, String, NaiveDateTime, NaiveDateTime)>, sqlx::Error> {
+) -> Result<Option<(i32, String, DateTime<Utc>, DateTime<Utc>)>, sqlx::Error> {
     let result = sqlx::query(
         r#"
         SELECT id, vk_id, dt_parse_begin, dt_parse_end
@@ -111,7 +113,7 @@ pub async fn get_post_by_id(
 pub async fn get_post_info_by_post_id(
     pool: &PgPool,
     post_id: i32,
-) -> Result<Vec<(i32, i32, i32, i32, NaiveDateTime)>, sqlx::Error> {
+) -> Result<Vec<(i64, i64, i64, i64, DateTime<Utc>)>, sqlx::Error> {
     let results = sqlx::query(
         r#"
         SELECT likes_count, comments_count, reposts_count, views_count, info_time
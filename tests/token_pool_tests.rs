@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+
+use errors::{AppError, VkError};
+use models::TokenRotation;
+use token_pool::{TokenPool, TokenStatus};
+
+fn create_pool(rotation: TokenRotation, cooldown: Duration) -> TokenPool {
+    TokenPool::new(
+        vec![
+            ("token-a".to_string(), 3.0),
+            ("token-b".to_string(), 3.0),
+            ("token-c".to_string(), 5.0),
+        ],
+        rotation,
+        cooldown,
+    )
+}
+
+// Checks out a token and reports the given outcome, returns the token
+fn use_token(pool: &TokenPool, result: Result<(), AppError>) -> String {
+    let lease = pool.checkout().expect("Expected a usable token");
+    pool.report(&lease, &result);
+    lease.token
+}
+
+#[test]
+fn test_round_robin_rotation() {
+    let pool = create_pool(TokenRotation::RoundRobin, Duration::from_secs(60));
+
+    let used: Vec<String> = (0..4).map(|_| use_token(&pool, Ok(()))).collect();
+
+    assert_eq!(used, vec!["token-a", "token-b", "token-c", "token-a"]);
+}
+
+#[test]
+fn test_least_recently_used_rotation() {
+    let pool = create_pool(TokenRotation::LeastRecentlyUsed, Duration::from_secs(60));
+
+    assert_eq!(use_token(&pool, Ok(())), "token-a");
+    assert_eq!(use_token(&pool, Ok(())), "token-b");
+    assert_eq!(use_token(&pool, Ok(())), "token-c");
+    std::thread::sleep(Duration::from_millis(5));
+
+    // token-a was used longest ago
+    assert_eq!(use_token(&pool, Ok(())), "token-a");
+}
+
+#[test]
+fn test_rate_limited_token_cools_down() {
+    let pool = create_pool(TokenRotation::RoundRobin, Duration::from_millis(100));
+
+    let rate_limited = Err(VkError::TooManyRequests("Too many requests".to_string()).into());
+    assert_eq!(use_token(&pool, rate_limited), "token-a");
+
+    // token-a is skipped while cooling down
+    let used: Vec<String> = (0..3).map(|_| use_token(&pool, Ok(()))).collect();
+    assert_eq!(used, vec!["token-b", "token-c", "token-b"]);
+    assert_eq!(pool.stats()[0].status, TokenStatus::CoolingDown);
+
+    std::thread::sleep(Duration::from_millis(150));
+    assert_eq!(pool.stats()[0].status, TokenStatus::Active);
+    assert_eq!(use_token(&pool, Ok(())), "token-c");
+    assert_eq!(use_token(&pool, Ok(())), "token-a");
+}
+
+#[test]
+fn test_rejected_token_is_revoked() {
+    let pool = create_pool(TokenRotation::RoundRobin, Duration::from_secs(60));
+
+    for _ in 0..3 {
+        let rejected = Err(VkError::AuthFailed("Invalid token".to_string()).into());
+        use_token(&pool, rejected);
+    }
+
+    assert!(!pool.has_usable_token());
+    assert!(matches!(
+        pool.checkout(),
+        Err(AppError::VkApi(VkError::AuthFailed(_)))
+    ));
+    assert!(
+        pool.stats()
+            .iter()
+            .all(|token| token.status == TokenStatus::Revoked)
+    );
+}
+
+#[test]
+fn test_all_tokens_cooling_down_is_rate_limited() {
+    let pool = create_pool(TokenRotation::RoundRobin, Duration::from_secs(60));
+
+    for _ in 0..3 {
+        let flooded = Err(VkError::FloodControl("Flood control".to_string()).into());
+        use_token(&pool, flooded);
+    }
+
+    // The client is told to come back once the first token is usable again
+    match pool.checkout() {
+        Err(AppError::RateLimited { retry_after, .. }) => {
+            assert!(
+                retry_after > Duration::from_secs(59) && retry_after <= Duration::from_secs(60)
+            );
+        }
+        _ => panic!("Expected a rate limit error"),
+    }
+}
+
+#[test]
+fn test_stats_count_uses_and_failures() {
+    let pool = create_pool(TokenRotation::RoundRobin, Duration::from_secs(60));
+
+    use_token(&pool, Ok(()));
+    use_token(&pool, Err(AppError::Vk("Request failed".to_string())));
+    use_token(&pool, Ok(()));
+    use_token(&pool, Ok(()));
+
+    let stats = pool.stats();
+    assert_eq!(stats[0].token, "toke...");
+    assert_eq!((stats[0].uses, stats[0].failures), (2, 0));
+    assert_eq!((stats[1].uses, stats[1].failures), (1, 1));
+    assert_eq!(stats[1].status, TokenStatus::Active);
+    assert_eq!(stats[2].rate_per_second, 5.0);
+}
+
+#[test]
+fn test_empty_pool_reports_missing_configuration() {
+    let pool = TokenPool::new(
+        Vec::new(),
+        TokenRotation::RoundRobin,
+        Duration::from_secs(60),
+    );
+
+    assert!(matches!(pool.checkout(), Err(AppError::Vk(_))));
+}
@@ -0,0 +1,183 @@
+use rstest::rstest;
+
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+
+use chrono_tz::Tz;
+use models::VkPostStats;
+use utils::{
+    format_timestamp, parse_idempotency_key, parse_iso8601_duration, parse_timestamp,
+    parse_timezone, post_counters, resolve_duration_seconds,
+};
+
+#[rstest]
+#[case::seconds("PT45S", 45)]
+#[case::minutes("PT30M", 30 * 60)]
+#[case::hours("PT6H", 6 * 60 * 60)]
+#[case::days("P2D", 2 * 24 * 60 * 60)]
+#[case::weeks("P1W", 7 * 24 * 60 * 60)]
+#[case::combined("P1DT2H3M4S", 24 * 60 * 60 + 2 * 60 * 60 + 3 * 60 + 4)]
+#[case::zero("PT0S", 0)]
+fn test_parse_iso8601_duration(#[case] value: &str, #[case] expected: i64) {
+    assert_eq!(parse_iso8601_duration(value), Ok(expected));
+}
+
+#[rstest]
+#[case::empty("")]
+#[case::no_prefix("T6H")]
+#[case::no_components("P")]
+#[case::empty_time("PT")]
+#[case::missing_unit("PT6")]
+#[case::time_unit_without_t("P6H")]
+#[case::unknown_unit("PT6X")]
+#[case::fraction("PT1.5S")]
+#[case::years("P1Y")]
+#[case::months("P1M")]
+#[case::overflow("P9999999999999999W")]
+#[case::dangling_t("P1DT")]
+#[case::repeated_unit("PT1H1H")]
+#[case::out_of_order_units("PT1S1H")]
+#[case::days_before_weeks("P1D1W")]
+fn test_parse_iso8601_duration_rejects_invalid(#[case] value: &str) {
+    assert!(
+        parse_iso8601_duration(value).is_err(),
+        "Expected '{}' to be rejected",
+        value
+    );
+}
+
+#[rstest]
+#[case::unset(None, Ok(300))]
+#[case::within_limits(Some("86400"), Ok(86400))]
+#[case::below_minimum(Some("30"), Err(()))]
+#[case::above_maximum(Some("31536000"), Err(()))]
+fn test_default_duration_is_checked(
+    #[case] period: Option<&str>,
+    #[case] expected: Result<i64, ()>,
+) {
+    // Tests run one at a time, see .cargo/config.toml
+    unsafe {
+        std::env::remove_var("POOLING_MIN_DURATION_SECONDS");
+        std::env::remove_var("POOLING_MAX_DURATION_SECONDS");
+        match period {
+            Some(period) => std::env::set_var("POOLING_PERIOD_SECONDS", period),
+            None => std::env::remove_var("POOLING_PERIOD_SECONDS"),
+        }
+    }
+
+    let seconds = resolve_duration_seconds(None);
+
+    unsafe { std::env::remove_var("POOLING_PERIOD_SECONDS") };
+    assert_eq!(seconds.map_err(|_| ()), expected);
+}
+
+#[rstest]
+#[case::default(None, "2026-02-25T21:52:04.123+00:00")]
+#[case::seconds(Some("s"), "2026-02-25T21:52:04+00:00")]
+#[case::milliseconds(Some("ms"), "2026-02-25T21:52:04.123+00:00")]
+#[case::microseconds(Some("us"), "2026-02-25T21:52:04.123456+00:00")]
+fn test_format_timestamp_precision(#[case] precision: Option<&str>, #[case] expected: &str) {
+    // Tests run one at a time, see .cargo/config.toml
+    unsafe {
+        match precision {
+            Some(precision) => std::env::set_var("TIMESTAMP_PRECISION", precision),
+            None => std::env::remove_var("TIMESTAMP_PRECISION"),
+        }
+    }
+
+    let dt = parse_timestamp("2026-02-25T21:52:04.123456", Tz::UTC).unwrap();
+    let formatted = format_timestamp(&dt, Tz::UTC);
+
+    unsafe { std::env::remove_var("TIMESTAMP_PRECISION") };
+    assert_eq!(formatted, expected);
+}
+
+#[rstest]
+#[case::seconds("2026-02-25T21:52:04", true)]
+#[case::milliseconds("2026-02-25T21:52:04.123", true)]
+#[case::utc_designator("2026-02-25T21:52:04Z", true)]
+#[case::offset("2026-02-25T21:52:04+03:00", true)]
+#[case::date_only("2026-02-25", false)]
+#[case::space_separator("2026-02-25 21:52:04", false)]
+fn test_parse_timestamp(#[case] value: &str, #[case] valid: bool) {
+    assert_eq!(parse_timestamp(value, Tz::UTC).is_some(), valid);
+}
+
+#[rstest]
+// Without an offset the time is read in the requested zone
+#[case::naive_in_zone("2026-02-25T21:52:04", "Europe/Moscow", "2026-02-25T18:52:04Z")]
+// An explicit offset wins over the requested zone
+#[case::offset_ignores_zone("2026-02-25T21:52:04+01:00", "Europe/Moscow", "2026-02-25T20:52:04Z")]
+// 02:30 does not exist on the spring DST switch
+#[case::dst_gap("2026-03-29T02:30:00", "Europe/Berlin", "")]
+// 02:30 happens twice on the autumn DST switch, the first one is taken
+#[case::dst_overlap("2026-10-25T02:30:00", "Europe/Berlin", "2026-10-25T00:30:00Z")]
+fn test_parse_timestamp_in_timezone(#[case] value: &str, #[case] tz: &str, #[case] expected: &str) {
+    let tz: Tz = tz.parse().unwrap();
+    let expected = chrono::DateTime::parse_from_rfc3339(expected)
+        .ok()
+        .map(|dt| dt.to_utc());
+
+    assert_eq!(parse_timestamp(value, tz), expected);
+}
+
+#[test]
+fn test_format_timestamp_in_timezone() {
+    let dt = parse_timestamp("2026-07-01T12:00:00Z", Tz::UTC).unwrap();
+
+    assert_eq!(
+        format_timestamp(&dt, "Europe/Moscow".parse().unwrap()),
+        "2026-07-01T15:00:00.000+03:00"
+    );
+    // Summer time is applied by the date, not by the current season
+    assert_eq!(
+        format_timestamp(&dt, "Europe/Berlin".parse().unwrap()),
+        "2026-07-01T14:00:00.000+02:00"
+    );
+}
+
+#[rstest]
+#[case::default(None, Some(Tz::UTC))]
+#[case::iana(Some("Asia/Yekaterinburg"), Some(Tz::Asia__Yekaterinburg))]
+#[case::unknown(Some("Mars/Olympus"), None)]
+#[case::abbreviation(Some("MSK"), None)]
+fn test_parse_timezone(#[case] value: Option<&str>, #[case] expected: Option<Tz>) {
+    assert_eq!(parse_timezone(value).ok(), expected);
+}
+
+#[rstest]
+#[case::fits_i32(3_000_000_000, Some(3_000_000_000))]
+#[case::fits_i64(i64::MAX as u64, Some(i64::MAX))]
+#[case::too_large(i64::MAX as u64 + 1, None)]
+fn test_post_counters(#[case] views: u64, #[case] expected: Option<i64>) {
+    let stats = VkPostStats {
+        comments_count: 1,
+        likes_count: 2,
+        views_count: views,
+        reposts_count: 3,
+        ..Default::default()
+    };
+
+    let counters = post_counters(&stats);
+    assert_eq!(counters.as_ref().ok().map(|c| c.views_count), expected);
+    if let Err(e) = counters {
+        assert!(e.contains("views_count"), "Unexpected error: {}", e);
+    }
+}
+
+#[rstest]
+#[case::missing(None, Some(None))]
+#[case::uuid(
+    Some("0b7c6f7e-3c1d-4c4e-9f0a-2d5c1b7e8a90"),
+    Some(Some("0b7c6f7e-3c1d-4c4e-9f0a-2d5c1b7e8a90"))
+)]
+#[case::padded(Some("  retry-1 "), Some(Some("retry-1")))]
+#[case::blank(Some("   "), None)]
+fn test_parse_idempotency_key(#[case] value: Option<&str>, #[case] expected: Option<Option<&str>>) {
+    let key = parse_idempotency_key(value).ok();
+    assert_eq!(key.as_ref().map(|key| key.as_deref()), expected);
+}
@@ -0,0 +1,517 @@
+use rstest::rstest;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+
+use errors::{AppError, VkError};
+use models::{AudienceKind, TokenRotation};
+use token_pool::{TokenPool, TokenStatus};
+use vk_api::{
+    ReqwestVkClient, RetryPolicy, VkClient, parse_audience_page, parse_comments_page,
+    parse_extra_metrics, parse_post_meta, parse_post_stats, parse_wall_page,
+};
+
+// Scripted reply of the stub VK server
+enum StubReply {
+    Json(&'static str),
+    // Close the connection without answering, like a network failure
+    Drop,
+}
+
+const POST_RESPONSE: &str = r#"{"response":{"items":[{"owner_id":-1,"id":1,"likes":{"count":3},"comments":{"count":2},"reposts":{"count":1},"views":{"count":4}}]}}"#;
+const INTERNAL_ERROR: &str = r#"{"error":{"error_code":10,"error_msg":"Internal server error"}}"#;
+const TOO_MANY_REQUESTS: &str =
+    r#"{"error":{"error_code":6,"error_msg":"Too many requests per second"}}"#;
+const AUTH_FAILED: &str =
+    r#"{"error":{"error_code":5,"error_msg":"User authorization failed: invalid access_token"}}"#;
+const ACCESS_DENIED: &str = r#"{"error":{"error_code":15,"error_msg":"Access denied"}}"#;
+
+// Token the stub always rejects
+const BAD_TOKEN: &str = "bad-token";
+
+// Serves the replies in order, the last one is repeated; returns the request counter.
+// Requests with the bad token are rejected without taking a reply or being counted.
+async fn start_stub_vk(replies: Vec<StubReply>) -> Arc<AtomicUsize> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .await
+        .expect("Failed to bind stub server");
+    let address = listener.local_addr().unwrap();
+    let requests = Arc::new(AtomicUsize::new(0));
+
+    let counter = requests.clone();
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                return;
+            };
+            // GET requests have no body, the headers are enough
+            let mut buffer = vec![0; 4096];
+            let read = socket.read(&mut buffer).await.unwrap_or_default();
+            let request = String::from_utf8_lossy(&buffer[..read]);
+
+            let reply = if request.contains(&format!("access_token={}&", BAD_TOKEN)) {
+                &StubReply::Json(AUTH_FAILED)
+            } else {
+                let index = counter
+                    .fetch_add(1, Ordering::SeqCst)
+                    .min(replies.len() - 1);
+                &replies[index]
+            };
+
+            if let StubReply::Json(body) = reply {
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        }
+    });
+
+    configure_vk_env(&format!("http://{}/method/wall.getById", address));
+    requests
+}
+
+fn configure_vk_env(domain: &str) {
+    // SAFETY: tests run on a single thread (see .cargo/config.toml), nothing reads env concurrently
+    unsafe {
+        std::env::set_var("VK_API_DOMAIN", domain);
+        std::env::set_var("VK_TOKEN", "test-token");
+        std::env::set_var("VK_RATE_LIMIT_PER_SECOND", "100");
+        std::env::set_var("VK_RETRY_MAX_ATTEMPTS", "3");
+        std::env::set_var("VK_RETRY_BASE_DELAY_MS", "10");
+        std::env::set_var("VK_RETRY_MAX_DELAY_MS", "5000");
+    }
+}
+
+// Pool of its own for a test that revokes or rests tokens, the shared one stays untouched
+fn token_pool(tokens: &[&str], cooldown: Duration) -> &'static TokenPool {
+    let tokens = tokens
+        .iter()
+        .map(|token| (token.to_string(), 100.0))
+        .collect();
+    Box::leak(Box::new(TokenPool::new(
+        tokens,
+        TokenRotation::RoundRobin,
+        cooldown,
+    )))
+}
+
+fn post_ids() -> Vec<String> {
+    vec!["-1_1".to_string()]
+}
+
+#[tokio::test]
+async fn test_retries_retryable_vk_error() {
+    let requests = start_stub_vk(vec![
+        StubReply::Json(INTERNAL_ERROR),
+        StubReply::Json(POST_RESPONSE),
+    ])
+    .await;
+
+    let batch = ReqwestVkClient::default()
+        .call_vk_batch(&post_ids())
+        .await
+        .expect("Call should succeed after a retry");
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(batch.stats["-1_1"].likes_count, 3);
+}
+
+#[tokio::test]
+async fn test_retries_network_failure() {
+    let requests = start_stub_vk(vec![StubReply::Drop, StubReply::Json(POST_RESPONSE)]).await;
+
+    let batch = ReqwestVkClient::default()
+        .call_vk_batch(&post_ids())
+        .await
+        .expect("Call should succeed after a retry");
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert_eq!(batch.stats["-1_1"].views_count, 4);
+}
+
+#[tokio::test]
+async fn test_gives_up_after_max_attempts() {
+    let requests = start_stub_vk(vec![StubReply::Json(INTERNAL_ERROR)]).await;
+
+    let result = ReqwestVkClient::default().call_vk_batch(&post_ids()).await;
+
+    assert!(matches!(result, Err(AppError::VkApi(VkError::Internal(_)))));
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_network_error_does_not_leak_token() {
+    let requests = start_stub_vk(vec![StubReply::Drop]).await;
+
+    let Err(error) = ReqwestVkClient::default().call_vk_batch(&post_ids()).await else {
+        panic!("Every attempt should fail");
+    };
+
+    assert_eq!(requests.load(Ordering::SeqCst), 3);
+    assert!(matches!(error, AppError::Vk(_)));
+    let body = error.body();
+    assert_eq!(body.code, "vk_error");
+    assert!(
+        !body.message.contains("test-token") && !body.message.contains("access_token"),
+        "Error exposes the token: {}",
+        body.message
+    );
+}
+
+#[tokio::test]
+async fn test_does_not_retry_fatal_error() {
+    let requests = start_stub_vk(vec![
+        StubReply::Json(AUTH_FAILED),
+        StubReply::Json(POST_RESPONSE),
+    ])
+    .await;
+
+    // The only token is rejected, so there is nothing to switch to
+    let client = ReqwestVkClient::with_tokens(token_pool(&["test-token"], Duration::from_secs(60)));
+    let result = client.call_vk_batch(&post_ids()).await;
+
+    assert!(matches!(
+        result,
+        Err(AppError::VkApi(VkError::AuthFailed(_)))
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_does_not_retry_post_error() {
+    let requests = start_stub_vk(vec![
+        StubReply::Json(ACCESS_DENIED),
+        StubReply::Json(POST_RESPONSE),
+    ])
+    .await;
+
+    let result = ReqwestVkClient::default().call_vk_batch(&post_ids()).await;
+
+    assert!(matches!(
+        result,
+        Err(AppError::VkApi(VkError::AccessDenied(_)))
+    ));
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_rate_limit_waits_for_next_window() {
+    let requests = start_stub_vk(vec![
+        StubReply::Json(TOO_MANY_REQUESTS),
+        StubReply::Json(POST_RESPONSE),
+    ])
+    .await;
+
+    let started = Instant::now();
+    ReqwestVkClient::default()
+        .call_vk_batch(&post_ids())
+        .await
+        .expect("Call should succeed after a retry");
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert!(
+        started.elapsed() >= Duration::from_secs(1),
+        "Rate-limited call was retried within the same second"
+    );
+}
+
+#[tokio::test]
+async fn test_rate_limited_single_token_is_retried_with_default_cooldown() {
+    let requests = start_stub_vk(vec![
+        StubReply::Json(TOO_MANY_REQUESTS),
+        StubReply::Json(POST_RESPONSE),
+    ])
+    .await;
+
+    // SAFETY: tests run on a single thread (see .cargo/config.toml), nothing reads env concurrently
+    unsafe { std::env::remove_var("VK_TOKEN_COOLDOWN_SECONDS") };
+    let cooldown = Duration::from_secs(utils::get_vk_token_cooldown_seconds());
+    let client = ReqwestVkClient::with_tokens(token_pool(&["test-token"], cooldown));
+
+    let started = Instant::now();
+    client
+        .call_vk_batch(&post_ids())
+        .await
+        .expect("The only token should be back after the rate-limit window");
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_waits_for_token_cooldown() {
+    let requests = start_stub_vk(vec![StubReply::Json(POST_RESPONSE)]).await;
+
+    // Another request has just put the only token into a short cooldown
+    let tokens = token_pool(&["test-token"], Duration::from_millis(300));
+    let lease = tokens.checkout().unwrap();
+    tokens.report::<()>(
+        &lease,
+        &Err(VkError::FloodControl("Flood control".to_string()).into()),
+    );
+
+    let started = Instant::now();
+    ReqwestVkClient::with_tokens(tokens)
+        .call_vk_batch(&post_ids())
+        .await
+        .expect("Call should wait for the token instead of failing");
+
+    assert_eq!(requests.load(Ordering::SeqCst), 1);
+    assert!(started.elapsed() >= Duration::from_millis(250));
+}
+
+#[tokio::test]
+async fn test_rejected_token_is_replaced() {
+    let requests = start_stub_vk(vec![StubReply::Json(POST_RESPONSE)]).await;
+
+    // Round-robin hands out the bad token within two calls unless it is revoked already
+    let tokens = token_pool(&[BAD_TOKEN, "test-token"], Duration::from_secs(60));
+    for _ in 0..2 {
+        ReqwestVkClient::with_tokens(tokens)
+            .call_vk_batch(&post_ids())
+            .await
+            .expect("Call should switch to the working token");
+    }
+
+    assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+    let stats = tokens.stats();
+    assert_eq!(stats[0].status, TokenStatus::Revoked);
+    assert_eq!(stats[1].status, TokenStatus::Active);
+}
+
+#[test]
+fn test_retry_delay_grows_exponentially_up_to_max() {
+    let policy = RetryPolicy {
+        max_attempts: 10,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_millis(1000),
+    };
+    let error = AppError::Vk("Request failed".to_string());
+
+    for (attempt, backoff_ms) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (9, 1000)] {
+        let delay = policy.delay(attempt, &error);
+        let backoff = Duration::from_millis(backoff_ms);
+
+        assert!(
+            delay >= backoff / 2 && delay <= backoff,
+            "Delay {:?} for attempt {} is outside of [{:?}, {:?}]",
+            delay,
+            attempt,
+            backoff / 2,
+            backoff
+        );
+    }
+}
+
+#[test]
+fn test_parse_post_stats() {
+    let post = serde_json::json!({
+        "comments": {"count": 1},
+        "likes": {"count": 2},
+        "reposts": {"count": 3},
+        "views": {"count": 3_000_000_000_i64}
+    });
+
+    let stats = parse_post_stats(&post).expect("Stats should be valid");
+    assert_eq!(
+        (
+            stats.comments_count,
+            stats.likes_count,
+            stats.reposts_count,
+            stats.views_count
+        ),
+        (1, 2, 3, 3_000_000_000)
+    );
+
+    // Old posts have no views at all
+    let post = serde_json::json!({"likes": {"count": 2}});
+    assert_eq!(parse_post_stats(&post).unwrap().views_count, 0);
+}
+
+#[rstest]
+#[case::negative(r#"{"views": {"count": -1}}"#)]
+#[case::above_i64(r#"{"views": {"count": 9223372036854775808}}"#)]
+#[case::float(r#"{"likes": {"count": 1.5}}"#)]
+#[case::string(r#"{"reposts": {"count": "7"}}"#)]
+fn test_parse_post_stats_rejects_invalid_counter(#[case] post: &str) {
+    let post: serde_json::Value = serde_json::from_str(post).unwrap();
+
+    let error = parse_post_stats(&post).expect_err("Counter should be rejected");
+    assert!(error.contains("Invalid"), "{}", error);
+}
+
+#[test]
+fn test_parse_post_meta() {
+    let post: serde_json::Value = serde_json::from_str(
+        r#"{"owner_id":-1,"id":1,"from_id":7,"date":1767258000,"edited":1767261600,"post_type":"copy","text":"Hello","attachments":[{"type":"photo"}]}"#,
+    )
+    .unwrap();
+
+    let meta = parse_post_meta(&post).expect("Post should have metadata");
+    assert_eq!(meta.owner_id, -1);
+    assert_eq!(meta.from_id, 7);
+    assert_eq!(meta.published_at.to_rfc3339(), "2026-01-01T09:00:00+00:00");
+    assert_eq!(
+        meta.edited_at.unwrap().to_rfc3339(),
+        "2026-01-01T10:00:00+00:00"
+    );
+    assert_eq!(meta.post_type, "copy");
+    assert_eq!(meta.text, "Hello");
+    assert_eq!(meta.attachments[0]["type"], "photo");
+    assert!(!meta.is_deleted);
+
+    // Without a publish date there is nothing to version
+    let post: serde_json::Value = serde_json::from_str(POST_RESPONSE).unwrap();
+    assert!(parse_post_meta(&post["response"]["items"][0]).is_none());
+}
+
+#[test]
+fn test_parse_extra_metrics() {
+    let post: serde_json::Value = serde_json::from_str(
+        r#"{
+            "owner_id": -1, "id": 1, "edited": 1767261600,
+            "reposts": {"count": 5, "wall_count": 3, "mail_count": 2},
+            "comments": {"count": 0, "can_post": 0},
+            "donut": {"is_donut": true},
+            "attachments": [
+                {"type": "video", "video": {"views": 1000}},
+                {"type": "photo", "photo": {"id": 1}},
+                {"type": "video", "video": {"views": 500}}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let metrics = parse_extra_metrics(&post);
+    let expected: Vec<(&str, i64)> = vec![
+        ("can_comment", 0),
+        ("edited_at", 1767261600),
+        ("is_donut", 1),
+        ("reposts_mail", 2),
+        ("reposts_wall", 3),
+        ("video_views", 1500),
+    ];
+    assert_eq!(
+        metrics
+            .iter()
+            .map(|(name, value)| (name.as_str(), *value))
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    // Fields VK did not send are left out instead of being zero
+    let post: serde_json::Value = serde_json::from_str(POST_RESPONSE).unwrap();
+    assert!(parse_extra_metrics(&post["response"]["items"][0]).is_empty());
+}
+
+#[test]
+fn test_parse_comments_page() {
+    let json_data: serde_json::Value = serde_json::from_str(
+        r#"{"response":{"count":5,"current_level_count":3,"items":[
+            {"id":11,"from_id":7,"date":1767258000,"text":"First","likes":{"count":2}},
+            {"id":12,"deleted":true},
+            {"id":13,"from_id":8,"date":1767258060,"text":"Reply","reply_to_comment":11,"thread":{"count":0}}
+        ]}}"#,
+    )
+    .unwrap();
+
+    let page = parse_comments_page(&json_data, Some(10));
+    assert_eq!(page.total, 3);
+    // The deleted comment still counts for the offset of the next page
+    assert_eq!(page.raw_count, 3);
+
+    let comments: Vec<(i64, Option<i64>, i64)> = page
+        .comments
+        .iter()
+        .map(|comment| (comment.id, comment.parent_id, comment.likes_count))
+        .collect();
+    // Deleted comments are left out, a reply without a target belongs to the thread
+    assert_eq!(comments, vec![(11, Some(10), 2), (13, Some(11), 0)]);
+    assert_eq!(page.comments[0].text, "First");
+}
+
+#[test]
+fn test_parse_audience_page() {
+    let likes: serde_json::Value =
+        serde_json::from_str(r#"{"response":{"count":1200,"items":[5,6,7]}}"#).unwrap();
+
+    let page = parse_audience_page(&likes, AudienceKind::Like);
+    assert_eq!(page.total, Some(1200));
+    let users: Vec<i64> = page.entries.iter().map(|entry| entry.user_id).collect();
+    assert_eq!(users, vec![5, 6, 7]);
+    assert!(page.entries.iter().all(|entry| entry.engaged_at.is_none()));
+
+    let reposts: serde_json::Value = serde_json::from_str(
+        r#"{"response":{"items":[
+            {"id":3,"owner_id":9,"from_id":9,"date":1767258000},
+            {"id":4,"owner_id":-20,"date":1767258060},
+            {"id":5}
+        ],"profiles":[],"groups":[]}}"#,
+    )
+    .unwrap();
+
+    // Reposts come without a total, a community repost has no from_id
+    let page = parse_audience_page(&reposts, AudienceKind::Repost);
+    assert_eq!(page.total, None);
+    // A repost without an author is left out but still counts for the next offset
+    assert_eq!(page.raw_count, 3);
+    let users: Vec<(i64, i64)> = page
+        .entries
+        .iter()
+        .map(|entry| (entry.user_id, entry.engaged_at.unwrap().timestamp()))
+        .collect();
+    assert_eq!(users, vec![(9, 1767258000), (-20, 1767258060)]);
+}
+
+#[test]
+fn test_parse_wall_page() {
+    let json_data: serde_json::Value = serde_json::from_str(
+        r#"{"response":{"count":250,"items":[
+            {"id":30,"owner_id":-1,"date":1767258000,"text":"Pinned","is_pinned":1},
+            {"id":31,"owner_id":-1,"date":1767258060,"text":"Latest"},
+            {"id":32,"date":1767258120}
+        ]}}"#,
+    )
+    .unwrap();
+
+    let page = parse_wall_page(&json_data);
+    assert_eq!(page.total, 250);
+    assert_eq!(page.raw_count, 3);
+
+    // An item without an owner can't be enrolled, so it is left out
+    let posts: Vec<(&str, bool, &str)> = page
+        .posts
+        .iter()
+        .map(|post| (post.vk_id.as_str(), post.is_pinned, post.text.as_str()))
+        .collect();
+    assert_eq!(
+        posts,
+        vec![("-1_30", true, "Pinned"), ("-1_31", false, "Latest")]
+    );
+    assert_eq!(page.posts[1].published_at.timestamp(), 1767258060);
+}
@@ -0,0 +1,79 @@
+use rstest::rstest;
+
+#[allow(dead_code)]
+#[path = "../src/vk_link.rs"]
+mod vk_link;
+
+use vk_link::{parse_vk_owner_id, parse_vk_post_link};
+
+#[rstest]
+#[case::canonical("https://vk.com/wall-38894284_2277607", "-38894284_2277607")]
+#[case::user_wall("https://vk.com/wall1_45", "1_45")]
+#[case::vk_ru("https://vk.ru/wall-1_2", "-1_2")]
+#[case::mobile("https://m.vk.com/wall-1_2", "-1_2")]
+#[case::mobile_vk_ru("https://m.vk.ru/wall-1_2", "-1_2")]
+#[case::www("https://www.vk.com/wall-1_2", "-1_2")]
+#[case::http("http://vk.com/wall-1_2", "-1_2")]
+#[case::no_scheme("vk.com/wall-1_2", "-1_2")]
+#[case::upper_case_host("https://VK.com/wall-1_2", "-1_2")]
+#[case::trailing_slash("https://vk.com/wall-1_2/", "-1_2")]
+#[case::query("https://vk.com/wall-1_2?reply=15&thread=3", "-1_2")]
+#[case::fragment("https://vk.com/wall-1_2#comments", "-1_2")]
+#[case::feed_w("https://vk.com/feed?w=wall-1_2", "-1_2")]
+#[case::community_w("https://vk.com/club1?w=wall-1_2", "-1_2")]
+#[case::w_after_other_params("https://vk.com/public1?z=photo&w=wall-1_2#top", "-1_2")]
+#[case::bare_id("-123_456", "-123_456")]
+#[case::bare_user_id("123_456", "123_456")]
+#[case::surrounding_spaces("  https://vk.com/wall-1_2\n", "-1_2")]
+#[case::leading_zeros("https://vk.com/wall-0001_002", "-1_2")]
+fn test_parse_vk_post_link(#[case] link: &str, #[case] expected: &str) {
+    assert_eq!(parse_vk_post_link(link), Ok(expected.to_string()));
+}
+
+#[rstest]
+#[case::empty("")]
+#[case::other_domain("https://invalid.com/wall-1_2")]
+#[case::lookalike_domain("https://vk.com.evil.org/wall-1_2")]
+#[case::not_a_post("https://vk.com/post123")]
+#[case::community_page("https://vk.com/club1")]
+#[case::photo_w("https://vk.com/feed?w=photo-1_2")]
+#[case::no_post_id("https://vk.com/wall-1")]
+#[case::empty_post_id("https://vk.com/wall-1_")]
+#[case::zero_owner("https://vk.com/wall0_2")]
+#[case::zero_post("-1_0")]
+#[case::negative_post("-1_-2")]
+#[case::letters("https://vk.com/wall-1_2a")]
+#[case::plus_sign("+1_2")]
+#[case::extra_part("-1_2_3")]
+#[case::overflow("-99999999999999999999_1")]
+#[case::other_scheme("ftp://vk.com/wall-1_2")]
+fn test_parse_vk_post_link_rejects(#[case] link: &str) {
+    let error = parse_vk_post_link(link).expect_err("link should be rejected");
+    assert!(error.contains("Invalid VK link format"), "{}", error);
+}
+
+#[rstest]
+#[case::community("-38894284", -38894284)]
+#[case::user("1", 1)]
+#[case::club("club38894284", -38894284)]
+#[case::public("public1", -1)]
+#[case::upper_case_prefix("Club1", -1)]
+#[case::leading_zeros("-0001", -1)]
+#[case::surrounding_spaces(" -1 ", -1)]
+fn test_parse_vk_owner_id(#[case] owner: &str, #[case] expected: i64) {
+    assert_eq!(parse_vk_owner_id(owner), Ok(expected));
+}
+
+#[rstest]
+#[case::empty("")]
+#[case::zero("0")]
+#[case::letters("durov")]
+#[case::signed_club("club-1")]
+#[case::club_without_id("club")]
+#[case::plus_sign("+1")]
+#[case::post_id("-1_2")]
+#[case::overflow("-99999999999999999999")]
+fn test_parse_vk_owner_id_rejects(#[case] owner: &str) {
+    let error = parse_vk_owner_id(owner).expect_err("owner should be rejected");
+    assert!(error.contains("Invalid VK owner"), "{}", error);
+}
@@ -0,0 +1,185 @@
+#[macro_use]
+extern crate rocket;
+
+use rocket::http::{ContentType, Status};
+use rocket::local::blocking::Client;
+use rstest::rstest;
+use serde_json::{Value, json};
+use std::sync::Arc;
+
+// Include all necessary modules for testing
+#[allow(dead_code)]
+#[path = "../src/clock.rs"]
+mod clock;
+#[allow(dead_code)]
+#[path = "../src/db_commands.rs"]
+mod db_commands;
+#[allow(dead_code)]
+#[path = "../src/errors.rs"]
+mod errors;
+#[allow(dead_code)]
+#[path = "../src/fake_vk_client.rs"]
+mod fake_vk_client;
+#[allow(dead_code)]
+#[path = "../src/manual_scheduler.rs"]
+mod manual_scheduler;
+#[allow(dead_code)]
+#[path = "../src/models.rs"]
+mod models;
+#[allow(dead_code)]
+#[path = "../src/rate_limiter.rs"]
+mod rate_limiter;
+#[allow(dead_code)]
+#[path = "../src/scheduler.rs"]
+mod scheduler;
+#[allow(dead_code)]
+#[path = "../src/tasks.rs"]
+mod tasks;
+#[allow(dead_code)]
+#[path = "../src/token_pool.rs"]
+mod token_pool;
+#[allow(dead_code)]
+#[path = "../src/utils.rs"]
+mod utils;
+#[allow(dead_code)]
+#[path = "../src/vk_api.rs"]
+mod vk_api;
+#[allow(dead_code)]
+#[path = "../src/vk_link.rs"]
+mod vk_link;
+
+#[allow(dead_code)]
+#[path = "../src/endpoints.rs"]
+mod endpoints;
+
+use clock::{Clock, SystemClock};
+use endpoints::{create_watcher, delete_watcher, list_watchers};
+use errors::VkError;
+use fake_vk_client::FakeVkClient;
+use vk_api::VkClient;
+
+mod test_utils;
+use test_utils::setup_test_db;
+
+// Every wall is empty, except the closed one
+fn create_test_rocket(pool: sqlx::PgPool) -> rocket::Rocket<rocket::Build> {
+    let vk = FakeVkClient::new();
+    vk.set_wall_error(
+        -403,
+        VkError::AccessDenied("Access to the wall denied".to_string()),
+    );
+    let vk: Arc<dyn VkClient> = Arc::new(vk);
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+
+    rocket::build()
+        .manage(Arc::new(pool))
+        .manage(clock)
+        .manage(vk)
+        .mount(
+            "/",
+            rocket::routes![create_watcher, list_watchers, delete_watcher],
+        )
+}
+
+fn create(client: &Client, request: Value) -> (Status, Value) {
+    let response = client
+        .post("/watchers")
+        .header(ContentType::JSON)
+        .body(request.to_string())
+        .dispatch();
+    let status = response.status();
+
+    (
+        status,
+        serde_json::from_str(&response.into_string().unwrap()).unwrap_or(Value::Null),
+    )
+}
+
+#[test]
+fn test_create_list_and_delete_watcher() {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let (status, watcher) = create(
+        &client,
+        json!({
+            "owner_id": -38894284,
+            "interval": "PT10M",
+            "duration": 3600,
+            "min_age": "PT5M",
+            "keywords": [" Выборы ", ""],
+            "track_audience": true
+        }),
+    );
+    assert_eq!(status, Status::Ok);
+    assert_eq!(watcher["owner_id"], -38894284);
+    assert_eq!(watcher["interval_seconds"], 600);
+    assert_eq!(watcher["duration_seconds"], 3600);
+    assert_eq!(watcher["min_age_seconds"], 300);
+    assert_eq!(watcher["include_pinned"], false);
+    assert_eq!(watcher["track_audience"], true);
+    // Keywords are trimmed, empty ones are dropped
+    assert_eq!(watcher["keywords"], json!(["Выборы"]));
+    assert_eq!(watcher["checked_at"], Value::Null);
+
+    let (status, _) = create(&client, json!({"owner_id": 1}));
+    assert_eq!(status, Status::Ok);
+
+    let response = client.get("/watchers").dispatch();
+    assert_eq!(response.status(), Status::Ok);
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    let owners: Vec<i64> = body["watchers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|watcher| watcher["owner_id"].as_i64().unwrap())
+        .collect();
+    assert_eq!(owners, vec![-38894284, 1]);
+
+    let watcher_id = watcher["watcher_id"].as_i64().unwrap();
+    let response = client
+        .delete(format!("/watchers?watcher_id={}", watcher_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::Ok);
+
+    let response = client
+        .delete(format!("/watchers?watcher_id={}", watcher_id))
+        .dispatch();
+    assert_eq!(response.status(), Status::NotFound);
+
+    let response = client.get("/watchers").dispatch();
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert_eq!(body["watchers"].as_array().unwrap().len(), 1);
+}
+
+#[rstest]
+#[case::zero_owner(json!({"owner_id": 0}), Status::BadRequest, "Invalid owner_id")]
+#[case::zero_interval(json!({"owner_id": -1, "interval": 0}), Status::BadRequest, "Interval must be positive")]
+#[case::negative_min_age(json!({"owner_id": -1, "min_age": -60}), Status::BadRequest, "Minimum age can't be negative")]
+#[case::too_long(json!({"owner_id": -1, "duration": "P3650D"}), Status::BadRequest, "Duration must be between")]
+#[case::closed_wall(json!({"owner_id": -403}), Status::Forbidden, "Access to the wall denied")]
+fn test_create_watcher_rejected(
+    #[case] request: Value,
+    #[case] expected_status: Status,
+    #[case] expected_message: &str,
+) {
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(setup_test_db());
+    let client = Client::tracked(create_test_rocket(pool)).expect("valid rocket instance");
+
+    let (status, body) = create(&client, request);
+    assert_eq!(status, expected_status);
+    assert!(
+        body["error"]["message"]
+            .as_str()
+            .unwrap_or_default()
+            .contains(expected_message),
+        "Unexpected error body: {}",
+        body
+    );
+
+    let response = client.get("/watchers").dispatch();
+    let body: Value = serde_json::from_str(&response.into_string().unwrap()).unwrap();
+    assert!(body["watchers"].as_array().unwrap().is_empty());
+}